@@ -0,0 +1,13 @@
+-- migrations/{}_create_unsubscribe_tokens_table.sql
+
+CREATE TABLE unsubscribe_tokens(
+    unsubscribe_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    PRIMARY KEY (unsubscribe_token)
+);
+
+-- existing subscribers need a token to appear in newsletter headers
+INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id)
+SELECT substr(md5(random()::text || id::text), 1, 25), id
+FROM subscriptions;
@@ -0,0 +1,7 @@
+-- migrations/{}_add_send_at_to_newsletter_issues.sql
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz;
+UPDATE newsletter_issues SET send_at = published_at;
+ALTER TABLE newsletter_issues ALTER COLUMN send_at SET NOT NULL;
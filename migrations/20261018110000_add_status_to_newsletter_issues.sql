@@ -0,0 +1,10 @@
+-- migrations/{}_add_status_to_newsletter_issues.sql
+
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT;
+UPDATE newsletter_issues
+    SET status = CASE WHEN send_at > now() THEN 'scheduled' ELSE 'sent' END;
+ALTER TABLE newsletter_issues ALTER COLUMN status SET NOT NULL;
+
+-- drafts are neither published nor scheduled yet
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN send_at DROP NOT NULL;
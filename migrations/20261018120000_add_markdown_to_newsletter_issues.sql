@@ -0,0 +1,3 @@
+-- migrations/{}_add_markdown_to_newsletter_issues.sql
+
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT;
@@ -0,0 +1,13 @@
+-- migrations/{}_create_api_tokens_table.sql
+
+CREATE TABLE api_tokens (
+    token_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL
+      REFERENCES users (user_id),
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL
+);
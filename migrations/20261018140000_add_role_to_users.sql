@@ -0,0 +1,9 @@
+-- migrations/{}_add_role_to_users.sql
+
+BEGIN;
+    ALTER TABLE users ADD COLUMN role TEXT NULL;
+    -- whoever was already here set the instance up
+    UPDATE users SET role = 'owner';
+    ALTER TABLE users ALTER COLUMN role SET NOT NULL;
+    ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT false;
+COMMIT;
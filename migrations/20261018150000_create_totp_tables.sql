@@ -0,0 +1,18 @@
+-- migrations/{}_create_totp_tables.sql
+
+CREATE TABLE totp_credentials (
+    user_id uuid PRIMARY KEY
+      REFERENCES users (user_id),
+    encrypted_secret BYTEA NOT NULL,
+    -- enrollment is pending until a first code has been checked
+    confirmed BOOLEAN NOT NULL,
+    last_used_step BIGINT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE totp_recovery_codes (
+    user_id uuid NOT NULL
+      REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL
+);
@@ -0,0 +1,10 @@
+-- migrations/{}_create_audit_log_table.sql
+
+CREATE TABLE audit_log (
+    audit_id uuid PRIMARY KEY,
+    occurred_at timestamptz NOT NULL,
+    action TEXT NOT NULL,
+    username TEXT NULL,
+    ip_address TEXT NULL,
+    details TEXT NOT NULL
+);
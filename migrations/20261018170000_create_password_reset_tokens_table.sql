@@ -0,0 +1,14 @@
+-- migrations/{}_create_password_reset_tokens_table.sql
+
+-- where password reset links are sent, users without one cannot reset
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+
+CREATE TABLE password_reset_tokens (
+    -- only a SHA-256 digest of the emailed token is kept
+    token_hash TEXT PRIMARY KEY,
+    user_id uuid NOT NULL
+      REFERENCES users (user_id),
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL
+);
@@ -0,0 +1,3 @@
+-- migrations/{}_add_request_fingerprint_to_idempotency.sql
+-- NULL for keys saved before fingerprints were recorded: they match any payload
+ALTER TABLE idempotency ADD COLUMN request_fingerprint TEXT NULL;
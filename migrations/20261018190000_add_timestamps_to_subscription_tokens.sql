@@ -0,0 +1,4 @@
+-- migrations/{}_add_timestamps_to_subscription_tokens.sql
+-- tokens issued before this migration get a full lifetime from now on
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE subscription_tokens ADD COLUMN consumed_at timestamptz NULL;
@@ -0,0 +1,3 @@
+-- migrations/{}_add_subscribed_at_index_to_subscriptions.sql
+-- the admin console pages through subscribers newest first
+CREATE INDEX subscriptions_subscribed_at_id_idx ON subscriptions (subscribed_at DESC, id DESC);
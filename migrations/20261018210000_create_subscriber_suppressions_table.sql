@@ -0,0 +1,6 @@
+-- migrations/{}_create_subscriber_suppressions_table.sql
+-- addresses whose data was erased, only a digest is kept
+CREATE TABLE subscriber_suppressions (
+    email_hash TEXT PRIMARY KEY,
+    suppressed_at timestamptz NOT NULL
+);
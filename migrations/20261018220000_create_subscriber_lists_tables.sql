@@ -0,0 +1,30 @@
+-- migrations/{}_create_subscriber_lists_tables.sql
+
+CREATE TABLE subscriber_lists (
+    list_id uuid PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_list_memberships (
+    list_id uuid NOT NULL
+        REFERENCES subscriber_lists (list_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+
+-- who an issue goes to, every confirmed subscriber when all are left empty
+ALTER TABLE newsletter_issues
+    ADD COLUMN list_id uuid NULL REFERENCES subscriber_lists (list_id) ON DELETE SET NULL,
+    ADD COLUMN segment_signed_up_from date NULL,
+    ADD COLUMN segment_signed_up_to date NULL,
+    ADD COLUMN segment_tag TEXT NULL,
+    ADD COLUMN segment_email_domain TEXT NULL;
@@ -0,0 +1,11 @@
+-- migrations/{}_add_publishing_status_to_newsletter_issues.sql
+
+-- an issue is 'publishing' until its delivery queue has drained
+UPDATE newsletter_issues n
+    SET status = 'publishing'
+    WHERE status = 'sent' AND EXISTS (
+        SELECT 1 FROM issue_delivery_queue q
+        WHERE q.newsletter_issue_id = n.newsletter_issue_id
+    );
+ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_status_check
+    CHECK (status IN ('draft', 'scheduled', 'publishing', 'sent'));
@@ -0,0 +1,4 @@
+-- migrations/{}_drop_user_reference_from_idempotency.sql
+-- keys sent by anonymous callers of public endpoints are stored under a
+-- namespace of their own, the nil uuid, which matches no user
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_user_id_fkey;
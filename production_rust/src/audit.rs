@@ -0,0 +1,34 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A security-relevant event, kept in `audit_log` for later review.
+#[derive(Debug)]
+pub struct AuditEvent<'a> {
+    pub action: &'a str,
+    pub username: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub details: String,
+}
+
+#[tracing::instrument(name = "Record an audit event", skip(connection_pool))]
+pub async fn record_audit_event(
+    event: AuditEvent<'_>,
+    connection_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (audit_id, occurred_at, action, username, ip_address, details)
+        VALUES ($1, now(), $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        event.action,
+        event.username,
+        event.ip_address,
+        event.details,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to record an audit event.")?;
+    Ok(())
+}
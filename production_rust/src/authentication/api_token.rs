@@ -0,0 +1,206 @@
+use super::password::{compute_password_hash, validate_password_hash, PasswordHashing};
+use super::AuthError;
+use crate::telemetry::spawn_blocking_with_tracing;
+use actix_web::http::Method;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Every scope an API token can be granted.
+pub const API_TOKEN_SCOPES: &[&str] = &["issues:read", "issues:write", "subscribers:read"];
+
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// The scope a token needs to be let through to a route,
+/// `None` for routes that only a logged-in session may use.
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    let read_only = method == Method::GET;
+    let issue_route = matches!(
+        path,
+        "/admin/newsletter" | "/admin/drafts" | "/admin/issues"
+    ) || path.starts_with("/admin/issues/");
+    match path {
+        _ if issue_route && read_only => Some("issues:read"),
+        _ if issue_route => Some("issues:write"),
+        "/api/v1/subscribers" if read_only => Some("subscribers:read"),
+        _ => None,
+    }
+}
+
+/// Stores a new token for `user_id`, returning the only copy of its
+/// plain-text value: `{token_id}.{secret}`.
+#[tracing::instrument(name = "Create an API token", skip(password_hashing, connection_pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[String],
+    password_hashing: &PasswordHashing,
+    connection_pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    let token_id = Uuid::new_v4();
+    let secret = Secret::new(generate_token_secret());
+    let token_hash = {
+        let secret = secret.clone();
+        let params = password_hashing.params();
+        spawn_blocking_with_tracing(move || compute_password_hash(secret, params))
+            .await?
+            .context("Failed to hash API token")?
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (token_id, user_id, name, token_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        token_id,
+        user_id,
+        name,
+        token_hash.expose_secret(),
+        scopes,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the API token.")?;
+
+    Ok(Secret::new(format!(
+        "{}.{}",
+        token_id,
+        secret.expose_secret()
+    )))
+}
+
+/// Checks a plain-text token against the stored hash, returning
+/// the owner of the token and the scopes it was granted.
+#[tracing::instrument(name = "Validate API token", skip(token, connection_pool))]
+pub async fn validate_api_token(
+    token: Secret<String>,
+    connection_pool: &PgPool,
+) -> Result<(Uuid, Vec<String>), AuthError> {
+    let (token_id, secret) = token
+        .expose_secret()
+        .split_once('.')
+        .and_then(|(token_id, secret)| Some((Uuid::parse_str(token_id).ok()?, secret)))
+        .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Malformed API token.")))?;
+    let secret = Secret::new(secret.to_string());
+
+    let row = sqlx::query!(
+        r#"
+        SELECT t.user_id, t.token_hash, t.scopes
+        FROM api_tokens t
+        JOIN users u USING (user_id)
+        WHERE t.token_id = $1 AND t.revoked_at IS NULL AND NOT u.disabled
+        "#,
+        token_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve the API token.")?
+    .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown API token.")))?;
+
+    let token_hash = Secret::new(row.token_hash);
+    spawn_blocking_with_tracing(move || validate_password_hash(token_hash, secret))
+        .await
+        .context("Failed to spawn blocking task.")?
+        .await?;
+
+    sqlx::query!(
+        "UPDATE api_tokens SET last_used_at = now() WHERE token_id = $1",
+        token_id,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to record the use of the API token.")?;
+
+    Ok((row.user_id, row.scopes))
+}
+
+#[tracing::instrument(name = "Get API tokens", skip(connection_pool))]
+pub async fn get_api_tokens(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT token_id, name, scopes, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve API tokens.")?;
+    Ok(tokens)
+}
+
+/// Returns whether a token was revoked, tokens of other users are left alone.
+#[tracing::instrument(name = "Revoke an API token", skip(connection_pool))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    token_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_revoked = sqlx::query!(
+        r#"
+        UPDATE api_tokens SET revoked_at = now()
+        WHERE token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        token_id,
+        user_id,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to revoke the API token.")?
+    .rows_affected();
+    Ok(n_revoked > 0)
+}
+
+fn generate_token_secret() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::required_scope;
+    use actix_web::http::Method;
+
+    #[test]
+    fn reads_and_writes_need_different_scopes() {
+        assert_eq!(
+            required_scope(&Method::GET, "/admin/issues/preview"),
+            Some("issues:read")
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/admin/newsletter"),
+            Some("issues:write")
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/api/v1/subscribers"),
+            Some("subscribers:read")
+        );
+    }
+
+    #[test]
+    fn account_routes_are_not_available_to_tokens() {
+        assert_eq!(required_scope(&Method::GET, "/admin/tokens"), None);
+        assert_eq!(required_scope(&Method::POST, "/admin/password"), None);
+        assert_eq!(required_scope(&Method::GET, "/admin/issuesx"), None);
+    }
+}
@@ -1,10 +1,18 @@
+use crate::authentication::{
+    get_active_role, required_role, required_scope, validate_api_token, AuthError, SessionRegistry,
+};
+use crate::routes::api_error;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage};
 use actix_web_lab::middleware::Next;
+use secrecy::Secret;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -12,6 +20,11 @@ pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(user_id) = authenticate_bearer_token(&req).await? {
+        req.extensions_mut().insert(user_id);
+        return next.call(req).await;
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
@@ -19,6 +32,33 @@ pub async fn reject_anonymous_users(
 
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
+            let session_registry =
+                req.app_data::<web::Data<SessionRegistry>>()
+                    .ok_or_else(|| {
+                        e500("The session registry is missing from the application data.")
+                    })?;
+            // sessions are revoked by dropping them from the registry
+            let active_session_id = match session.get_session_id().map_err(e500)? {
+                Some(session_id)
+                    if session_registry
+                        .is_active(user_id, session_id)
+                        .await
+                        .map_err(e500)? =>
+                {
+                    Some(session_id)
+                }
+                _ => None,
+            };
+            let Some(session_id) = active_session_id else {
+                session.logout();
+                let response = see_other("/login");
+                let e = anyhow::anyhow!("The session has been revoked");
+                return Err(InternalError::from_response(e, response).into());
+            };
+            session_registry
+                .touch(user_id, session_id)
+                .await
+                .map_err(e500)?;
             req.extensions_mut().insert(UserId(user_id));
             next.call(req).await
         }
@@ -30,6 +70,112 @@ pub async fn reject_anonymous_users(
     }
 }
 
+// layered inside `reject_anonymous_users`, which provides the `UserId`
+pub async fn reject_unauthorized_roles(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let user_id = *req
+        .extensions()
+        .get::<UserId>()
+        .ok_or_else(|| e500("The user id is missing from the request."))?;
+    let connection_pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| e500("The connection pool is missing from the application data."))?
+        .clone();
+
+    let role = match get_active_role(*user_id, &connection_pool)
+        .await
+        .map_err(e500)?
+    {
+        Some(role) => role,
+        None => {
+            let session = {
+                let (http_request, payload) = req.parts_mut();
+                TypedSession::from_request(http_request, payload).await
+            }?;
+            session.logout();
+            let response = see_other("/login");
+            let e = anyhow::anyhow!("The user has been disabled or deleted");
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+
+    if role < required_role(req.method(), req.path()) {
+        let e = anyhow::anyhow!("The {} role cannot access {}", role, req.path());
+        return Err(InternalError::new(e, StatusCode::FORBIDDEN).into());
+    }
+    req.extensions_mut().insert(role);
+    next.call(req).await
+}
+
+// the `/api` counterpart of `reject_anonymous_users`: tokens only, JSON errors
+pub async fn reject_unauthorized_api_clients(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    match authenticate_bearer_token(&req).await? {
+        Some(user_id) => {
+            req.extensions_mut().insert(user_id);
+            next.call(req).await
+        }
+        None => {
+            let response = api_error(
+                StatusCode::UNAUTHORIZED,
+                "missing_token",
+                "An `Authorization: Bearer` API token is required.",
+            );
+            let e = anyhow::anyhow!("The request carries no API token");
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+/// Returns `None` when the request does not carry a bearer token at all,
+/// and fails when the token is invalid or lacks the scope of the route.
+async fn authenticate_bearer_token(
+    req: &ServiceRequest,
+) -> Result<Option<UserId>, actix_web::Error> {
+    let token = match req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(token) => Secret::new(token.trim().to_string()),
+        None => return Ok(None),
+    };
+    let connection_pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| e500("The connection pool is missing from the application data."))?;
+
+    let (user_id, scopes) = match validate_api_token(token, connection_pool).await {
+        Ok(token) => token,
+        Err(AuthError::InvalidCredentials(e)) => {
+            let response = api_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_token",
+                "The API token is invalid or has been revoked.",
+            );
+            return Err(InternalError::from_response(e, response).into());
+        }
+        Err(AuthError::UnexpectedError(e)) => return Err(e500(e)),
+    };
+
+    match required_scope(req.method(), req.path()) {
+        Some(scope) if scopes.iter().any(|s| s == scope) => Ok(Some(UserId(user_id))),
+        _ => {
+            let response = api_error(
+                StatusCode::FORBIDDEN,
+                "insufficient_scope",
+                "The API token does not grant access to this resource.",
+            );
+            let e = anyhow::anyhow!("The API token lacks the scope of {}", req.path());
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct UserId(Uuid);
 
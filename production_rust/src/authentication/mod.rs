@@ -1,5 +1,33 @@
+mod api_token;
 mod middleware;
 mod password;
-pub use middleware::reject_anonymous_users;
+mod password_policy;
+mod password_reset;
+mod role;
+mod session_registry;
+mod throttle;
+mod totp;
+pub use api_token::{
+    create_api_token, get_api_tokens, required_scope, revoke_api_token, validate_api_token,
+    ApiToken, API_TOKEN_SCOPES,
+};
 pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{
+    reject_anonymous_users, reject_unauthorized_api_clients, reject_unauthorized_roles,
+};
+pub use password::{
+    change_password, create_user, validate_credentials, AuthError, Credentials, PasswordHashing,
+};
+pub use password_policy::{PasswordPolicy, PasswordRejection};
+pub use password_reset::{
+    create_password_reset_token, get_recovery_email, is_valid_reset_token, reset_password,
+    set_recovery_email, PasswordResetRequest, RESET_TOKEN_LIFETIME_MINUTES,
+};
+pub use role::{get_active_role, required_role, Role};
+pub use session_registry::{ActiveSession, SessionMetadata, SessionRegistry};
+pub use throttle::{failure_delay, FailedLogin, LoginThrottle};
+pub use totp::{
+    base32_decode, base32_encode, begin_totp_enrollment, confirm_totp_enrollment, disable_totp,
+    get_totp_status, provisioning_uri, time_step, totp_code, verify_second_factor, TotpCipher,
+    TotpStatus,
+};
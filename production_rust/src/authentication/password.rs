@@ -1,7 +1,11 @@
+use super::Role;
+use crate::configuration::PasswordHashingSettings;
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
@@ -11,18 +15,66 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, connection_pool))]
+/// The Argon2 parameters of new hashes, along with a dummy hash computed
+/// with them: unknown usernames are checked against it so that they take
+/// as long to reject as wrong passwords.
+#[derive(Clone)]
+pub struct PasswordHashing {
+    params: Params,
+    dummy_hash: Secret<String>,
+}
+
+impl PasswordHashing {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let params = Params::new(
+            settings.memory_kib,
+            settings.iterations,
+            settings.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+        let dummy_password: String = thread_rng()
+            .sample_iter(Alphanumeric)
+            .map(char::from)
+            .take(32)
+            .collect();
+        let dummy_hash = compute_password_hash(Secret::new(dummy_password), params.clone())?;
+        Ok(Self { params, dummy_hash })
+    }
+
+    pub(super) fn params(&self) -> Params {
+        self.params.clone()
+    }
+
+    /// Whether `password_hash` was computed with weaker settings than the current ones.
+    pub fn needs_rehash(&self, password_hash: &str) -> bool {
+        let password_hash = match PasswordHash::new(password_hash) {
+            Ok(password_hash) => password_hash,
+            Err(_) => return true,
+        };
+        let params = match Params::try_from(&password_hash) {
+            Ok(params) => params,
+            Err(_) => return true,
+        };
+        password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+            || params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+    }
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, password_hashing, connection_pool)
+)]
 pub async fn validate_credentials(
     credentials: Credentials,
+    password_hashing: &PasswordHashing,
     connection_pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = password_hashing.dummy_hash.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, connection_pool).await?
@@ -31,6 +83,8 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
+    let stored_password_hash = expected_password_hash.clone();
+    let password = credentials.password.clone();
     spawn_blocking_with_tracing(move || {
         validate_password_hash(expected_password_hash, credentials.password)
     })
@@ -38,16 +92,69 @@ pub async fn validate_credentials(
     .context("Failed to spawn blocking task.")?
     .await?;
 
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    if password_hashing.needs_rehash(stored_password_hash.expose_secret()) {
+        // the password is right either way, a failed upgrade can wait for the next login
+        if let Err(e) = rehash_password(
+            user_id,
+            password,
+            stored_password_hash,
+            password_hashing,
+            connection_pool,
+        )
+        .await
+        {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to upgrade a password hash."
+            );
+        }
+    }
+    Ok(user_id)
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, previous_password_hash, password_hashing, connection_pool)
+)]
+async fn rehash_password(
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    previous_password_hash: Secret<String>,
+    password_hashing: &PasswordHashing,
+    connection_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let params = password_hashing.params();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+
+    // left alone if the password changed in the meantime
+    sqlx::query!(
+        r#"
+        UPDATE users SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        previous_password_hash.expose_secret(),
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the upgraded password hash.")?;
+    Ok(())
 }
 
 #[tracing::instrument(
     name = "Validate password hash",
     skip(expected_password_hash, password_candidate)
 )]
-async fn validate_password_hash(
+pub(super) async fn validate_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<(), AuthError> {
@@ -71,7 +178,7 @@ async fn get_stored_credentials(
     let row = sqlx::query!(
         r#"
         SELECT user_id, password_hash FROM users
-        WHERE username = $1
+        WHERE username = $1 AND NOT disabled
         "#,
         username,
     )
@@ -91,15 +198,21 @@ pub enum AuthError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
-#[tracing::instrument(name = "Change password", skip(password, connection_pool))]
+#[tracing::instrument(
+    name = "Change password",
+    skip(password, password_hashing, connection_pool)
+)]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
+    password_hashing: &PasswordHashing,
     connection_pool: &PgPool,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let params = password_hashing.params();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
 
     sqlx::query!(
         r#"
@@ -114,14 +227,88 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+#[tracing::instrument(
+    name = "Create user",
+    skip(password, password_hashing, connection_pool)
+)]
+pub async fn create_user(
+    username: &str,
+    password: Secret<String>,
+    role: Role,
+    password_hashing: &PasswordHashing,
+    connection_pool: &PgPool,
+) -> Result<uuid::Uuid, anyhow::Error> {
+    let params = password_hashing.params();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+
+    let user_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, role)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        role.as_str(),
     )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the new user in the database.")?;
+    Ok(user_id)
+}
+
+pub(super) fn compute_password_hash(
+    password: Secret<String>,
+    params: Params,
+) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordHashing;
+    use crate::configuration::PasswordHashingSettings;
+    use secrecy::ExposeSecret;
+
+    fn hashing(memory_kib: u32, iterations: u32) -> PasswordHashing {
+        PasswordHashing::new(&PasswordHashingSettings {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn weaker_hashes_need_a_rehash() {
+        let current = hashing(15000, 2);
+        assert!(current.needs_rehash(hashing(15000, 1).dummy_hash.expose_secret()));
+        assert!(current.needs_rehash(hashing(8192, 2).dummy_hash.expose_secret()));
+        assert!(current.needs_rehash("not a PHC string"));
+    }
+
+    #[test]
+    fn current_or_stronger_hashes_are_left_alone() {
+        let current = hashing(15000, 2);
+        assert!(!current.needs_rehash(current.dummy_hash.expose_secret()));
+        assert!(!current.needs_rehash(hashing(15000, 3).dummy_hash.expose_secret()));
+    }
+
+    #[test]
+    fn invalid_parameters_are_rejected() {
+        let settings = PasswordHashingSettings {
+            memory_kib: 15000,
+            iterations: 0,
+            parallelism: 1,
+        };
+        assert!(PasswordHashing::new(&settings).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::configuration::PasswordPolicySettings;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PasswordRejection {
+    #[error("The new password must be at least {0} characters long.")]
+    TooShort(usize),
+    #[error("The new password must be at most {0} characters long.")]
+    TooLong(usize),
+    #[error("The new password must be different from the current one.")]
+    SameAsCurrent,
+    #[error("This password has appeared in a data breach, please choose another one.")]
+    Breached,
+    #[error("The new password is too easy to guess, try a longer phrase of unrelated words.")]
+    TooWeak,
+}
+
+/// What a new password has to satisfy before it is hashed and stored.
+pub struct PasswordPolicy {
+    settings: PasswordPolicySettings,
+    breached: BreachedPasswords,
+    // word -> rank, 1 for the most common
+    dictionary: HashMap<String, usize>,
+}
+
+impl PasswordPolicy {
+    pub fn load(settings: PasswordPolicySettings) -> Result<Self, anyhow::Error> {
+        let directory = Path::new(&settings.directory);
+        let read = |file: &str| -> Result<String, anyhow::Error> {
+            let path = directory.join(file);
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read password list {}", path.display()))
+        };
+        let breached = BreachedPasswords::parse(&read("breached.txt")?);
+        let dictionary = data_lines(&read("dictionary.txt")?)
+            .enumerate()
+            .map(|(i, word)| (word.to_lowercase(), i + 1))
+            .collect();
+
+        Ok(Self {
+            settings,
+            breached,
+            dictionary,
+        })
+    }
+
+    /// `current_password` is known when a password is changed, not when it is reset.
+    /// `user_inputs` are words a guesser would try first, such as the username.
+    pub fn check(
+        &self,
+        password: &Secret<String>,
+        current_password: Option<&Secret<String>>,
+        user_inputs: &[&str],
+    ) -> Result<(), PasswordRejection> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        if length < self.settings.min_length {
+            return Err(PasswordRejection::TooShort(self.settings.min_length));
+        }
+        if length > self.settings.max_length {
+            return Err(PasswordRejection::TooLong(self.settings.max_length));
+        }
+        if current_password.map(|p| p.expose_secret()) == Some(password) {
+            return Err(PasswordRejection::SameAsCurrent);
+        }
+        if self.breached.contains(password) {
+            return Err(PasswordRejection::Breached);
+        }
+        if self.strength(password, user_inputs) < self.settings.min_strength {
+            return Err(PasswordRejection::TooWeak);
+        }
+        Ok(())
+    }
+
+    /// From 0 to 4, on the same scale as zxcvbn.
+    pub fn strength(&self, password: &str, user_inputs: &[&str]) -> u8 {
+        let guesses = estimate_guesses(password, &self.dictionary, user_inputs);
+        match guesses {
+            g if g < 1e3 => 0,
+            g if g < 1e6 => 1,
+            g if g < 1e8 => 2,
+            g if g < 1e10 => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// SHA-1 digests of breached passwords, grouped by their first five
+/// hex characters the way a k-anonymity range query hands them out.
+struct BreachedPasswords {
+    ranges: HashMap<String, Vec<String>>,
+}
+
+impl BreachedPasswords {
+    fn parse(source: &str) -> Self {
+        let mut ranges: HashMap<String, Vec<String>> = HashMap::new();
+        for digest in data_lines(source).filter(|line| line.len() == 40) {
+            let (prefix, suffix) = digest.split_at(5);
+            ranges
+                .entry(prefix.to_uppercase())
+                .or_default()
+                .push(suffix.to_uppercase());
+        }
+        Self { ranges }
+    }
+
+    fn contains(&self, password: &str) -> bool {
+        let digest: String = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let (prefix, suffix) = digest.split_at(5);
+        self.ranges
+            .get(prefix)
+            .is_some_and(|suffixes| suffixes.iter().any(|s| s == suffix))
+    }
+}
+
+fn data_lines(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// A simplified take on zxcvbn: the password is covered with the cheapest
+/// sequence of dictionary words, repeats, runs and single characters,
+/// multiplying the guesses each part would take.
+fn estimate_guesses(
+    password: &str,
+    dictionary: &HashMap<String, usize>,
+    user_inputs: &[&str],
+) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let user_inputs: Vec<String> = user_inputs
+        .iter()
+        .map(|input| input.to_lowercase())
+        .filter(|input| input.chars().count() >= 3)
+        .collect();
+
+    // cheapest[j] covers the first j characters
+    let mut cheapest = vec![f64::INFINITY; chars.len() + 1];
+    cheapest[0] = 1.0;
+    for end in 1..=chars.len() {
+        for start in 0..end {
+            if let Some(guesses) = segment_guesses(&chars[start..end], dictionary, &user_inputs) {
+                cheapest[end] = cheapest[end].min(cheapest[start] * guesses);
+            }
+        }
+    }
+    cheapest[chars.len()]
+}
+
+fn segment_guesses(
+    segment: &[char],
+    dictionary: &HashMap<String, usize>,
+    user_inputs: &[String],
+) -> Option<f64> {
+    if segment.len() == 1 {
+        return Some(cardinality(segment[0]));
+    }
+    if segment.len() < 3 {
+        return None;
+    }
+
+    let mut candidates = Vec::new();
+    let lowercase: String = segment.iter().flat_map(|c| c.to_lowercase()).collect();
+    let unleeted: String = lowercase.chars().map(unleet).collect();
+    // capitalised words take a few more tries
+    let case_factor = if segment.iter().any(|c| c.is_uppercase()) {
+        2.0
+    } else {
+        1.0
+    };
+
+    if user_inputs.contains(&lowercase) || user_inputs.contains(&unleeted) {
+        candidates.push(2.0 * case_factor);
+    }
+    if let Some(rank) = dictionary.get(&lowercase) {
+        candidates.push(*rank as f64 * case_factor);
+    }
+    if let Some(rank) = dictionary.get(&unleeted) {
+        candidates.push(*rank as f64 * case_factor * 2.0);
+    }
+    let length = segment.len() as f64;
+    if segment.iter().all(|c| *c == segment[0]) {
+        candidates.push(cardinality(segment[0]) * length);
+    }
+    let steps: Vec<i64> = segment
+        .windows(2)
+        .map(|pair| pair[1] as i64 - pair[0] as i64)
+        .collect();
+    if steps
+        .iter()
+        .all(|step| *step == steps[0] && step.abs() == 1)
+    {
+        candidates.push(cardinality(segment[0]) * length);
+    }
+
+    candidates.into_iter().reduce(f64::min)
+}
+
+fn cardinality(c: char) -> f64 {
+    match c {
+        '0'..='9' => 10.0,
+        'a'..='z' | 'A'..='Z' => 26.0,
+        c if c.is_ascii() => 33.0,
+        _ => 100.0,
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '5' | '$' => 's',
+        '7' => 't',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PasswordPolicy, PasswordRejection};
+    use crate::configuration::PasswordPolicySettings;
+    use secrecy::Secret;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::load(PasswordPolicySettings {
+            min_length: 12,
+            max_length: 128,
+            min_strength: 3,
+            directory: "configuration/passwords".into(),
+        })
+        .unwrap()
+    }
+
+    fn check(password: &str) -> Result<(), PasswordRejection> {
+        policy().check(&Secret::new(password.to_string()), None, &["ursula"])
+    }
+
+    #[test]
+    fn lengths_are_bounded() {
+        assert_eq!(check("short"), Err(PasswordRejection::TooShort(12)));
+        assert_eq!(
+            check(&"x7#Q".repeat(33)),
+            Err(PasswordRejection::TooLong(128))
+        );
+    }
+
+    #[test]
+    fn the_current_password_cannot_be_reused() {
+        let password = Secret::new("tG7#pq9!Lz2@vR".to_string());
+        assert_eq!(
+            policy().check(&password, Some(&password), &[]),
+            Err(PasswordRejection::SameAsCurrent)
+        );
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected() {
+        assert_eq!(check("1q2w3e4r5t6y"), Err(PasswordRejection::Breached));
+        assert_eq!(
+            check("correcthorsebatterystaple"),
+            Err(PasswordRejection::Breached)
+        );
+    }
+
+    #[test]
+    fn predictable_passwords_are_too_weak() {
+        for password in [
+            "passwordpassword",
+            "P4ssw0rdP4ssw0rd",
+            "aaaaaaaaaaaaaaaa",
+            "abcdefghijklmnop",
+            "ursula-ursula-ursula",
+            "qwertymonkey1",
+        ] {
+            assert_eq!(
+                check(password),
+                Err(PasswordRejection::TooWeak),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn unpredictable_passwords_are_accepted() {
+        for password in [
+            "tG7#pq9!Lz2@vR",
+            "f47ac10b-58cc-4372-a567-0e02b2c3d479",
+            "gravel umbrella kettle sonata",
+        ] {
+            assert_eq!(check(password), Ok(()), "{}", password);
+        }
+    }
+
+    #[test]
+    fn strength_grows_with_length() {
+        let policy = policy();
+        assert_eq!(policy.strength("aaaaaaaa", &[]), 0);
+        assert!(policy.strength("kettle", &[]) < policy.strength("kettle sonata gravel", &[]));
+    }
+}
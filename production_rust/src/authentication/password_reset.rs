@@ -0,0 +1,209 @@
+use super::password::{compute_password_hash, PasswordHashing};
+use crate::domain::SubscriberEmail;
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How long an emailed reset link can be used for.
+pub const RESET_TOKEN_LIFETIME_MINUTES: i64 = 30;
+
+/// A reset token to email to the user who asked for it.
+pub struct PasswordResetRequest {
+    pub recipient: SubscriberEmail,
+    pub token: Secret<String>,
+}
+
+/// `None` when there is no active user with that name or the user
+/// has no recovery email address: there is nowhere to send a token.
+#[tracing::instrument(name = "Create a password reset token", skip(connection_pool))]
+pub async fn create_password_reset_token(
+    username: &str,
+    connection_pool: &PgPool,
+) -> Result<Option<PasswordResetRequest>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email AS "email!" FROM users
+        WHERE username = $1 AND NOT disabled AND email IS NOT NULL
+        "#,
+        username,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve the recovery email of a user.")?;
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let recipient = SubscriberEmail::parse(row.email).map_err(anyhow::Error::msg)?;
+
+    let token = Secret::new(generate_reset_token());
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_reset_token(&token),
+        row.user_id,
+        now,
+        now + chrono::Duration::minutes(RESET_TOKEN_LIFETIME_MINUTES),
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the password reset token.")?;
+
+    Ok(Some(PasswordResetRequest { recipient, token }))
+}
+
+#[tracing::instrument(name = "Check a password reset token", skip(token, connection_pool))]
+pub async fn is_valid_reset_token(
+    token: &Secret<String>,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id FROM password_reset_tokens
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        "#,
+        hash_reset_token(token),
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve a password reset token.")?;
+    Ok(row.is_some())
+}
+
+/// Uses up `token` to set a new password, returning whose password changed.
+/// `None` when the token is unknown, expired or has already been used.
+/// Every other outstanding token of the user is invalidated along the way.
+#[tracing::instrument(
+    name = "Reset password",
+    skip(token, password, password_hashing, connection_pool)
+)]
+pub async fn reset_password(
+    token: Secret<String>,
+    password: Secret<String>,
+    password_hashing: &PasswordHashing,
+    connection_pool: &PgPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_reset_token(&token),
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to use up the password reset token.")?;
+    let user_id = match row {
+        Some(row) => row.user_id,
+        None => return Ok(None),
+    };
+
+    let params = password_hashing.params();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, params))
+            .await?
+            .context("Failed to hash password")?;
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        password_hash.expose_secret(),
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to change user's password in the database.")?;
+    sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = now()
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to invalidate the other password reset tokens.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password reset.")?;
+    Ok(Some(user_id))
+}
+
+#[tracing::instrument(name = "Get recovery email", skip(connection_pool))]
+pub async fn get_recovery_email(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!("SELECT email FROM users WHERE user_id = $1", user_id)
+        .fetch_one(connection_pool)
+        .await
+        .context("Failed to perform a query to retrieve the recovery email of a user.")?;
+    Ok(row.email)
+}
+
+#[tracing::instrument(name = "Set recovery email", skip(connection_pool))]
+pub async fn set_recovery_email(
+    user_id: Uuid,
+    email: Option<&SubscriberEmail>,
+    connection_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email.map(|e| e.as_ref()),
+        user_id,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the recovery email of the user.")?;
+    Ok(())
+}
+
+// only a digest is stored, a leaked table does not hand out working links
+fn hash_reset_token(token: &Secret<String>) -> String {
+    Sha256::digest(token.expose_secret().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn generate_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_reset_token, hash_reset_token};
+    use secrecy::Secret;
+
+    #[test]
+    fn tokens_are_stored_as_sha256_hex_digests() {
+        let digest = hash_reset_token(&Secret::new("abc".to_string()));
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn tokens_are_not_reused() {
+        assert_eq!(generate_reset_token().len(), 32);
+        assert_ne!(generate_reset_token(), generate_reset_token());
+    }
+}
@@ -0,0 +1,140 @@
+use actix_web::http::Method;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What a user is allowed to do, ordered from least to most privileged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            other => Err(format!("{} is not a known role.", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The least privileged role allowed through to an `/admin` route.
+/// Editors may post to `/admin/newsletter`, but only to save drafts:
+/// publishing is checked by the handler itself.
+pub fn required_role(method: &Method, path: &str) -> Role {
+    match path {
+        p if p.starts_with("/admin/users")
+            || p.starts_with("/admin/tokens")
+            || p.starts_with("/admin/settings")
+            || p.starts_with("/admin/privacy") =>
+        {
+            Role::Owner
+        }
+        _ if method == Method::GET => Role::Viewer,
+        "/admin/logout" => Role::Viewer,
+        p if p.starts_with("/admin/password") => Role::Viewer,
+        p if p.starts_with("/admin/totp") => Role::Viewer,
+        p if p.starts_with("/admin/sessions") => Role::Viewer,
+        "/admin/newsletter" | "/admin/issues/test_send" => Role::Editor,
+        _ => Role::Owner,
+    }
+}
+
+/// `None` when the user has been deleted or disabled since they logged in.
+#[tracing::instrument(name = "Get the role of an active user", skip(connection_pool))]
+pub async fn get_active_role(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Option<Role>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT role FROM users WHERE user_id = $1 AND NOT disabled",
+        user_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve the role of a user.")?;
+
+    row.map(|r| Role::try_from(r.role.as_str()).map_err(anyhow::Error::msg))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{required_role, Role};
+    use actix_web::http::Method;
+
+    #[test]
+    fn viewers_can_only_look_around() {
+        assert_eq!(required_role(&Method::GET, "/admin/issues"), Role::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/logout"), Role::Viewer);
+        assert_eq!(
+            required_role(&Method::POST, "/admin/password/email"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/admin/totp/enroll"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/admin/sessions/revoke_others"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/admin/issues/reschedule"),
+            Role::Owner
+        );
+        assert_eq!(
+            required_role(&Method::GET, "/admin/subscribers"),
+            Role::Viewer
+        );
+        assert_eq!(
+            required_role(&Method::POST, "/admin/subscribers/delete"),
+            Role::Owner
+        );
+        assert_eq!(required_role(&Method::GET, "/admin/lists"), Role::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/lists"), Role::Owner);
+    }
+
+    #[test]
+    fn account_management_is_reserved_to_owners() {
+        assert_eq!(required_role(&Method::GET, "/admin/users"), Role::Owner);
+        assert_eq!(required_role(&Method::GET, "/admin/tokens"), Role::Owner);
+        assert_eq!(required_role(&Method::POST, "/admin/settings"), Role::Owner);
+        assert_eq!(
+            required_role(&Method::GET, "/admin/privacy/export"),
+            Role::Owner
+        );
+    }
+
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(Role::Owner > Role::Editor);
+        assert!(Role::Editor > Role::Viewer);
+        assert_eq!(Role::try_from("editor"), Ok(Role::Editor));
+        assert!(Role::try_from("admin").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Where and when a session was opened, recorded at login.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SessionMetadata {
+    pub created_at: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    /// `None` for sessions opened before metadata was recorded.
+    pub metadata: Option<SessionMetadata>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Keeps track, in Redis, of the sessions each user is logged in with,
+/// so that they can be listed and ended one by one or all at once.
+/// A session whose id is missing from the registry is no longer accepted.
+/// Entries expire with the session they stand for, unless it keeps being used.
+#[derive(Clone)]
+pub struct SessionRegistry {
+    connection: ConnectionManager,
+    ttl: Duration,
+}
+
+impl SessionRegistry {
+    pub fn new(connection: ConnectionManager, ttl: Duration) -> Self {
+        Self { connection, ttl }
+    }
+
+    /// Returns the id to store in the new session of `user_id`.
+    #[tracing::instrument(name = "Register a session", skip(self))]
+    pub async fn register(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error> {
+        let session_id = Uuid::new_v4();
+        let now = Utc::now();
+        let metadata = SessionMetadata {
+            created_at: now,
+            ip_address: ip_address.map(Into::into),
+            user_agent: user_agent.map(Into::into),
+        };
+        let ttl = self.ttl_seconds();
+        let mut connection = self.connection.clone();
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(metadata_key(session_id))
+            .arg(serde_json::to_string(&metadata)?)
+            .arg("EX")
+            .arg(ttl)
+            .ignore()
+            .cmd("SET")
+            .arg(last_seen_key(session_id))
+            .arg(now.to_rfc3339())
+            .arg("EX")
+            .arg(ttl)
+            .ignore()
+            .sadd(sessions_key(user_id), session_id.to_string())
+            .ignore()
+            .expire(sessions_key(user_id), ttl)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .context("Failed to register a session in Redis.")?;
+        Ok(session_id)
+    }
+
+    #[tracing::instrument(name = "Check a session", skip(self))]
+    pub async fn is_active(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .sismember(sessions_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to look up a session in Redis.")
+    }
+
+    /// Records that the session has just been used, pushing back its expiry.
+    #[tracing::instrument(name = "Touch a session", skip(self))]
+    pub async fn touch(&self, user_id: Uuid, session_id: Uuid) -> Result<(), anyhow::Error> {
+        let ttl = self.ttl_seconds();
+        let mut connection = self.connection.clone();
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(last_seen_key(session_id))
+            .arg(Utc::now().to_rfc3339())
+            .arg("EX")
+            .arg(ttl)
+            .ignore()
+            .expire(metadata_key(session_id), ttl)
+            .ignore()
+            .expire(sessions_key(user_id), ttl)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .context("Failed to record the last use of a session in Redis.")?;
+        Ok(())
+    }
+
+    /// The sessions of `user_id`, most recently used first.
+    /// Sessions that expired while others kept the user's entry alive are dropped.
+    #[tracing::instrument(name = "List active sessions", skip(self))]
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<ActiveSession>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(sessions_key(user_id))
+            .await
+            .context("Failed to list the sessions of a user in Redis.")?;
+
+        let mut sessions = Vec::new();
+        for session_id in session_ids {
+            let session_id = match Uuid::parse_str(&session_id) {
+                Ok(session_id) => session_id,
+                Err(_) => continue,
+            };
+            let metadata: Option<String> = connection
+                .get(metadata_key(session_id))
+                .await
+                .context("Failed to read the metadata of a session from Redis.")?;
+            let last_seen: Option<String> = connection
+                .get(last_seen_key(session_id))
+                .await
+                .context("Failed to read the last use of a session from Redis.")?;
+            if last_seen.is_none() {
+                self.remove(user_id, session_id).await?;
+                continue;
+            }
+            sessions.push(ActiveSession {
+                session_id,
+                metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+                last_seen: last_seen
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+            });
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen));
+        Ok(sessions)
+    }
+
+    /// Returns `false` if `session_id` was not a session of `user_id`.
+    #[tracing::instrument(name = "Remove a session", skip(self))]
+    pub async fn remove(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let n_removed: u32 = connection
+            .srem(sessions_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to remove a session from Redis.")?;
+        if n_removed > 0 {
+            self.forget(&[session_id]).await?;
+        }
+        Ok(n_removed > 0)
+    }
+
+    /// Logs `user_id` out everywhere but in `current_session_id`.
+    #[tracing::instrument(name = "Revoke other sessions", skip(self))]
+    pub async fn revoke_others(
+        &self,
+        user_id: Uuid,
+        current_session_id: Uuid,
+    ) -> Result<(), anyhow::Error> {
+        for session in self.list(user_id).await? {
+            if session.session_id != current_session_id {
+                self.remove(user_id, session.session_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Logs `user_id` out everywhere.
+    #[tracing::instrument(name = "Revoke all sessions", skip(self))]
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(sessions_key(user_id))
+            .await
+            .context("Failed to list the sessions of a user in Redis.")?;
+        let _: () = connection
+            .del(sessions_key(user_id))
+            .await
+            .context("Failed to revoke the sessions of a user in Redis.")?;
+        let session_ids: Vec<Uuid> = session_ids
+            .iter()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect();
+        self.forget(&session_ids).await
+    }
+
+    fn ttl_seconds(&self) -> usize {
+        self.ttl.as_secs() as usize
+    }
+
+    async fn forget(&self, session_ids: &[Uuid]) -> Result<(), anyhow::Error> {
+        if session_ids.is_empty() {
+            return Ok(());
+        }
+        let keys: Vec<String> = session_ids
+            .iter()
+            .flat_map(|id| [metadata_key(*id), last_seen_key(*id)])
+            .collect();
+        let mut connection = self.connection.clone();
+        let _: () = connection
+            .del(keys)
+            .await
+            .context("Failed to delete the metadata of a session from Redis.")?;
+        Ok(())
+    }
+}
+
+fn sessions_key(user_id: Uuid) -> String {
+    format!("user_sessions:{}", user_id)
+}
+
+fn metadata_key(session_id: Uuid) -> String {
+    format!("session_metadata:{}", session_id)
+}
+
+// kept apart from the metadata so that every request is a single SET
+fn last_seen_key(session_id: Uuid) -> String {
+    format!("session_last_seen:{}", session_id)
+}
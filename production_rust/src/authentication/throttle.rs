@@ -0,0 +1,170 @@
+use crate::configuration::LoginThrottleSettings;
+use crate::rate_limiter::{block, blocked_for, client_ip, count};
+use actix_web::HttpRequest;
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::time::Duration;
+
+/// What a failed login attempt led to.
+#[derive(Debug, Default)]
+pub struct FailedLogin {
+    /// `None` while the account is still within its free attempts.
+    pub retry_after: Option<Duration>,
+    pub account_locked: bool,
+    pub address_locked: bool,
+}
+
+/// Counts failed logins per username and per client address in Redis,
+/// blocking further attempts for a while once they pile up.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    settings: LoginThrottleSettings,
+    trust_proxy_headers: bool,
+}
+
+impl LoginThrottle {
+    pub fn new(
+        connection: ConnectionManager,
+        settings: LoginThrottleSettings,
+        trust_proxy_headers: bool,
+    ) -> Self {
+        Self {
+            connection,
+            settings,
+            trust_proxy_headers,
+        }
+    }
+
+    pub fn client_ip(&self, request: &HttpRequest) -> Option<String> {
+        client_ip(request, self.trust_proxy_headers)
+    }
+
+    /// How long the caller has to wait before trying to log in again,
+    /// `None` if neither the account nor the address are blocked.
+    #[tracing::instrument(name = "Check login throttle", skip(self))]
+    pub async fn retry_after(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<Duration>, anyhow::Error> {
+        let mut keys = vec![blocked_key("user", username)];
+        keys.extend(ip_address.map(|ip| blocked_key("ip", ip)));
+        blocked_for(&self.connection, &keys).await
+    }
+
+    #[tracing::instrument(name = "Record a failed login", skip(self))]
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<FailedLogin, anyhow::Error> {
+        let mut outcome = FailedLogin::default();
+        let window = Duration::from_secs(self.settings.window_seconds);
+
+        let n_failures = count(&self.connection, &failures_key("user", username), window).await?;
+        if let Some(delay) = failure_delay(n_failures, &self.settings) {
+            block(&self.connection, &blocked_key("user", username), delay).await?;
+            outcome.retry_after = Some(delay);
+            outcome.account_locked = n_failures >= self.settings.max_failures;
+        }
+
+        if let Some(ip) = ip_address {
+            let n_failures = count(&self.connection, &failures_key("ip", ip), window).await?;
+            if n_failures >= self.settings.max_failures_per_ip {
+                let lockout = self.settings.lockout();
+                block(&self.connection, &blocked_key("ip", ip), lockout).await?;
+                outcome.retry_after = outcome.retry_after.max(Some(lockout));
+                outcome.address_locked = true;
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Forgets the failures of an account.
+    /// Failures of the address are kept, a single valid account
+    /// must not be enough to keep guessing the password of others.
+    #[tracing::instrument(name = "Record a successful login", skip(self))]
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let _: () = connection
+            .del(&[
+                failures_key("user", username),
+                blocked_key("user", username),
+            ])
+            .await
+            .context("Failed to clear failed logins from Redis.")?;
+        Ok(())
+    }
+}
+
+fn failures_key(kind: &str, value: &str) -> String {
+    format!("login_failures:{}:{}", kind, value)
+}
+
+fn blocked_key(kind: &str, value: &str) -> String {
+    format!("login_blocked:{}:{}", kind, value)
+}
+
+/// The delay imposed on an account after its `n_failures`-th failure:
+/// none for the free attempts, doubling afterwards, a lockout at the end.
+pub fn failure_delay(n_failures: u32, settings: &LoginThrottleSettings) -> Option<Duration> {
+    if n_failures >= settings.max_failures {
+        return Some(settings.lockout());
+    }
+    let exponent = n_failures.checked_sub(settings.free_attempts + 1)?;
+    let delay = settings
+        .base_delay()
+        .saturating_mul(2u32.saturating_pow(exponent));
+    Some(delay.min(settings.lockout()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::failure_delay;
+    use crate::configuration::LoginThrottleSettings;
+    use std::time::Duration;
+
+    fn settings() -> LoginThrottleSettings {
+        LoginThrottleSettings {
+            free_attempts: 2,
+            base_delay_milliseconds: 1000,
+            max_failures: 6,
+            max_failures_per_ip: 50,
+            lockout_seconds: 900,
+            window_seconds: 900,
+        }
+    }
+
+    #[test]
+    fn free_attempts_are_not_delayed() {
+        assert_eq!(failure_delay(0, &settings()), None);
+        assert_eq!(failure_delay(1, &settings()), None);
+        assert_eq!(failure_delay(2, &settings()), None);
+    }
+
+    #[test]
+    fn delays_double_until_the_lockout() {
+        assert_eq!(failure_delay(3, &settings()), Some(Duration::from_secs(1)));
+        assert_eq!(failure_delay(4, &settings()), Some(Duration::from_secs(2)));
+        assert_eq!(failure_delay(5, &settings()), Some(Duration::from_secs(4)));
+        assert_eq!(
+            failure_delay(6, &settings()),
+            Some(Duration::from_secs(900))
+        );
+        assert_eq!(
+            failure_delay(60, &settings()),
+            Some(Duration::from_secs(900))
+        );
+    }
+
+    #[test]
+    fn delays_never_exceed_the_lockout() {
+        let settings = LoginThrottleSettings {
+            max_failures: 100,
+            ..settings()
+        };
+        assert_eq!(failure_delay(99, &settings), Some(Duration::from_secs(900)));
+    }
+}
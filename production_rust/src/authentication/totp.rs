@@ -0,0 +1,451 @@
+use super::password::{compute_password_hash, validate_password_hash, PasswordHashing};
+use crate::telemetry::spawn_blocking_with_tracing;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+// codes from the previous and next step are accepted to absorb clock drift
+const ALLOWED_DRIFT: u64 = 1;
+const NONCE_LENGTH: usize = 12;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const N_RECOVERY_CODES: usize = 8;
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+pub enum TotpStatus {
+    Disabled,
+    /// Enrolled, but no code has been checked against the secret yet.
+    Pending(Secret<Vec<u8>>),
+    Enabled,
+}
+
+/// A fresh 160 bits shared secret, the size RFC 4226 recommends.
+pub fn generate_totp_secret() -> Secret<Vec<u8>> {
+    let mut secret = vec![0u8; 20];
+    thread_rng().fill_bytes(&mut secret);
+    Secret::new(secret)
+}
+
+/// The `otpauth://` URI authenticator apps read from a QR code.
+pub fn provisioning_uri(secret: &Secret<Vec<u8>>, issuer: &str, username: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = urlencoding::encode(issuer),
+        username = urlencoding::encode(username),
+        secret = base32_encode(secret.expose_secret()),
+    )
+}
+
+/// The HOTP value (RFC 4226) of the given time step.
+pub fn totp_code(secret: &[u8], step: u64) -> String {
+    let mut mac =
+        <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+pub fn time_step(unix_time: u64) -> u64 {
+    unix_time / STEP_SECONDS
+}
+
+/// Returns the time step the code belongs to. Steps up to `last_used_step`
+/// are refused so that a code cannot be replayed.
+pub fn verify_totp_code(
+    secret: &Secret<Vec<u8>>,
+    code: &str,
+    unix_time: u64,
+    last_used_step: Option<u64>,
+) -> Option<u64> {
+    let code = code.trim();
+    let current = time_step(unix_time);
+    (current.saturating_sub(ALLOWED_DRIFT)..=current + ALLOWED_DRIFT)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| totp_code(secret.expose_secret(), *step) == code)
+}
+
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes([
+            0, 0, 0, buffer[0], buffer[1], buffer[2], buffer[3], buffer[4],
+        ]);
+        let n_chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..n_chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            output.push(BASE32_ALPHABET[index as usize] as char);
+        }
+    }
+    output
+}
+
+pub fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut buffer: u64 = 0;
+    let mut n_bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u64;
+        n_bits += 5;
+        if n_bits >= 8 {
+            n_bits -= 8;
+            output.push((buffer >> n_bits) as u8);
+            buffer &= (1 << n_bits) - 1;
+        }
+    }
+    Some(output)
+}
+
+/// Encrypts TOTP secrets at rest with AES-256-GCM, under a key
+/// derived from `application.totp_encryption_key`.
+#[derive(Clone)]
+pub struct TotpCipher(Aes256Gcm);
+
+impl TotpCipher {
+    pub fn new(key: &Secret<String>) -> Self {
+        let key = Sha256::digest(key.expose_secret().as_bytes());
+        Self(Aes256Gcm::new(&key))
+    }
+
+    /// The random nonce is stored in front of the ciphertext.
+    pub fn encrypt(&self, secret: &Secret<Vec<u8>>) -> Result<Vec<u8>, anyhow::Error> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), secret.expose_secret().as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the TOTP secret."))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Secret<Vec<u8>>, anyhow::Error> {
+        if encrypted.len() < NONCE_LENGTH {
+            anyhow::bail!("The encrypted TOTP secret is truncated.");
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+        let secret = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt the TOTP secret."))?;
+        Ok(Secret::new(secret))
+    }
+}
+
+fn unix_time() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+#[tracing::instrument(name = "Get TOTP status", skip(cipher, connection_pool))]
+pub async fn get_totp_status(
+    user_id: Uuid,
+    cipher: &TotpCipher,
+    connection_pool: &PgPool,
+) -> Result<TotpStatus, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT encrypted_secret, confirmed FROM totp_credentials WHERE user_id = $1",
+        user_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to retrieve the TOTP credentials.")?;
+
+    Ok(match row {
+        None => TotpStatus::Disabled,
+        Some(row) if row.confirmed => TotpStatus::Enabled,
+        Some(row) => TotpStatus::Pending(cipher.decrypt(&row.encrypted_secret)?),
+    })
+}
+
+/// Replaces any pending enrollment with a new secret.
+#[tracing::instrument(name = "Begin TOTP enrollment", skip(cipher, connection_pool))]
+pub async fn begin_totp_enrollment(
+    user_id: Uuid,
+    cipher: &TotpCipher,
+    connection_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let encrypted_secret = cipher.encrypt(&generate_totp_secret())?;
+    sqlx::query!(
+        r#"
+        INSERT INTO totp_credentials (user_id, encrypted_secret, confirmed, created_at)
+        VALUES ($1, $2, false, now())
+        ON CONFLICT (user_id) DO UPDATE
+        SET encrypted_secret = EXCLUDED.encrypted_secret, created_at = now()
+        WHERE NOT totp_credentials.confirmed
+        "#,
+        user_id,
+        encrypted_secret,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to store the TOTP secret.")?;
+    Ok(())
+}
+
+/// Checks a first code against a pending secret and, on success, enables
+/// TOTP and returns a fresh set of recovery codes to show the user once.
+#[tracing::instrument(
+    name = "Confirm TOTP enrollment",
+    skip(code, cipher, password_hashing, connection_pool)
+)]
+pub async fn confirm_totp_enrollment(
+    user_id: Uuid,
+    code: &str,
+    cipher: &TotpCipher,
+    password_hashing: &PasswordHashing,
+    connection_pool: &PgPool,
+) -> Result<Option<Vec<Secret<String>>>, anyhow::Error> {
+    let secret = match get_totp_status(user_id, cipher, connection_pool).await? {
+        TotpStatus::Pending(secret) => secret,
+        _ => return Ok(None),
+    };
+    let step = match verify_totp_code(&secret, code, unix_time(), None) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let recovery_codes: Vec<Secret<String>> = (0..N_RECOVERY_CODES)
+        .map(|_| Secret::new(generate_recovery_code()))
+        .collect();
+    let mut code_hashes = Vec::new();
+    for recovery_code in &recovery_codes {
+        let recovery_code = recovery_code.clone();
+        let params = password_hashing.params();
+        let code_hash =
+            spawn_blocking_with_tracing(move || compute_password_hash(recovery_code, params))
+                .await?
+                .context("Failed to hash a recovery code")?;
+        code_hashes.push(code_hash.expose_secret().clone());
+    }
+
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        "UPDATE totp_credentials SET confirmed = true, last_used_step = $1 WHERE user_id = $2",
+        step as i64,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to confirm the TOTP enrollment.")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO totp_recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &code_hashes,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the TOTP enrollment.")?;
+
+    Ok(Some(recovery_codes))
+}
+
+/// Accepts either a code from the authenticator app or an unused recovery code,
+/// which is then burnt.
+#[tracing::instrument(name = "Verify second factor", skip(code, cipher, connection_pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: Secret<String>,
+    cipher: &TotpCipher,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT encrypted_secret, last_used_step FROM totp_credentials
+        WHERE user_id = $1 AND confirmed
+        "#,
+        user_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to retrieve the TOTP credentials.")?;
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(false),
+    };
+
+    let secret = cipher.decrypt(&row.encrypted_secret)?;
+    let last_used_step = row.last_used_step.map(|step| step as u64);
+    if let Some(step) = verify_totp_code(&secret, code.expose_secret(), unix_time(), last_used_step)
+    {
+        // a concurrent login may have used the same code in the meantime
+        let n_updated = sqlx::query!(
+            r#"
+            UPDATE totp_credentials SET last_used_step = $1
+            WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+            "#,
+            step as i64,
+            user_id,
+        )
+        .execute(connection_pool)
+        .await
+        .context("Failed to record the use of a TOTP code.")?
+        .rows_affected();
+        return Ok(n_updated > 0);
+    }
+
+    // spare the Argon2 verifications when the input cannot be a recovery code
+    if code.expose_secret().trim().len() != RECOVERY_CODE_LENGTH {
+        return Ok(false);
+    }
+    use_recovery_code(user_id, code, connection_pool).await
+}
+
+async fn use_recovery_code(
+    user_id: Uuid,
+    code: Secret<String>,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let code = Secret::new(code.expose_secret().trim().to_string());
+    let code_hashes = sqlx::query!(
+        "SELECT code_hash FROM totp_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+        user_id,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the recovery codes.")?;
+
+    for row in code_hashes {
+        let candidate = code.clone();
+        let code_hash = Secret::new(row.code_hash.clone());
+        let is_match =
+            spawn_blocking_with_tracing(move || validate_password_hash(code_hash, candidate))
+                .await
+                .context("Failed to spawn blocking task.")?
+                .await
+                .is_ok();
+        if is_match {
+            let n_updated = sqlx::query!(
+                r#"
+                UPDATE totp_recovery_codes SET used_at = now()
+                WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+                "#,
+                user_id,
+                row.code_hash,
+            )
+            .execute(connection_pool)
+            .await
+            .context("Failed to burn the recovery code.")?
+            .rows_affected();
+            return Ok(n_updated > 0);
+        }
+    }
+    Ok(false)
+}
+
+#[tracing::instrument(name = "Disable TOTP", skip(connection_pool))]
+pub async fn disable_totp(user_id: Uuid, connection_pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the recovery codes.")?;
+    sqlx::query!("DELETE FROM totp_credentials WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the TOTP credentials.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the removal of TOTP.")?;
+    Ok(())
+}
+
+fn generate_recovery_code() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(|c| char::from(c).to_ascii_lowercase())
+        .take(RECOVERY_CODE_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, truncated to 6 digits
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        let secret = b"12345678901234567890";
+        assert_eq!(totp_code(secret, time_step(59)), "287082");
+        assert_eq!(totp_code(secret, time_step(1111111109)), "081804");
+        assert_eq!(totp_code(secret, time_step(2000000000)), "279037");
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        let secret = generate_totp_secret();
+        let encoded = base32_encode(secret.expose_secret());
+        assert_eq!(&base32_decode(&encoded).unwrap(), secret.expose_secret());
+    }
+
+    #[test]
+    fn drifting_codes_are_accepted_but_not_replayed() {
+        let secret = Secret::new(b"12345678901234567890".to_vec());
+        let now = 1111111109;
+        let previous = totp_code(secret.expose_secret(), time_step(now) - 1);
+
+        let step = verify_totp_code(&secret, &previous, now, None);
+        assert_eq!(step, Some(time_step(now) - 1));
+        assert_eq!(verify_totp_code(&secret, &previous, now, step), None);
+        assert_eq!(verify_totp_code(&secret, &previous, now + 60, None), None);
+    }
+
+    #[test]
+    fn secrets_are_encrypted_with_a_fresh_nonce() {
+        let cipher = TotpCipher::new(&Secret::new("a key".to_string()));
+        let secret = generate_totp_secret();
+
+        let first = cipher.encrypt(&secret).unwrap();
+        let second = cipher.encrypt(&secret).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            cipher.decrypt(&first).unwrap().expose_secret(),
+            secret.expose_secret()
+        );
+
+        let other_cipher = TotpCipher::new(&Secret::new("another key".to_string()));
+        assert!(other_cipher.decrypt(&first).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::email_client::{
+    EmailClient, EmailTransport, FileDropTransport, PostmarkTransport, Sandbox, SmtpTransport,
+};
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
@@ -11,6 +13,14 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub issue_delivery: IssueDeliverySettings,
+    pub templates: TemplateSettings,
+    pub login_throttle: LoginThrottleSettings,
+    pub rate_limits: RateLimitSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub idempotency: IdempotencySettings,
+    pub subscriptions: SubscriptionSettings,
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -19,14 +29,89 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub auth_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    #[serde(default)]
+    pub transport: EmailTransportKind,
+    pub smtp: Option<SmtpSettings>,
+    pub file_drop_directory: Option<String>,
+    #[serde(default)]
+    pub sandbox: SandboxSettings,
+}
+
+#[derive(Clone, Default, serde::Deserialize)]
+pub struct SandboxSettings {
+    pub enabled: bool,
+    pub redirect_to: Option<String>,
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+}
+
+#[derive(Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTransportKind {
+    #[default]
+    Postmark,
+    Smtp,
+    FileDrop,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    pub starttls: bool,
 }
 
 impl EmailClientSettings {
-    pub fn client(self) -> EmailClient {
-        // change to unwrap_or_else and add error reporting
-        let sender_email = self.sender().expect("Invalid sender");
+    pub fn client(self) -> Result<EmailClient, String> {
+        let sender_email = self
+            .sender()
+            .map_err(|e| format!("Invalid email_client.sender_email: {}", e))?;
+        let sandbox = self.sandbox()?;
+        Ok(EmailClient::new(sender_email, self.transport()?, sandbox))
+    }
+
+    pub fn sandbox(&self) -> Result<Option<Sandbox>, String> {
+        if !self.sandbox.enabled {
+            return Ok(None);
+        }
+        let redirect_to = self
+            .sandbox
+            .redirect_to
+            .clone()
+            .ok_or("Sandbox mode requires email_client.sandbox.redirect_to")?;
+        Ok(Some(Sandbox::new(
+            SubscriberEmail::parse(redirect_to)?,
+            self.sandbox.allowlist.clone(),
+        )))
+    }
+
+    pub fn transport(self) -> Result<Box<dyn EmailTransport>, String> {
         let timeout = self.timeout();
-        EmailClient::new(self.base_url, sender_email, self.auth_token, timeout)
+        let transport: Box<dyn EmailTransport> = match self.transport {
+            EmailTransportKind::Postmark => Box::new(PostmarkTransport::new(
+                self.base_url,
+                self.auth_token,
+                timeout,
+            )),
+            EmailTransportKind::Smtp => {
+                let smtp = self
+                    .smtp
+                    .ok_or("The smtp transport requires email_client.smtp")?;
+                let credentials = smtp.username.zip(smtp.password);
+                Box::new(
+                    SmtpTransport::new(&smtp.host, smtp.port, credentials, smtp.starttls, timeout)
+                        .map_err(|e| format!("Invalid email_client.smtp settings: {}", e))?,
+                )
+            }
+            EmailTransportKind::FileDrop => Box::new(FileDropTransport::new(
+                self.file_drop_directory
+                    .ok_or("The file_drop transport requires email_client.file_drop_directory")?,
+            )),
+        };
+        Ok(transport)
     }
 
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
@@ -38,6 +123,154 @@ impl EmailClientSettings {
     }
 }
 
+#[derive(Clone, serde::Deserialize)]
+pub struct TemplateSettings {
+    pub directory: String,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct IssueDeliverySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: i32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub backoff_base_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub backoff_max_milliseconds: u64,
+}
+
+impl IssueDeliverySettings {
+    pub fn backoff_base(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.backoff_base_milliseconds)
+    }
+
+    pub fn backoff_max(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.backoff_max_milliseconds)
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct SubscriptionSettings {
+    /// How long a confirmation link stays valid after it was sent.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub confirmation_token_lifetime_hours: u64,
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_lifetime(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.confirmation_token_lifetime_hours * 60 * 60)
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct IdempotencySettings {
+    /// How long a saved response is replayed for, counted from the first request.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_seconds: u64,
+    /// How often expired keys are deleted by the background cleanup task.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cleanup_interval_seconds: u64,
+    /// How long a retry waits for the first request with its key to finish
+    /// before being told that it is still in progress.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub in_flight_timeout_milliseconds: u64,
+}
+
+impl IdempotencySettings {
+    pub fn ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.ttl_seconds)
+    }
+
+    pub fn cleanup_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cleanup_interval_seconds)
+    }
+
+    pub fn in_flight_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.in_flight_timeout_milliseconds)
+    }
+}
+
+/// Argon2id costs for new password hashes. Raising them upgrades
+/// existing hashes as their users log in.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PasswordPolicySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_length: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_length: usize,
+    /// From 0, guessable in a handful of tries, to 4, out of reach.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_strength: u8,
+    /// Holds `breached.txt` and `dictionary.txt`.
+    pub directory: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct LoginThrottleSettings {
+    /// Failures of an account that are not followed by any delay.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub free_attempts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_delay_milliseconds: u64,
+    /// Failures of an account that trigger a lockout.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures: u32,
+    /// Failures from a single address, across all accounts, that trigger a lockout.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+    /// How long failures are remembered after the last one.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl LoginThrottleSettings {
+    pub fn base_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.base_delay_milliseconds)
+    }
+
+    pub fn lockout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.lockout_seconds)
+    }
+}
+
+/// The limits of the forms that send emails to whoever is named in them.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct RateLimitSettings {
+    pub password_reset: RateLimit,
+    pub confirmation_resend: RateLimit,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct RateLimit {
+    /// Requests naming the same account or address within the window.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests: u32,
+    /// Requests from a single client address, whatever they name, within the window.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests_per_ip: u32,
+    /// How long requests are remembered after the last one,
+    /// and how long further ones are refused once a limit is reached.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl RateLimit {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window_seconds)
+    }
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -56,6 +289,25 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    // signs unsubscribe links, kept apart from the cookie signing key
+    pub unsubscribe_signing_key: Secret<String>,
+    pub totp_encryption_key: Secret<String>,
+    // keys the digests of erased addresses, so that they cannot be reversed with a word list
+    pub suppression_hash_key: Secret<String>,
+    /// How long an idle session is kept, in the session store and in the session registry.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_ttl_seconds: u64,
+    /// Count requests against the client address reported by a reverse proxy
+    /// in `Forwarded`/`X-Forwarded-For` rather than against the peer address.
+    /// Off by default: without a proxy in front, anyone can set those headers.
+    #[serde(default)]
+    pub trust_proxy_headers: bool,
+}
+
+impl ApplicationSettings {
+    pub fn session_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.session_ttl_seconds)
+    }
 }
 
 impl DatabaseSettings {
@@ -0,0 +1,66 @@
+use super::{EmailError, EmailMessage, EmailTransport};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Writes every message as an `.eml` file for local development,
+/// where it can be opened by any mail client.
+pub struct FileDropTransport {
+    directory: PathBuf,
+}
+
+impl FileDropTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileDropTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let (_, bytes) = message.to_mime()?;
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let path = self.directory.join(format!("{}.eml", Uuid::new_v4()));
+        tokio::fs::write(&path, bytes).await?;
+        tracing::info!(path = %path.display(), "Dropped an email to disk.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileDropTransport;
+    use crate::email_client::{EmailMessage, EmailTransport};
+    use claims::assert_ok;
+
+    #[tokio::test]
+    async fn messages_are_written_as_eml_files() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let transport = FileDropTransport::new(&directory);
+        let message = EmailMessage {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: "Subject".into(),
+            html_body: "<p>Body</p>".into(),
+            text_body: "Body".into(),
+            headers: vec![(
+                "List-Unsubscribe-Post".into(),
+                "List-Unsubscribe=One-Click".into(),
+            )],
+        };
+
+        assert_ok!(transport.send(&message).await);
+
+        let files: Vec<_> = std::fs::read_dir(&directory).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let path = files[0].as_ref().unwrap().path();
+        assert_eq!(path.extension().unwrap(), "eml");
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n"));
+        assert!(contents.contains("To: recipient@example.com"));
+        assert!(contents.contains("Subject: Subject"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
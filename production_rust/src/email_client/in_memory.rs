@@ -0,0 +1,54 @@
+use super::{EmailError, EmailMessage, EmailTransport};
+use std::sync::{Arc, Mutex};
+
+/// Captures outgoing messages instead of delivering them.
+/// Clones share the same mailbox, so a handle can be kept to inspect what was sent.
+#[derive(Clone, Default)]
+pub struct InMemoryTransport {
+    messages: Arc<Mutex<Vec<EmailMessage>>>,
+}
+
+impl InMemoryTransport {
+    pub fn messages(&self) -> Vec<EmailMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for InMemoryTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        self.messages.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InMemoryTransport;
+    use crate::email_client::{EmailMessage, EmailTransport};
+    use claims::assert_ok;
+
+    #[tokio::test]
+    async fn sent_messages_are_captured() {
+        let transport = InMemoryTransport::default();
+        let handle = transport.clone();
+        let message = EmailMessage {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: "Subject".into(),
+            html_body: "<p>Body</p>".into(),
+            text_body: "Body".into(),
+            headers: vec![("List-Unsubscribe".into(), "<https://example.com>".into())],
+        };
+
+        assert_ok!(transport.send(&message).await);
+
+        let messages = handle.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, "recipient@example.com");
+        assert_eq!(
+            messages[0].header("list-unsubscribe"),
+            Some("<https://example.com>")
+        );
+    }
+}
@@ -0,0 +1,456 @@
+mod file_drop;
+mod in_memory;
+mod postmark;
+mod sandbox;
+mod smtp;
+
+pub use file_drop::FileDropTransport;
+pub use in_memory::InMemoryTransport;
+pub use postmark::PostmarkTransport;
+pub use sandbox::Sandbox;
+pub use smtp::SmtpTransport;
+
+use crate::domain::SubscriberEmail;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError>;
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl EmailMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Renders the message as RFC 5322 bytes alongside its SMTP envelope.
+    /// lettre has no API for arbitrary headers, so extra headers are prepended
+    /// to the formatted message, once checked for anything that would end them early.
+    pub fn to_mime(&self) -> Result<(lettre::address::Envelope, Vec<u8>), EmailError> {
+        check_header("From", &self.from)?;
+        check_header("To", &self.to)?;
+        check_header("Subject", &self.subject)?;
+        for (name, value) in &self.headers {
+            check_header(name, value)?;
+        }
+        let message = Message::builder()
+            .from(self.from.parse::<Mailbox>()?)
+            .to(self.to.parse::<Mailbox>()?)
+            .subject(&self.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                self.text_body.clone(),
+                self.html_body.clone(),
+            ))?;
+
+        let mut bytes = Vec::new();
+        for (name, value) in &self.headers {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(&message.formatted());
+        Ok((message.envelope().clone(), bytes))
+    }
+}
+
+// a line break would let the value start a header, or the body, of its own
+fn check_header(name: &str, value: &str) -> Result<(), EmailError> {
+    let valid_name = !name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic() && b != b':');
+    if !valid_name || value.contains(['\r', '\n']) {
+        return Err(EmailError::InvalidHeader(name.to_string()));
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmailError {
+    #[error("The {0} header is malformed or contains a line break.")]
+    InvalidHeader(String),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error(transparent)]
+    Message(#[from] lettre::error::Error),
+    #[error(transparent)]
+    Address(#[from] lettre::address::AddressError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl EmailError {
+    /// Whether the failure is worth retrying later, as opposed to a rejection
+    /// that would fail the same way on every attempt.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EmailError::Http(e) => match e.status() {
+                Some(status) => {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                // timeouts and connection failures never got an answer from the provider
+                None => true,
+            },
+            // 4xx replies and connection failures are transient, 5xx replies are not
+            EmailError::Smtp(e) => !e.is_permanent(),
+            EmailError::Message(_) | EmailError::Address(_) | EmailError::InvalidHeader(_) => false,
+            EmailError::Io(_) => true,
+        }
+    }
+}
+
+pub struct EmailClient {
+    sender: SubscriberEmail,
+    transport: Box<dyn EmailTransport>,
+    sandbox: Option<Sandbox>,
+}
+
+impl EmailClient {
+    pub fn new(
+        sender: SubscriberEmail,
+        transport: Box<dyn EmailTransport>,
+        sandbox: Option<Sandbox>,
+    ) -> Self {
+        Self {
+            sender,
+            transport,
+            sandbox,
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_url: Option<&str>,
+    ) -> Result<(), EmailError> {
+        // RFC 8058 one-click unsubscribe, surfaced as a native button by mail clients
+        let mut headers = match unsubscribe_url {
+            Some(url) => vec![
+                ("List-Unsubscribe".to_string(), format!("<{}>", url)),
+                (
+                    "List-Unsubscribe-Post".to_string(),
+                    "List-Unsubscribe=One-Click".to_string(),
+                ),
+            ],
+            None => Vec::new(),
+        };
+
+        let to = match &self.sandbox {
+            Some(sandbox) if !sandbox.is_allowed(recipient) => {
+                tracing::info!(
+                    original_recipient = %recipient.as_ref(),
+                    sandbox_recipient = %sandbox.redirect_to().as_ref(),
+                    "Rewrote the recipient of an email in sandbox mode."
+                );
+                headers.push(("X-Original-To".to_string(), recipient.as_ref().to_owned()));
+                sandbox.redirect_to()
+            }
+            _ => recipient,
+        };
+
+        let message = EmailMessage {
+            from: self.sender.as_ref().to_owned(),
+            to: to.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            html_body: html_content.to_owned(),
+            text_body: text_content.to_owned(),
+            headers,
+        };
+
+        self.transport.send(&message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        EmailClient, EmailError, EmailMessage, InMemoryTransport, PostmarkTransport, Sandbox,
+    };
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+
+            if let Ok(body) = result {
+                dbg!(&body);
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    struct ListUnsubscribeHeadersMatcher;
+
+    impl wiremock::Match for ListUnsubscribeHeadersMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+
+            if let Ok(body) = result {
+                let headers = body["Headers"].as_array().cloned().unwrap_or_default();
+                let header = |name: &str| {
+                    headers
+                        .iter()
+                        .find(|h| h["Name"] == name)
+                        .and_then(|h| h["Value"].as_str().map(str::to_owned))
+                };
+                header("List-Unsubscribe").as_deref()
+                    == Some("<https://example.com/subscriptions/unsubscribe?unsubscribe_token=abc>")
+                    && header("List-Unsubscribe-Post").as_deref()
+                        == Some("List-Unsubscribe=One-Click")
+            } else {
+                false
+            }
+        }
+    }
+
+    fn subscriber_email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn email_client(base_url: String) -> EmailClient {
+        EmailClient::new(
+            subscriber_email(),
+            Box::new(PostmarkTransport::new(
+                base_url,
+                Secret::new(Faker.fake()),
+                std::time::Duration::from_millis(200),
+            )),
+            None,
+        )
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_expected_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(
+                &subscriber_email(),
+                &subject(),
+                &content(),
+                &content(),
+                None,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_list_unsubscribe_headers() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(ListUnsubscribeHeadersMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(
+                &subscriber_email(),
+                &subject(),
+                &content(),
+                &content(),
+                Some("https://example.com/subscriptions/unsubscribe?unsubscribe_token=abc"),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_accepted_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                &subscriber_email(),
+                &subject(),
+                &content(),
+                &content(),
+                None,
+            )
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_rejected_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                &subscriber_email(),
+                &subject(),
+                &content(),
+                &content(),
+                None,
+            )
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_rejected_timeout() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(180));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(
+                &subscriber_email(),
+                &subject(),
+                &content(),
+                &content(),
+                None,
+            )
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: subject(),
+            html_body: content(),
+            text_body: content(),
+            headers: vec![("List-Unsubscribe".into(), "<https://example.com>".into())],
+        }
+    }
+
+    #[test]
+    fn to_mime_rejects_line_breaks_in_headers() {
+        assert_ok!(message().to_mime());
+
+        let mut injected_subject = message();
+        injected_subject.subject = "Hello\r\nBcc: victim@example.com".into();
+        assert!(matches!(
+            injected_subject.to_mime(),
+            Err(EmailError::InvalidHeader(name)) if name == "Subject"
+        ));
+
+        let mut injected_value = message();
+        injected_value.headers[0].1 = "<https://example.com>\nBcc: victim@example.com".into();
+        assert_err!(injected_value.to_mime());
+
+        let mut injected_name = message();
+        injected_name.headers[0].0 = "Bcc: victim@example.com\r\nX-Foo".into();
+        assert_err!(injected_name.to_mime());
+    }
+
+    fn sandboxed_email_client(transport: InMemoryTransport) -> EmailClient {
+        let sandbox = Sandbox::new(
+            SubscriberEmail::parse("sandbox@example.com".to_string()).unwrap(),
+            vec!["staging.example.com".into()],
+        );
+        EmailClient::new(subscriber_email(), Box::new(transport), Some(sandbox))
+    }
+
+    #[tokio::test]
+    async fn sandbox_rewrites_recipients_outside_the_allowlist() {
+        let transport = InMemoryTransport::default();
+        let email_client = sandboxed_email_client(transport.clone());
+        let recipient = SubscriberEmail::parse("someone@gmail.com".to_string()).unwrap();
+
+        let outcome = email_client
+            .send_email(&recipient, &subject(), &content(), &content(), None)
+            .await;
+
+        assert_ok!(outcome);
+        let messages = transport.messages();
+        assert_eq!(messages[0].to, "sandbox@example.com");
+        assert_eq!(
+            messages[0].header("X-Original-To"),
+            Some("someone@gmail.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn sandbox_delivers_allowlisted_recipients_unchanged() {
+        let transport = InMemoryTransport::default();
+        let email_client = sandboxed_email_client(transport.clone());
+        let recipient = SubscriberEmail::parse("qa@staging.example.com".to_string()).unwrap();
+
+        let outcome = email_client
+            .send_email(&recipient, &subject(), &content(), &content(), None)
+            .await;
+
+        assert_ok!(outcome);
+        let messages = transport.messages();
+        assert_eq!(messages[0].to, "qa@staging.example.com");
+        assert_eq!(messages[0].header("X-Original-To"), None);
+    }
+}
@@ -0,0 +1,71 @@
+use super::{EmailError, EmailMessage, EmailTransport};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+
+pub struct PostmarkTransport {
+    http_client: Client,
+    base_url: String,
+    auth_token: Secret<String>,
+}
+
+impl PostmarkTransport {
+    pub fn new(base_url: String, auth_token: Secret<String>, timeout: std::time::Duration) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            auth_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let url = format!("{}/email", self.base_url);
+
+        let body = SendEmailRequest {
+            from: &message.from,
+            to: &message.to,
+            subject: &message.subject,
+            html_body: &message.html_body,
+            text_body: &message.text_body,
+            messagestream: "outbound",
+            headers: message
+                .headers
+                .iter()
+                .map(|(name, value)| EmailHeader { name, value })
+                .collect(),
+        };
+
+        self.http_client
+            .post(&url)
+            .header("X-Postmark-Server-Token", self.auth_token.expose_secret())
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    messagestream: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<EmailHeader<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
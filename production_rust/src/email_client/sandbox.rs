@@ -0,0 +1,74 @@
+use crate::domain::SubscriberEmail;
+
+/// Keeps staging from mailing real subscribers: any recipient that is not
+/// covered by the allowlist is rewritten to a single sandbox address.
+pub struct Sandbox {
+    redirect_to: SubscriberEmail,
+    allowlist: Vec<String>,
+}
+
+impl Sandbox {
+    /// Allowlist entries are either full addresses (`jane@example.com`)
+    /// or domains (`example.com` or `@example.com`).
+    pub fn new(redirect_to: SubscriberEmail, allowlist: Vec<String>) -> Self {
+        let allowlist = allowlist
+            .into_iter()
+            .map(|entry| entry.trim().to_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        Self {
+            redirect_to,
+            allowlist,
+        }
+    }
+
+    pub fn is_allowed(&self, recipient: &SubscriberEmail) -> bool {
+        let recipient = recipient.as_ref().to_lowercase();
+        let domain = recipient.rsplit('@').next().unwrap_or_default();
+        self.allowlist
+            .iter()
+            .any(|entry| match entry.strip_prefix('@') {
+                Some(allowed_domain) => allowed_domain == domain,
+                None if entry.contains('@') => *entry == recipient,
+                None => entry == domain,
+            })
+    }
+
+    pub fn redirect_to(&self) -> &SubscriberEmail {
+        &self.redirect_to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sandbox;
+    use crate::domain::SubscriberEmail;
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    fn sandbox() -> Sandbox {
+        Sandbox::new(
+            email("sandbox@example.com"),
+            vec![
+                "Jane@Staging.io".into(),
+                "team.example.org".into(),
+                "@qa.example.org".into(),
+            ],
+        )
+    }
+
+    #[test]
+    fn allowlisted_addresses_are_allowed() {
+        assert!(sandbox().is_allowed(&email("jane@staging.io")));
+        assert!(!sandbox().is_allowed(&email("john@staging.io")));
+    }
+
+    #[test]
+    fn allowlisted_domains_are_allowed() {
+        assert!(sandbox().is_allowed(&email("anyone@team.example.org")));
+        assert!(sandbox().is_allowed(&email("anyone@qa.example.org")));
+        assert!(!sandbox().is_allowed(&email("anyone@example.org")));
+    }
+}
@@ -0,0 +1,253 @@
+use super::{EmailError, EmailMessage, EmailTransport};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        starttls: bool,
+        timeout: std::time::Duration,
+    ) -> Result<Self, EmailError> {
+        // without STARTTLS the connection, credentials included, stays in plain text
+        let tls = if starttls {
+            Tls::Required(TlsParameters::new(host.into())?)
+        } else {
+            Tls::None
+        };
+        Ok(Self::with_tls(host, port, credentials, tls, timeout))
+    }
+
+    fn with_tls(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        tls: Tls,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+            .port(port)
+            .tls(tls)
+            .timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+
+        Self {
+            mailer: builder.build(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let (envelope, bytes) = message.to_mime()?;
+        self.mailer.send_raw(&envelope, &bytes).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SmtpTransport;
+    use crate::email_client::{EmailMessage, EmailTransport};
+    use claims::{assert_err, assert_ok};
+    use lettre::transport::smtp::client::{Certificate, Tls, TlsParameters};
+    use secrecy::Secret;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls;
+    use tokio_rustls::TlsAcceptor;
+
+    /// Accepts a single SMTP session and records every line the client sends.
+    /// With an `acceptor`, STARTTLS upgrades the session and is recorded as `TLS`.
+    async fn smtp_server(
+        capabilities: &'static str,
+        acceptor: Option<TlsAcceptor>,
+    ) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            if let Some(stream) = converse(stream, capabilities, &log).await {
+                let stream = acceptor.unwrap().accept(stream).await.unwrap();
+                log.lock().unwrap().push("TLS".into());
+                converse(stream, capabilities, &log).await;
+            }
+        });
+
+        (port, received)
+    }
+
+    /// Answers commands until the client quits,
+    /// or hands the stream back when it asks for STARTTLS.
+    async fn converse<S>(stream: S, capabilities: &str, log: &Mutex<Vec<String>>) -> Option<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufReader::new(stream);
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return None;
+            }
+            let line = line.trim_end().to_string();
+            log.lock().unwrap().push(line.clone());
+            let reply = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                "250 2.0.0 Queued\r\n".to_string()
+            } else {
+                match line.split(' ').next().unwrap_or("").to_uppercase().as_str() {
+                    "EHLO" => format!("250-localhost\r\n{}\r\n", capabilities),
+                    "STARTTLS" => {
+                        stream
+                            .write_all(b"220 2.0.0 Ready to start TLS\r\n")
+                            .await
+                            .unwrap();
+                        return Some(stream.into_inner());
+                    }
+                    "AUTH" => "235 2.7.0 Authentication successful\r\n".into(),
+                    "DATA" => {
+                        in_data = true;
+                        "354 End data with <CR><LF>.<CR><LF>\r\n".into()
+                    }
+                    "QUIT" => "221 2.0.0 Bye\r\n".into(),
+                    _ => "250 2.0.0 Ok\r\n".into(),
+                }
+            };
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        }
+    }
+
+    // a self-signed certificate for `localhost`, valid until 2126
+    const CERTIFICATE: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/localhost.crt"
+    ));
+    const PRIVATE_KEY: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/localhost.key"
+    ));
+
+    fn tls_acceptor() -> TlsAcceptor {
+        let certificates = rustls_pemfile::certs(&mut &CERTIFICATE[..])
+            .unwrap()
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut &PRIVATE_KEY[..])
+            .unwrap()
+            .remove(0);
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certificates, rustls::PrivateKey(key))
+            .unwrap();
+        TlsAcceptor::from(Arc::new(config))
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: "Subject".into(),
+            html_body: "<p>Body</p>".into(),
+            text_body: "Body".into(),
+            headers: vec![(
+                "List-Unsubscribe".into(),
+                "<https://example.com/unsubscribe>".into(),
+            )],
+        }
+    }
+
+    #[tokio::test]
+    async fn send_authenticates_and_delivers_message() {
+        let (port, received) = smtp_server("250 AUTH PLAIN LOGIN", None).await;
+        let transport = SmtpTransport::new(
+            "127.0.0.1",
+            port,
+            Some(("user".into(), Secret::new("password".into()))),
+            false,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert_ok!(transport.send(&message()).await);
+
+        let received = received.lock().unwrap();
+        assert!(received.contains(&"AUTH PLAIN AHVzZXIAcGFzc3dvcmQ=".to_string()));
+        assert!(received
+            .iter()
+            .any(|line| line.starts_with("MAIL FROM:<sender@example.com>")));
+        assert!(received.contains(&"RCPT TO:<recipient@example.com>".to_string()));
+        assert!(
+            received.contains(&"List-Unsubscribe: <https://example.com/unsubscribe>".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn send_refuses_servers_without_starttls_when_required() {
+        let (port, received) = smtp_server("250 AUTH PLAIN LOGIN", None).await;
+        let transport = SmtpTransport::new(
+            "127.0.0.1",
+            port,
+            Some(("user".into(), Secret::new("password".into()))),
+            true,
+            std::time::Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert_err!(transport.send(&message()).await);
+
+        let received = received.lock().unwrap();
+        assert!(!received.iter().any(|line| line.starts_with("AUTH")));
+    }
+
+    #[tokio::test]
+    async fn send_upgrades_the_connection_with_starttls_before_authenticating() {
+        let (port, received) =
+            smtp_server("250-STARTTLS\r\n250 AUTH PLAIN LOGIN", Some(tls_acceptor())).await;
+        let tls = TlsParameters::builder("localhost".into())
+            .add_root_certificate(Certificate::from_pem(CERTIFICATE).unwrap())
+            .build_rustls()
+            .unwrap();
+        let transport = SmtpTransport::with_tls(
+            "localhost",
+            port,
+            Some(("user".into(), Secret::new("password".into()))),
+            Tls::Required(tls),
+            std::time::Duration::from_secs(5),
+        );
+
+        assert_ok!(transport.send(&message()).await);
+
+        let received = received.lock().unwrap();
+        let upgrade = received.iter().position(|line| line == "TLS").unwrap();
+        let auth = received
+            .iter()
+            .position(|line| line.starts_with("AUTH"))
+            .unwrap();
+        assert!(upgrade < auth);
+        assert!(received.contains(&"RCPT TO:<recipient@example.com>".to_string()));
+    }
+}
@@ -0,0 +1,30 @@
+use super::delete_expired_keys;
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::startup::get_connection_pool;
+use sqlx::PgPool;
+
+pub async fn run_cleanup_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    cleanup_loop(connection_pool, configuration.idempotency).await
+}
+
+async fn cleanup_loop(
+    connection_pool: PgPool,
+    settings: IdempotencySettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        // a failed run is retried at the next tick, nothing is lost meanwhile
+        match delete_expired_keys(&connection_pool, settings.ttl()).await {
+            Ok(n_deleted) if n_deleted > 0 => {
+                tracing::info!(n_deleted, "Deleted expired idempotency keys")
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to delete expired idempotency keys"
+            ),
+        }
+        tokio::time::sleep(settings.cleanup_interval()).await;
+    }
+}
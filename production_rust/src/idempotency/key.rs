@@ -1,4 +1,6 @@
+use actix_web::http::{Method, Uri};
 use actix_web_flash_messages::FlashMessage;
+use sha2::{Digest, Sha256};
 
 #[derive(Debug)]
 pub struct IdempotencyKey(String);
@@ -28,3 +30,56 @@ impl AsRef<str> for IdempotencyKey {
         &self.0
     }
 }
+
+/// A digest of everything a request asks for, stored next to its key
+/// so that a key reused for a different request can be turned down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestFingerprint(String);
+
+impl RequestFingerprint {
+    pub fn new(method: &Method, uri: &Uri, body: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(method.as_str());
+        hasher.update(b"\n");
+        hasher.update(uri.path_and_query().map_or("", |p| p.as_str()));
+        hasher.update(b"\n");
+        hasher.update(body);
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Self(digest)
+    }
+}
+
+impl AsRef<str> for RequestFingerprint {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestFingerprint;
+    use actix_web::http::{Method, Uri};
+
+    #[test]
+    fn fingerprints_cover_the_target_and_the_payload() {
+        let uri = Uri::from_static("/admin/tokens");
+        let fingerprint = RequestFingerprint::new(&Method::POST, &uri, b"name=ci");
+
+        assert_eq!(
+            fingerprint,
+            RequestFingerprint::new(&Method::POST, &uri, b"name=ci")
+        );
+        assert_ne!(
+            fingerprint,
+            RequestFingerprint::new(&Method::POST, &uri, b"name=cd")
+        );
+        assert_ne!(
+            fingerprint,
+            RequestFingerprint::new(&Method::POST, &Uri::from_static("/admin/users"), b"name=ci")
+        );
+    }
+}
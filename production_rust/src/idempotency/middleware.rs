@@ -0,0 +1,194 @@
+use super::{save_response, try_processing, IdempotencyKey, NextAction, RequestFingerprint};
+use crate::authentication::UserId;
+use crate::configuration::IdempotencySettings;
+use crate::routes::api_error;
+use crate::utils::e500;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{InternalError, PayloadError};
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use futures_util::Stream;
+use sqlx::PgPool;
+use std::pin::Pin;
+use uuid::Uuid;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Where the keys of callers without an account are saved, no user has this id.
+const ANONYMOUS_CALLERS: Uuid = Uuid::nil();
+
+/// Replays the saved response of a POST request sent again with the same
+/// `Idempotency-Key` header or `idempotency_key` form field, and turns away,
+/// with a 422, a key reused for a request with a different payload.
+/// A retry arriving while the first request is still being processed
+/// waits for its response, and gets a 409 if that takes too long.
+/// Requests without a key, and file uploads, go through untouched.
+/// Responses marked `Cache-Control: no-store`, which show a secret once,
+/// are not saved: a retry runs the request again.
+/// Layered inside the authentication middleware, keys are scoped per user,
+/// on public endpoints they share the `ANONYMOUS_CALLERS` namespace.
+pub async fn idempotent_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if req.method() != Method::POST || is_upload(&req) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+
+    // the body is read here to be fingerprinted, then handed back to the handler
+    let body = req.extract::<web::Bytes>().await?;
+    req.set_payload(bytes_to_payload(body.clone()));
+    let fingerprint = RequestFingerprint::new(req.method(), req.uri(), &body);
+    let idempotency_key = find_idempotency_key(&req, &body);
+    req.extensions_mut().insert(fingerprint.clone());
+
+    let idempotency_key = match idempotency_key {
+        Some(key) if !is_exempt(req.path()) => key,
+        _ => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)
+        }
+    };
+    let user_id = req
+        .extensions()
+        .get::<UserId>()
+        .map_or(ANONYMOUS_CALLERS, |user_id| **user_id);
+    let connection_pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| e500("The connection pool is missing from the application data."))?
+        .clone();
+    let settings = req
+        .app_data::<web::Data<IdempotencySettings>>()
+        .ok_or_else(|| e500("The idempotency settings are missing from the application data."))?
+        .clone();
+
+    let transaction = match try_processing(
+        &connection_pool,
+        &idempotency_key,
+        user_id,
+        &fingerprint,
+        &settings,
+    )
+    .await
+    .map_err(e500)?
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            return Ok(req.into_response(saved_response));
+        }
+        NextAction::RejectReusedKey => return Err(reused_key_error(req.path())),
+        NextAction::RequestInProgress => return Err(in_progress_error(req.path())),
+    };
+
+    let response = next.call(req).await?;
+    // dropping the transaction releases the key, a failed request can be retried
+    if response.status().is_server_error() || is_no_store(&response) {
+        return Ok(response.map_into_boxed_body());
+    }
+    let (request, response) = response.into_parts();
+    let response = save_response(
+        transaction,
+        &idempotency_key,
+        user_id,
+        response.map_into_boxed_body(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(ServiceResponse::new(request, response))
+}
+
+fn is_exempt(path: &str) -> bool {
+    match path {
+        // saves its response in the same transaction as the issue itself
+        "/admin/newsletter" => true,
+        // its `idempotency_key` field names the key to act on, not its own
+        "/admin/settings" => true,
+        _ => false,
+    }
+}
+
+fn is_no_store<B>(response: &ServiceResponse<B>) -> bool {
+    response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|d| d.trim() == "no-store"))
+}
+
+// uploads can be far larger than what the payload extractor buffers,
+// they are streamed to their handler untouched
+fn is_upload(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"))
+}
+
+fn find_idempotency_key(req: &ServiceRequest, body: &[u8]) -> Option<IdempotencyKey> {
+    let from_header = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let is_form = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    let from_form = || {
+        serde_urlencoded::from_bytes::<Vec<(String, String)>>(body)
+            .ok()?
+            .into_iter()
+            .find(|(field, _)| field == "idempotency_key")
+            .map(|(_, value)| value)
+    };
+
+    // an empty key is left to the handler to complain about
+    from_header
+        .or_else(|| is_form.then(from_form).flatten())
+        .map(|key| key.trim().to_owned())
+        .filter(|key| !key.is_empty())
+        .and_then(|key| IdempotencyKey::try_from(key).ok())
+}
+
+fn reused_key_error(path: &str) -> actix_web::Error {
+    let e = anyhow::anyhow!("The idempotency key was already used for another request");
+    if path.starts_with("/api") {
+        let response = api_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "idempotency_key_reused",
+            "The idempotency key was already used for a request with a different payload.",
+        );
+        InternalError::from_response(e, response).into()
+    } else {
+        InternalError::new(e, StatusCode::UNPROCESSABLE_ENTITY).into()
+    }
+}
+
+fn in_progress_error(path: &str) -> actix_web::Error {
+    let e = anyhow::anyhow!("A request with the same idempotency key is still in progress");
+    if path.starts_with("/api") {
+        let response = api_error(
+            StatusCode::CONFLICT,
+            "request_in_progress",
+            "A request with the same idempotency key is still in progress, retry later.",
+        );
+        InternalError::from_response(e, response).into()
+    } else {
+        InternalError::new(e, StatusCode::CONFLICT).into()
+    }
+}
+
+fn bytes_to_payload(body: web::Bytes) -> Payload {
+    let stream: Pin<Box<dyn Stream<Item = Result<web::Bytes, PayloadError>>>> =
+        Box::pin(futures_util::stream::once(async move { Ok(body) }));
+    Payload::from(stream)
+}
@@ -1,5 +1,11 @@
+mod cleanup;
 mod key;
+mod middleware;
 mod persistence;
 
-pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response, try_processing, NextAction};
+pub use cleanup::run_cleanup_until_stopped;
+pub use key::{IdempotencyKey, RequestFingerprint};
+pub use middleware::idempotent_requests;
+pub use persistence::{
+    delete_expired_keys, get_saved_response, save_response, try_processing, NextAction,
+};
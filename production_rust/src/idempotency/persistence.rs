@@ -1,11 +1,17 @@
-use super::IdempotencyKey;
+use super::{IdempotencyKey, RequestFingerprint};
+use crate::configuration::IdempotencySettings;
 use actix_web::body::to_bytes;
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
 use sqlx::postgres::PgHasArrayType;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
 use uuid::Uuid;
 
+// SQLSTATE raised when `lock_timeout` runs out
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+
 #[allow(dead_code)]
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
@@ -35,6 +41,7 @@ pub async fn get_saved_response(
         WHERE
             user_id = $1 
             AND idempotency_key = $2
+            AND response_status_code IS NOT NULL
         "#,
         user_id,
         idempotency_key.as_ref(),
@@ -108,58 +115,108 @@ pub async fn save_response(
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
+    // the key was first used for a request with another fingerprint
+    RejectReusedKey,
+    // the first request with the key has yet to save its response
+    RequestInProgress,
 }
 
+/// A key first used more than `ttl` ago is expired: its row is reset
+/// and the request processed again, as if the key had never been seen.
+/// While another request holds the key, this waits for it to finish,
+/// for at most the in-flight timeout.
 pub async fn try_processing(
     connection_pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    fingerprint: &RequestFingerprint,
+    settings: &IdempotencySettings,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = connection_pool.begin().await?;
-    let n_inserted_rows = sqlx::query!(
+    // the wait is bounded for the insert only, not for the handler's own queries
+    sqlx::query!(
+        "SELECT set_config('lock_timeout', $1, true)",
+        format!("{}ms", settings.in_flight_timeout().as_millis()),
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    let inserted = sqlx::query!(
         r#"
         INSERT INTO idempotency (
             user_id,
             idempotency_key,
-            created_at
+            created_at,
+            request_fingerprint
         )
-        VALUES ($1, $2, now()) 
-        ON CONFLICT DO NOTHING
+        VALUES ($1, $2, now(), $4)
+        ON CONFLICT (user_id, idempotency_key) DO UPDATE
+        SET
+            created_at = now(),
+            response_status_code = NULL,
+            response_headers = NULL,
+            response_body = NULL,
+            validity = true,
+            request_fingerprint = $4
+        WHERE idempotency.created_at < $3
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        expiry_cutoff(settings.ttl())?,
+        fingerprint.as_ref(),
     )
     .execute(&mut transaction)
-    .await?
-    .rows_affected();
+    .await;
+    let n_inserted_rows = match inserted {
+        Ok(result) => result.rows_affected(),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(LOCK_NOT_AVAILABLE) => {
+            return Ok(NextAction::RequestInProgress);
+        }
+        Err(e) => return Err(e.into()),
+    };
     if n_inserted_rows > 0 {
-        Ok(NextAction::StartProcessing(transaction))
-    } else {
-        let saved_response = get_saved_response(connection_pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
-        Ok(NextAction::ReturnSavedResponse(saved_response))
+        sqlx::query!("SELECT set_config('lock_timeout', '0', true)")
+            .fetch_one(&mut transaction)
+            .await?;
+        return Ok(NextAction::StartProcessing(transaction));
     }
+
+    let saved_fingerprint = sqlx::query!(
+        r#"
+        SELECT request_fingerprint FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+    )
+    .fetch_one(connection_pool)
+    .await?
+    .request_fingerprint;
+    if saved_fingerprint.is_some_and(|saved| saved != fingerprint.as_ref()) {
+        return Ok(NextAction::RejectReusedKey);
+    }
+    // a row committed without a response is never going to get one
+    match get_saved_response(connection_pool, idempotency_key, user_id).await? {
+        Some(saved_response) => Ok(NextAction::ReturnSavedResponse(saved_response)),
+        None => Ok(NextAction::RequestInProgress),
+    }
+}
+
+/// Returns how many expired keys were deleted.
+#[tracing::instrument(name = "Delete expired idempotency keys", skip(connection_pool))]
+pub async fn delete_expired_keys(
+    connection_pool: &PgPool,
+    ttl: Duration,
+) -> Result<u64, anyhow::Error> {
+    let n_deleted_rows = sqlx::query!(
+        "DELETE FROM idempotency WHERE created_at < $1",
+        expiry_cutoff(ttl)?,
+    )
+    .execute(connection_pool)
+    .await?
+    .rows_affected();
+    Ok(n_deleted_rows)
 }
 
-// pub async fn delete_expired_keys(
-//     connection_pool: &PgPool,
-//     idempotency_key: &IdempotencyKey,
-//     user_id: Uuid,
-//     expiry: chrono::DateTime<Utc>,
-// ) -> Result<(), anyhow::Error> {
-//     sqlx::query!(
-//         r#"
-//         DELETE FROM idempotency
-//         WHERE
-//             user_id = $1
-//             AND idempotency_key = $2
-//         "#,
-//         user_id,
-//         idempotency_key.as_ref(),
-//         expiry,
-//     )
-//     .execute(connection_pool)
-//     .await?;
-//     Ok(())
-// }
+fn expiry_cutoff(ttl: Duration) -> Result<DateTime<Utc>, anyhow::Error> {
+    Ok(Utc::now() - chrono::Duration::from_std(ttl)?)
+}
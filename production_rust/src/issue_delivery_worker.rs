@@ -1,6 +1,11 @@
+use crate::configuration::{IssueDeliverySettings, Settings};
 use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
-use crate::{configuration::Settings, startup::get_connection_pool};
+use crate::email_client::{EmailClient, EmailError};
+use crate::routes::UnsubscribeLinks;
+use crate::startup::get_connection_pool;
+use crate::templates::{MergeFields, Templates};
+use chrono::Utc;
+use rand::Rng;
 use sqlx::{PgPool, Postgres, Transaction};
 use std::time::Duration;
 use tracing::{field::display, Span};
@@ -8,17 +13,43 @@ use uuid::Uuid;
 
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email_client.client();
+    let email_client = configuration
+        .email_client
+        .client()
+        .map_err(anyhow::Error::msg)?;
+    let templates = Templates::load(&configuration.templates.directory)?;
 
-    worker_loop(connection_pool, email_client).await
+    let unsubscribe_links = UnsubscribeLinks::new(
+        configuration.application.base_url,
+        configuration.application.unsubscribe_signing_key,
+    );
+    worker_loop(
+        connection_pool,
+        email_client,
+        templates,
+        unsubscribe_links,
+        configuration.issue_delivery,
+    )
+    .await
 }
 
 async fn worker_loop(
     connection_pool: PgPool,
     email_client: EmailClient,
+    templates: Templates,
+    unsubscribe_links: UnsubscribeLinks,
+    settings: IssueDeliverySettings,
 ) -> Result<(), anyhow::Error> {
     loop {
-        match try_execute_task(&connection_pool, &email_client).await {
+        match try_execute_task(
+            &connection_pool,
+            &email_client,
+            &templates,
+            &unsubscribe_links,
+            &settings,
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -41,42 +72,72 @@ pub enum ExecutionOutcome {
     fields(
         newsletter_issue_id=tracing::field::Empty,
         subscriber_email=tracing::field::Empty,
+        n_retries=tracing::field::Empty,
     ),
     err
 )]
 pub async fn try_execute_task(
     connection_pool: &PgPool,
     email_client: &EmailClient,
+    templates: &Templates,
+    unsubscribe_links: &UnsubscribeLinks,
+    settings: &IssueDeliverySettings,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
+    enqueue_due_issues(connection_pool).await?;
     let task = dequeue_task(connection_pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (transaction, issue_id, email) = task.unwrap();
+    let (transaction, task) = task.unwrap();
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
-
-    match SubscriberEmail::parse(email.clone()) {
-        Ok(email) => {
-            let issue = get_issue(connection_pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
+        .record("newsletter_issue_id", display(task.newsletter_issue_id))
+        .record("subscriber_email", display(&task.subscriber_email))
+        .record("n_retries", task.n_retries);
+
+    match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => match get_recipient(connection_pool, email.as_ref()).await? {
+            Some(recipient) => {
+                let issue = get_issue(connection_pool, task.newsletter_issue_id).await?;
+                let unsubscribe_link = unsubscribe_links.link(&recipient.unsubscribe_token);
+                let fields = MergeFields {
+                    name: &recipient.name,
+                    email: email.as_ref(),
+                    unsubscribe_url: &unsubscribe_link,
+                };
+                let rendered = match templates.render_issue(
                     &issue.title,
-                    &issue.text_content,
                     &issue.html_content,
-                )
-                .await
-            {
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. \
-                    Skipping.",
-                );
+                    &issue.text_content,
+                    &fields,
+                ) {
+                    Ok(rendered) => rendered,
+                    Err(e) => {
+                        tracing::error!(
+                            error.message = %e,
+                            "Failed to render the issue. Moving it to the dead-letter table.",
+                        );
+                        dead_letter_task(transaction, &task, &e.to_string()).await?;
+                        return Ok(ExecutionOutcome::TaskCompleted);
+                    }
+                };
+                if let Err(e) = email_client
+                    .send_email(
+                        &email,
+                        &issue.title,
+                        &rendered.html,
+                        &rendered.text,
+                        Some(&unsubscribe_link),
+                    )
+                    .await
+                {
+                    handle_delivery_failure(transaction, &task, e, settings).await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
             }
-        }
+            None => {
+                tracing::info!("Skipping a subscriber who is no longer confirmed.");
+            }
+        },
         Err(e) => {
             tracing::error!(
                 error.cause_chain = ?e,
@@ -87,45 +148,189 @@ pub async fn try_execute_task(
         }
     }
 
-    delete_task(transaction, issue_id, &email).await?;
+    delete_task(transaction, &task).await?;
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
-#[allow(dead_code)]
+async fn handle_delivery_failure(
+    transaction: PgTransaction,
+    task: &DeliveryTask,
+    e: EmailError,
+    settings: &IssueDeliverySettings,
+) -> Result<(), anyhow::Error> {
+    if e.is_retryable() && task.n_retries + 1 < settings.max_attempts {
+        let delay = backoff_delay(
+            task.n_retries,
+            settings.backoff_base(),
+            settings.backoff_max(),
+        );
+        tracing::warn!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            retry_in_milliseconds = delay.as_millis() as u64,
+            "Failed to deliver issue to a confirmed subscriber. \
+            Retrying.",
+        );
+        schedule_retry(transaction, task, delay).await
+    } else {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to deliver issue to a confirmed subscriber. \
+            Moving it to the dead-letter table.",
+        );
+        dead_letter_task(transaction, task, &e.to_string()).await
+    }
+}
+
+/// Exponential backoff with "equal jitter": half of the delay is fixed,
+/// the other half is random so that failed deliveries do not retry in lockstep.
+pub fn backoff_delay(n_retries: i32, base: Duration, max: Duration) -> Duration {
+    let exponent = u32::try_from(n_retries).unwrap_or(0);
+    let delay = base.saturating_mul(2u32.saturating_pow(exponent)).min(max);
+    let half = delay / 2;
+    half + half.mul_f64(rand::thread_rng().gen::<f64>())
+}
+
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     connection_pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+) -> Result<Option<(PgTransaction, DeliveryTask)>, anyhow::Error> {
     let mut transaction = connection_pool.begin().await?;
-    let r = sqlx::query!(
+    let r = sqlx::query_as!(
+        DeliveryTask,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
-        FOR UPDATE
+        SELECT q.newsletter_issue_id, q.subscriber_email, q.n_retries
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE q.execute_after <= now() AND n.send_at <= now()
+        FOR UPDATE OF q
         SKIP LOCKED
         LIMIT 1
         "#,
     )
     .fetch_optional(&mut transaction)
     .await?;
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
+    Ok(r.map(|task| (transaction, task)))
+}
+
+/// Scheduled issues get their recipients when they become due, so that whoever
+/// confirms or unsubscribes before `send_at` is accounted for.
+#[tracing::instrument(skip_all)]
+async fn enqueue_due_issues(connection_pool: &PgPool) -> Result<(), anyhow::Error> {
+    mark_drained_issues_sent(connection_pool).await?;
+    loop {
+        let mut transaction = connection_pool.begin().await?;
+        let issue = sqlx::query!(
+            r#"
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE status = 'scheduled' AND send_at <= now()
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+        let newsletter_issue_id = match issue {
+            Some(issue) => issue.newsletter_issue_id,
+            None => return Ok(()),
+        };
+        enqueue_delivery_tasks(&mut transaction, newsletter_issue_id).await?;
+        mark_issue_publishing(&mut transaction, newsletter_issue_id).await?;
+        transaction.commit().await?;
     }
 }
 
+/// Queues the issue for every confirmed subscriber matching the audience saved on it.
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        ) 
+        SELECT n.newsletter_issue_id, s.email
+        FROM subscriptions s
+        JOIN newsletter_issues n ON n.newsletter_issue_id = $1
+        WHERE
+            s.status = 'confirmed'
+            AND (n.list_id IS NULL OR EXISTS (
+                SELECT 1 FROM subscriber_list_memberships m
+                WHERE m.list_id = n.list_id AND m.subscriber_id = s.id
+            ))
+            AND (n.segment_signed_up_from IS NULL
+                OR s.subscribed_at >= n.segment_signed_up_from)
+            AND (n.segment_signed_up_to IS NULL
+                OR s.subscribed_at < n.segment_signed_up_to + 1)
+            AND (n.segment_tag IS NULL OR EXISTS (
+                SELECT 1 FROM subscriber_tags t
+                WHERE t.subscriber_id = s.id AND t.tag = n.segment_tag
+            ))
+            AND (n.segment_email_domain IS NULL
+                OR lower(split_part(s.email, '@', 2)) = n.segment_email_domain)
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn mark_issue_publishing(
+    transaction: &mut PgTransaction,
+    newsletter_issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'publishing'
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+// an issue counts as sent once every delivery has either gone out or been dead-lettered
+#[tracing::instrument(skip_all)]
+async fn mark_drained_issues_sent(connection_pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues n
+        SET status = 'sent'
+        WHERE n.status = 'publishing' AND NOT EXISTS (
+            SELECT 1 FROM issue_delivery_queue q
+            WHERE q.newsletter_issue_id = n.newsletter_issue_id
+        )
+        "#,
+    )
+    .execute(connection_pool)
+    .await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn delete_task(
     mut transaction: PgTransaction,
-    issue_id: Uuid,
-    email: &str,
+    task: &DeliveryTask,
 ) -> Result<(), anyhow::Error> {
     sqlx::query!(
         r#"
@@ -134,8 +339,35 @@ async fn delete_task(
             newsletter_issue_id = $1 AND
             subscriber_email = $2
         "#,
-        issue_id,
-        email,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn schedule_retry(
+    mut transaction: PgTransaction,
+    task: &DeliveryTask,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let execute_after = Utc::now() + chrono::Duration::from_std(delay)?;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = $3
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        execute_after,
     )
     .execute(&mut transaction)
     .await?;
@@ -143,6 +375,66 @@ async fn delete_task(
     Ok(())
 }
 
+#[tracing::instrument(skip_all)]
+async fn dead_letter_task(
+    mut transaction: PgTransaction,
+    task: &DeliveryTask,
+    last_error: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures (
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            last_error,
+            failed_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_retries = EXCLUDED.n_retries,
+            last_error = EXCLUDED.last_error,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        task.n_retries,
+        last_error,
+    )
+    .execute(&mut transaction)
+    .await?;
+    delete_task(transaction, task).await
+}
+
+struct Recipient {
+    name: String,
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_recipient(
+    connection_pool: &PgPool,
+    email: &str,
+) -> Result<Option<Recipient>, anyhow::Error> {
+    let r = sqlx::query_as!(
+        Recipient,
+        r#"
+        SELECT subscriptions.name, unsubscribe_token
+        FROM unsubscribe_tokens
+        JOIN subscriptions ON subscriptions.id = unsubscribe_tokens.subscriber_id
+        WHERE
+            subscriptions.email = $1 AND
+            subscriptions.status = 'confirmed'
+        LIMIT 1
+        "#,
+        email
+    )
+    .fetch_optional(connection_pool)
+    .await?;
+    Ok(r)
+}
+
 #[allow(dead_code)]
 struct NewsletterIssue {
     title: String,
@@ -169,3 +461,34 @@ async fn get_issue(
     .await?;
     Ok(issue)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::backoff_delay;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let base = Duration::from_secs(30);
+        let max = Duration::from_secs(3600);
+
+        for n_retries in 0..4 {
+            let expected = base * 2u32.pow(n_retries as u32);
+            let delay = backoff_delay(n_retries, base, max);
+            assert!(delay >= expected / 2, "{:?} < {:?}", delay, expected / 2);
+            assert!(delay <= expected, "{:?} > {:?}", delay, expected);
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let base = Duration::from_secs(30);
+        let max = Duration::from_secs(3600);
+
+        for n_retries in [10, 31, 64, i32::MAX] {
+            let delay = backoff_delay(n_retries, base, max);
+            assert!(delay >= max / 2);
+            assert!(delay <= max);
+        }
+    }
+}
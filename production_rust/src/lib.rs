@@ -1,11 +1,17 @@
+pub mod audit;
 pub mod authentication;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod markdown;
+pub mod rate_limiter;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
+pub mod subscriber_data;
+pub mod subscriber_lists;
 pub mod telemetry;
+pub mod templates;
 pub mod utils;
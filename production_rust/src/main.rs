@@ -1,4 +1,5 @@
 use production_rust::configuration::get_configuration;
+use production_rust::idempotency::run_cleanup_until_stopped;
 use production_rust::issue_delivery_worker::run_worker_until_stopped;
 use production_rust::startup::Application;
 use production_rust::telemetry::{get_subscriber, init_subscriber};
@@ -15,11 +16,13 @@ async fn main() -> anyhow::Result<()> {
     let application = Application::build(config.clone()).await?;
 
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(config));
+    let worker_task = tokio::spawn(run_worker_until_stopped(config.clone()));
+    let cleanup_task = tokio::spawn(run_cleanup_until_stopped(config));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = cleanup_task => report_exit("Idempotency cleanup", o),
     };
 
     Ok(())
@@ -0,0 +1,142 @@
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag};
+
+fn parser(markdown: &str) -> Parser<'_, '_> {
+    Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    )
+}
+
+/// Renders Markdown to HTML, stripped of anything unsafe to mail out
+/// (scripts, event handlers, inline styles...).
+pub fn render_html(markdown: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser(markdown));
+    ammonia::clean(&html)
+}
+
+/// Renders Markdown to a plain-text alternative: headings are underlined
+/// and links are collected as numbered footnotes at the end of the text.
+pub fn render_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut footnotes: Vec<String> = Vec::new();
+    let mut heading_start = 0;
+    let mut links: Vec<(String, usize)> = Vec::new();
+    // one entry per open list, holding the next number of an ordered list
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    for event in parser(markdown) {
+        match event {
+            Event::Start(Tag::Heading(..)) => heading_start = text.len(),
+            Event::End(Tag::Heading(level, ..)) => {
+                let width = text[heading_start..].chars().count();
+                let underline = if level == HeadingLevel::H1 { "=" } else { "-" };
+                text.push('\n');
+                text.push_str(&underline.repeat(width));
+                text.push_str("\n\n");
+            }
+            Event::End(Tag::Paragraph) | Event::End(Tag::CodeBlock(_)) => {
+                text.push_str(if lists.is_empty() { "\n\n" } else { "\n" })
+            }
+            Event::Start(Tag::List(first_number)) => {
+                if !lists.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                lists.push(first_number);
+            }
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                let indent = "  ".repeat(lists.len().saturating_sub(1));
+                let marker = match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                text.push_str(&indent);
+                text.push_str(&marker);
+            }
+            Event::End(Tag::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::Start(Tag::Link(_, destination, _))
+            | Event::Start(Tag::Image(_, destination, _)) => {
+                links.push((destination.to_string(), text.len()));
+            }
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => {
+                // bare links already spell out their destination
+                if let Some((destination, start)) = links.pop() {
+                    if !text[start..].ends_with(destination.trim_start_matches("mailto:")) {
+                        footnotes.push(destination);
+                        text.push_str(&format!("[{}]", footnotes.len()));
+                    }
+                }
+            }
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("----\n\n"),
+            Event::TaskListMarker(checked) => text.push_str(if checked { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    let mut text = text.trim_end().to_string();
+    if !footnotes.is_empty() {
+        text.push_str("\n\n");
+        for (n, destination) in footnotes.iter().enumerate() {
+            text.push_str(&format!("[{}] {}\n", n + 1, destination));
+        }
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_html, render_text};
+
+    #[test]
+    fn headings_are_underlined() {
+        let text = render_text("# Weekly digest\n\n## News\n\nHello");
+        assert_eq!(text, "Weekly digest\n=============\n\nNews\n----\n\nHello");
+    }
+
+    #[test]
+    fn links_become_footnotes() {
+        let text = render_text(
+            "Read [the post](https://example.com/post) and [the docs](https://example.com/docs).",
+        );
+        assert_eq!(
+            text,
+            "Read the post[1] and the docs[2].\n\n\
+            [1] https://example.com/post\n\
+            [2] https://example.com/docs"
+        );
+    }
+
+    #[test]
+    fn bare_links_are_not_duplicated_as_footnotes() {
+        let text = render_text("Visit <https://example.com>.");
+        assert_eq!(text, "Visit https://example.com.");
+    }
+
+    #[test]
+    fn lists_keep_their_markers() {
+        let text = render_text("- one\n- two\n  1. nested\n  2. again\n\nAfter");
+        assert_eq!(text, "- one\n- two\n  1. nested\n  2. again\n\nAfter");
+    }
+
+    #[test]
+    fn html_is_rendered_and_sanitized() {
+        let html = render_html(
+            "# Title\n\n<script>alert(1)</script>\n\n**bold** [link](https://example.com)",
+        );
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains(r#"href="https://example.com""#));
+        assert!(!html.contains("<script>"));
+    }
+}
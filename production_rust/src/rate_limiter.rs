@@ -0,0 +1,178 @@
+use crate::configuration::{RateLimit, RateLimitSettings};
+use actix_web::HttpRequest;
+use anyhow::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::time::Duration;
+
+/// The requests that are limited, each with its own settings.
+#[derive(Copy, Clone, Debug)]
+pub enum RateLimitedAction {
+    PasswordReset,
+    ConfirmationResend,
+}
+
+impl RateLimitedAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitedAction::PasswordReset => "password_reset",
+            RateLimitedAction::ConfirmationResend => "confirmation_resend",
+        }
+    }
+}
+
+/// Counts requests per named account or address and per client address in Redis,
+/// refusing further ones for a while once there are too many.
+/// Every request counts, whether it led anywhere or not.
+#[derive(Clone)]
+pub struct RateLimiter {
+    connection: ConnectionManager,
+    settings: RateLimitSettings,
+    trust_proxy_headers: bool,
+}
+
+impl RateLimiter {
+    pub fn new(
+        connection: ConnectionManager,
+        settings: RateLimitSettings,
+        trust_proxy_headers: bool,
+    ) -> Self {
+        Self {
+            connection,
+            settings,
+            trust_proxy_headers,
+        }
+    }
+
+    pub fn client_ip(&self, request: &HttpRequest) -> Option<String> {
+        client_ip(request, self.trust_proxy_headers)
+    }
+
+    /// How long the caller has to wait before trying again,
+    /// `None` if neither `key` nor the address are blocked.
+    #[tracing::instrument(name = "Check rate limit", skip(self))]
+    pub async fn retry_after(
+        &self,
+        action: RateLimitedAction,
+        key: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<Duration>, anyhow::Error> {
+        let mut keys = vec![blocked_key(action.as_str(), "key", key)];
+        keys.extend(ip_address.map(|ip| blocked_key(action.as_str(), "ip", ip)));
+        blocked_for(&self.connection, &keys).await
+    }
+
+    #[tracing::instrument(name = "Record a rate limited request", skip(self))]
+    pub async fn record(
+        &self,
+        action: RateLimitedAction,
+        key: &str,
+        ip_address: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let limit = self.limit(action);
+        let mut counters = vec![(
+            counter_key(action.as_str(), "key", key),
+            blocked_key(action.as_str(), "key", key),
+            limit.max_requests,
+        )];
+        counters.extend(ip_address.map(|ip| {
+            (
+                counter_key(action.as_str(), "ip", ip),
+                blocked_key(action.as_str(), "ip", ip),
+                limit.max_requests_per_ip,
+            )
+        }));
+
+        for (counter, blocked, max_requests) in counters {
+            let n_requests = count(&self.connection, &counter, limit.window()).await?;
+            if n_requests >= max_requests {
+                block(&self.connection, &blocked, limit.window()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn limit(&self, action: RateLimitedAction) -> &RateLimit {
+        match action {
+            RateLimitedAction::PasswordReset => &self.settings.password_reset,
+            RateLimitedAction::ConfirmationResend => &self.settings.confirmation_resend,
+        }
+    }
+}
+
+/// The address of the client, as far as it can be trusted.
+pub fn client_ip(request: &HttpRequest, trust_proxy_headers: bool) -> Option<String> {
+    if trust_proxy_headers {
+        request
+            .connection_info()
+            .realip_remote_addr()
+            .map(Into::into)
+    } else {
+        request.peer_addr().map(|address| address.ip().to_string())
+    }
+}
+
+/// Increments the counter at `key`, which is forgotten `window` after the last increment.
+pub async fn count(
+    connection: &ConnectionManager,
+    key: &str,
+    window: Duration,
+) -> Result<u32, anyhow::Error> {
+    let mut connection = connection.clone();
+    let (n,): (u32,) = redis::pipe()
+        .atomic()
+        .incr(key, 1)
+        .expire(key, window.as_secs() as usize)
+        .ignore()
+        .query_async(&mut connection)
+        .await
+        .context("Failed to increment a counter in Redis.")?;
+    Ok(n)
+}
+
+/// Blocks `key` for `delay`, replacing any earlier block.
+pub async fn block(
+    connection: &ConnectionManager,
+    key: &str,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let mut connection = connection.clone();
+    let _: () = redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .arg("PX")
+        .arg(delay.as_millis() as u64)
+        .query_async(&mut connection)
+        .await
+        .context("Failed to store a block in Redis.")?;
+    Ok(())
+}
+
+/// The longest time any of `keys` is still blocked for.
+pub async fn blocked_for(
+    connection: &ConnectionManager,
+    keys: &[String],
+) -> Result<Option<Duration>, anyhow::Error> {
+    let mut connection = connection.clone();
+    let mut retry_after = None;
+    for key in keys {
+        // -2 for a missing key, -1 for a key without expiry
+        let milliseconds: i64 = connection
+            .pttl(key)
+            .await
+            .context("Failed to read a block from Redis.")?;
+        if milliseconds > 0 {
+            let delay = Duration::from_millis(milliseconds as u64);
+            retry_after = retry_after.max(Some(delay));
+        }
+    }
+    Ok(retry_after)
+}
+
+fn counter_key(action: &str, kind: &str, value: &str) -> String {
+    format!("{}_requests:{}:{}", action, kind, value)
+}
+
+fn blocked_key(action: &str, kind: &str, value: &str) -> String {
+    format!("{}_blocked:{}:{}", action, kind, value)
+}
@@ -1,114 +1,76 @@
+use crate::routes::admin_page;
 use crate::session_state::TypedSession;
 use crate::utils::e500;
-use actix_web::http::header::{ContentType, LOCATION};
+use actix_web::http::header::LOCATION;
 use actix_web::{web, HttpResponse};
 use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-pub async fn admin_dashboard(
-    session: TypedSession,
-    connection_pool: web::Data<PgPool>,
-) -> Result<HttpResponse, actix_web::Error> {
-    let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
-        get_username(user_id, &connection_pool)
-            .await
-            .map_err(e500)?
-    } else {
-        return Ok(HttpResponse::SeeOther()
-            .insert_header((LOCATION, "/login"))
-            .finish());
-    };
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Admin dashboard</title>
-    <style>
-        body {{
-            font-family: Arial, sans-serif;
-            margin: 0;
-            background-color: #000000;
-            display: flex;
-            justify-content: center;
-            align-items: center;
-            height: 100vh;
-        }}
-
-        .dashboard {{
-            background-color: #ffffff;
-            padding: 20px;
-            border-radius: 5px;
-            box-shadow: 0 2px 5px rgba(0, 0, 0, 0.1);
-        }}
-
-        h1 {{
-            text-align: center;
-            color: #3B5323;
-        }}
-
-        o1 {{
-            list-style-type: none;
-            padding-left: 0;
-        }}
+const STYLE: &str = r#"
+        .form-container {
+            max-width: 400px;
+        }
 
-        li {{
+        li {
             margin-bottom: 10px;
-        }}
-        
-        li a:hover {{
-            text-decoration: underline;
-        }}
-
-        .form-container {{
-            background-color: #F8F8F8;
-            padding: 20px;
-            border-radius: 5px;
-            box-shadow: 0 2px 5px rgba(0, 0, 0, 0.1);
-            max-width: 400px;
-            margin: 0 auto;
-        }}
+        }
 
+        li a:hover {
+            text-decoration: underline;
+        }
 
-        form {{
+        form {
             display: flex;
             flex-direction: column;
-        }}
-
-        label {{
-            margin-bottom: 10px;
-            color: #3B5323;
-        }}
+        }
 
-        input[type="submit"] {{
+        input[type="submit"] {
             padding: 10px 20px;
             background-color: #3B5323;
             color: #ffffff;
             border: none;
             border-radius: 3px;
             cursor: pointer;
-        }}
-    </style>
-</head>
-<body>
-    <div class="dashboard">
-        <h1>Welcome {username}!</h1>
-        <p>Available actions:</p>
+        }
+"#;
+
+pub async fn admin_dashboard(
+    session: TypedSession,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
+        get_username(user_id, &connection_pool)
+            .await
+            .map_err(e500)?
+    } else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/login"))
+            .finish());
+    };
+    Ok(admin_page(
+        &format!("Welcome {}!", htmlescape::encode_minimal(&username)),
+        STYLE,
+        r#"<p>Available actions:</p>
         <ol>
             <li><a href="/admin/newsletter">Send a newsletter</a></li>
+            <li><a href="/admin/drafts">Edit drafts</a></li>
+            <li><a href="/admin/issues">Manage scheduled issues</a></li>
+            <li><a href="/admin/subscribers">Manage subscribers</a></li>
+            <li><a href="/admin/lists">Manage lists</a></li>
+            <li><a href="/admin/privacy">Handle data requests</a></li>
             <li><a href="/admin/password">Change password</a></li>
+            <li><a href="/admin/totp">Two-factor authentication</a></li>
+            <li><a href="/admin/sessions">Active sessions</a></li>
             <li><a href="/admin/settings">Manage keys</a></li>
+            <li><a href="/admin/failures">Inspect failed deliveries</a></li>
+            <li><a href="/admin/tokens">Manage API tokens</a></li>
+            <li><a href="/admin/users">Manage users</a></li>
         </ol>
-        <form name ="logoutForm" action="/admin/logout" method="post">
+        <form name="logoutForm" action="/admin/logout" method="post">
             <input type="submit" value="Logout">
-        </form>
-    </div>
-</body>
-</html>"#
-        )))
+        </form>"#,
+    ))
 }
 
 #[tracing::instrument(name = "Get username", skip(connection_pool))]
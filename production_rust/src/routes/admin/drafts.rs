@@ -0,0 +1,75 @@
+use crate::routes::admin_page;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const STYLE: &str = r#"
+        a {
+            color: #3B5323;
+        }
+"#;
+
+struct Draft {
+    newsletter_issue_id: Uuid,
+    title: String,
+}
+
+pub async fn drafts(connection_pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let drafts = get_drafts(&connection_pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for draft in &drafts {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{title}</td>
+                <td><a href="/admin/issues/preview?newsletter_issue_id={newsletter_issue_id}">Preview</a></td>
+                <td><a href="/admin/newsletter?newsletter_issue_id={newsletter_issue_id}">Edit</a></td>
+            </tr>"#,
+            title = htmlescape::encode_minimal(&draft.title),
+            newsletter_issue_id = draft.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+    if drafts.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="3">No drafts.</td></tr>"#);
+    }
+
+    Ok(admin_page(
+        "Drafts",
+        STYLE,
+        &format!(
+            r#"<table>
+            <tr>
+                <th>Issue</th>
+                <th></th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <div class="button-container">
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </div>"#
+        ),
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_drafts(connection_pool: &PgPool) -> Result<Vec<Draft>, anyhow::Error> {
+    let drafts = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT newsletter_issue_id, title
+        FROM newsletter_issues
+        WHERE status = 'draft'
+        ORDER BY title
+        "#,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve drafts.")?;
+    Ok(drafts)
+}
@@ -0,0 +1,113 @@
+use crate::routes::admin_page;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct DeliveryFailure {
+    newsletter_issue_id: Uuid,
+    title: String,
+    subscriber_email: String,
+    n_retries: i32,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+pub async fn delivery_failures(
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let failures = get_delivery_failures(&connection_pool)
+        .await
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for failure in &failures {
+        let subscriber_email = htmlescape::encode_minimal(&failure.subscriber_email);
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{title}</td>
+                <td>{subscriber_email}</td>
+                <td>{n_retries}</td>
+                <td>{last_error}</td>
+                <td>{failed_at}</td>
+                <td>
+                    <form action="/admin/failures/requeue" method="post">
+                        <input hidden type="text" name="newsletter_issue_id" value="{newsletter_issue_id}">
+                        <input hidden type="text" name="subscriber_email" value="{subscriber_email}">
+                        <button type="submit">Requeue</button>
+                    </form>
+                </td>
+            </tr>"#,
+            title = htmlescape::encode_minimal(&failure.title),
+            n_retries = failure.n_retries,
+            last_error = htmlescape::encode_minimal(&failure.last_error),
+            failed_at = failure.failed_at.to_rfc2822(),
+            newsletter_issue_id = failure.newsletter_issue_id,
+        )
+        .unwrap();
+    }
+    if failures.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="6">No failed deliveries.</td></tr>"#);
+    }
+
+    Ok(admin_page(
+        "Failed Deliveries",
+        "",
+        &format!(
+            r#"{msg_html}
+        <table>
+            <tr>
+                <th>Issue</th>
+                <th>Subscriber</th>
+                <th>Retries</th>
+                <th>Last error</th>
+                <th>Failed at</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <div class="button-container">
+            <form action="/admin/failures/requeue_all" method="post">
+                <button type="submit">Requeue all</button>
+                <a href="/admin/dashboard"><button type="button">Back</button></a>
+            </form>
+        </div>"#
+        ),
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_delivery_failures(
+    connection_pool: &PgPool,
+) -> Result<Vec<DeliveryFailure>, anyhow::Error> {
+    let failures = sqlx::query_as!(
+        DeliveryFailure,
+        r#"
+        SELECT
+            f.newsletter_issue_id,
+            n.title,
+            f.subscriber_email,
+            f.n_retries,
+            f.last_error,
+            f.failed_at
+        FROM issue_delivery_failures f
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        ORDER BY f.failed_at DESC
+        "#,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve failed deliveries.")?;
+    Ok(failures)
+}
@@ -0,0 +1,91 @@
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+}
+
+#[tracing::instrument(name = "Requeue a failed delivery", skip(form, connection_pool))]
+pub async fn requeue_failure(
+    form: web::Form<FormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = connection_pool.begin().await.map_err(e500)?;
+    let n_requeued = requeue(
+        &mut transaction,
+        Some((form.newsletter_issue_id, &form.subscriber_email)),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    if n_requeued > 0 {
+        FlashMessage::info("The delivery has been requeued.").send();
+    } else {
+        FlashMessage::error("No matching failed delivery found.").send();
+    }
+    Ok(see_other("/admin/failures"))
+}
+
+#[tracing::instrument(name = "Requeue all failed deliveries", skip(connection_pool))]
+pub async fn requeue_all_failures(
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = connection_pool.begin().await.map_err(e500)?;
+    let n_requeued = requeue(&mut transaction, None).await.map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!("{} deliveries have been requeued.", n_requeued)).send();
+    Ok(see_other("/admin/failures"))
+}
+
+// `None` requeues every failed delivery
+async fn requeue(
+    transaction: &mut Transaction<'_, Postgres>,
+    failure: Option<(Uuid, &str)>,
+) -> Result<u64, sqlx::Error> {
+    let (newsletter_issue_id, subscriber_email) = failure.unzip();
+
+    let n_requeued = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            execute_after
+        )
+        SELECT newsletter_issue_id, subscriber_email, 0, now()
+        FROM issue_delivery_failures
+        WHERE
+            ($1::uuid IS NULL OR newsletter_issue_id = $1)
+            AND ($2::text IS NULL OR subscriber_email = $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        subscriber_email,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_failures
+        WHERE
+            ($1::uuid IS NULL OR newsletter_issue_id = $1)
+            AND ($2::text IS NULL OR subscriber_email = $2)
+        "#,
+        newsletter_issue_id,
+        subscriber_email,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(n_requeued)
+}
@@ -0,0 +1,111 @@
+use crate::routes::admin_page;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    send_at: DateTime<Utc>,
+    n_deliveries: Option<i64>,
+}
+
+pub async fn scheduled_issues(
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let issues = get_scheduled_issues(&connection_pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for issue in &issues {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{title}</td>
+                <td>{send_at}</td>
+                <td>{n_deliveries}</td>
+                <td>
+                    <form action="/admin/issues/reschedule" method="post">
+                        <input hidden type="text" name="newsletter_issue_id" value="{newsletter_issue_id}">
+                        <input type="datetime-local" name="send_at" value="{send_at_value}" required>
+                        <button type="submit">Reschedule</button>
+                    </form>
+                </td>
+                <td>
+                    <form action="/admin/issues/cancel" method="post">
+                        <input hidden type="text" name="newsletter_issue_id" value="{newsletter_issue_id}">
+                        <button type="submit">Cancel</button>
+                    </form>
+                </td>
+            </tr>"#,
+            title = htmlescape::encode_minimal(&issue.title),
+            send_at = issue.send_at.to_rfc2822(),
+            n_deliveries = issue.n_deliveries.unwrap_or(0),
+            newsletter_issue_id = issue.newsletter_issue_id,
+            send_at_value = issue.send_at.format("%Y-%m-%dT%H:%M"),
+        )
+        .unwrap();
+    }
+    if issues.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="5">No scheduled issues.</td></tr>"#);
+    }
+
+    Ok(admin_page(
+        "Scheduled Issues",
+        "",
+        &format!(
+            r#"{msg_html}
+        <p>Times are in UTC.</p>
+        <table>
+            <tr>
+                <th>Issue</th>
+                <th>Send at</th>
+                <th>Deliveries</th>
+                <th></th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <div class="button-container">
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </div>"#
+        ),
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_scheduled_issues(
+    connection_pool: &PgPool,
+) -> Result<Vec<ScheduledIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        ScheduledIssue,
+        r#"
+        SELECT
+            n.newsletter_issue_id,
+            n.title,
+            n.send_at AS "send_at!",
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = n.newsletter_issue_id
+            ) AS n_deliveries
+        FROM newsletter_issues n
+        WHERE n.status = 'scheduled' AND n.send_at > now()
+        ORDER BY n.send_at
+        "#,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve scheduled issues.")?;
+    Ok(issues)
+}
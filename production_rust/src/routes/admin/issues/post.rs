@@ -0,0 +1,129 @@
+use crate::utils::{e400, e500, parse_datetime_local, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    newsletter_issue_id: Uuid,
+    send_at: String,
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct CancelFormData {
+    newsletter_issue_id: Uuid,
+}
+
+fn not_pending_message() -> FlashMessage {
+    FlashMessage::error("The issue is no longer pending -> its delivery has already started.")
+}
+
+#[tracing::instrument(name = "Reschedule a newsletter issue", skip(form, connection_pool))]
+pub async fn reschedule_issue(
+    form: web::Form<RescheduleFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let send_at = parse_datetime_local(form.send_at.trim()).map_err(e400)?;
+
+    let mut transaction = connection_pool.begin().await.map_err(e500)?;
+    if !lock_pending_issue(&mut transaction, form.newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        not_pending_message().send();
+        return Ok(see_other("/admin/issues"));
+    }
+    update_send_at(&mut transaction, form.newsletter_issue_id, send_at)
+        .await
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "The issue has been rescheduled for {}.",
+        send_at.to_rfc2822()
+    ))
+    .send();
+    Ok(see_other("/admin/issues"))
+}
+
+#[tracing::instrument(name = "Cancel a newsletter issue", skip(form, connection_pool))]
+pub async fn cancel_issue(
+    form: web::Form<CancelFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = connection_pool.begin().await.map_err(e500)?;
+    if !lock_pending_issue(&mut transaction, form.newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    {
+        not_pending_message().send();
+        return Ok(see_other("/admin/issues"));
+    }
+    delete_issue(&mut transaction, form.newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info("The issue has been cancelled.").send();
+    Ok(see_other("/admin/issues"))
+}
+
+// the row lock keeps the issue from becoming due while it is being changed
+#[tracing::instrument(skip(transaction))]
+async fn lock_pending_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled' AND send_at > now()
+        FOR UPDATE
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(transaction)
+    .await?;
+    Ok(issue.is_some())
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn update_send_at(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    send_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET send_at = $2 WHERE newsletter_issue_id = $1",
+        newsletter_issue_id,
+        send_at,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn delete_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE newsletter_issue_id = $1",
+        newsletter_issue_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::routes::admin_page;
+use crate::subscriber_lists::get_subscriber_lists;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn manage_lists(
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let lists = get_subscriber_lists(&connection_pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for list in &lists {
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{name}</td>
+                <td>{n_members}</td>
+                <td>
+                    <form action="/admin/lists/delete" method="post">
+                        <input hidden type="text" name="list_id" value="{list_id}">
+                        <button type="submit">Delete</button>
+                    </form>
+                </td>
+            </tr>"#,
+            name = htmlescape::encode_minimal(&list.name),
+            n_members = list.n_members,
+            list_id = list.list_id,
+        )
+        .unwrap();
+    }
+    if lists.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="3">No subscriber lists.</td></tr>"#);
+    }
+
+    Ok(admin_page(
+        "Subscriber Lists",
+        "",
+        &format!(
+            r#"{msg_html}
+        <table>
+            <tr>
+                <th>Name</th>
+                <th>Members</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <h2>New list</h2>
+        <form action="/admin/lists" method="post">
+            <div class="input-group">
+                <label for="name">Name:</label>
+                <input type="text" id="name" name="name" placeholder="e.g. Product updates">
+            </div>
+            <button type="submit">Create list</button>
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </form>"#
+        ),
+    ))
+}
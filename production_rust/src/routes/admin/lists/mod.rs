@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::manage_lists;
+pub use post::{create_list, delete_list};
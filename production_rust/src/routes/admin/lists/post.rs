@@ -0,0 +1,61 @@
+use crate::subscriber_lists::{create_subscriber_list, delete_subscriber_list, ListDeletion};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct CreateFormData {
+    name: String,
+}
+
+#[tracing::instrument(name = "Create a subscriber list from the admin page", skip_all)]
+pub async fn create_list(
+    form: web::Form<CreateFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("The list needs a name.").send();
+        return Ok(see_other("/admin/lists"));
+    }
+
+    let created = create_subscriber_list(name, &connection_pool)
+        .await
+        .map_err(e500)?;
+    if created {
+        FlashMessage::info("The list has been created.").send();
+    } else {
+        FlashMessage::error("A list with this name already exists.").send();
+    }
+    Ok(see_other("/admin/lists"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    list_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Delete a subscriber list from the admin page",
+    skip_all,
+    fields(list_id=%form.list_id)
+)]
+pub async fn delete_list(
+    form: web::Form<DeleteFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match delete_subscriber_list(form.list_id, &connection_pool)
+        .await
+        .map_err(e500)?
+    {
+        ListDeletion::Deleted => FlashMessage::info("The list has been deleted.").send(),
+        ListDeletion::NotFound => FlashMessage::error("No matching list found.").send(),
+        ListDeletion::InUse => FlashMessage::error(
+            "Issues that have not been sent yet are aimed at this list, it cannot be deleted.",
+        )
+        .send(),
+    }
+    Ok(see_other("/admin/lists"))
+}
@@ -1,14 +1,24 @@
+use crate::authentication::SessionRegistry;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        Ok(see_other("/login"))
-    } else {
+pub async fn log_out(
+    session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(user_id) = session.get_user_id().map_err(e500)? {
+        if let Some(session_id) = session.get_session_id().map_err(e500)? {
+            session_registry
+                .remove(user_id, session_id)
+                .await
+                .map_err(e500)?;
+        }
         session.logout();
         FlashMessage::info("You have successfully logged out.").send();
         Ok(see_other("/login"))
+    } else {
+        Ok(see_other("/login"))
     }
 }
@@ -1,11 +1,33 @@
 mod dashboard;
+mod drafts;
+mod failures;
+mod issues;
+mod lists;
 mod logout;
 mod newsletter;
 mod password;
+mod preview;
+mod privacy;
+mod sessions;
 mod settings;
+mod subscribers;
+mod tokens;
+mod totp;
+mod users;
 
-pub use dashboard::admin_dashboard;
+pub use dashboard::{admin_dashboard, get_username};
+pub use drafts::drafts;
+pub use failures::*;
+pub use issues::*;
+pub use lists::*;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use preview::*;
+pub use privacy::*;
+pub use sessions::*;
 pub use settings::*;
+pub use subscribers::*;
+pub use tokens::*;
+pub use totp::*;
+pub use users::*;
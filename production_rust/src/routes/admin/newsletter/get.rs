@@ -1,114 +1,187 @@
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use crate::authentication::Role;
+use crate::markdown;
+use crate::routes::admin_page;
+use crate::subscriber_lists::get_subscriber_lists;
+use crate::utils::{e400, e500};
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
 
-pub async fn new_newsletter_form(
-    flash_messages: IncomingFlashMessages,
-) -> Result<HttpResponse, actix_web::Error> {
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-    let idempotency_key = uuid::Uuid::new_v4();
-
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-i">
-    <title>Submit a Newsletter</title>
-    <style>
-        body {{
-            font-family: Arial, sans-serif;
-            margin: 0;
-            background-color: #000000;
-            display: flex;
-            justify-content: center;
-            align-items: center;
-            height: 100vh;
-        }}
-
-        .form-container {{
-            background-color: #F8F8F8;
-            padding: 20px;
-            border-radius: 5px;
-            box-shadow: 0 2px 5px rgba(0, 0, 0, 0.1);
+const STYLE: &str = r#"
+        .form-container {
             max-width: 400px;
-            margin: 0 auto;
-        }}
+        }
 
-        h1 {{
-            text-align: center;
-            color: #3B5323;
-        }}
-
-        form {{
+        form {
             display: flex;
             flex-direction: column;
-        }}
+        }
 
-        label {{
+        label {
             margin-bottom: 10px;
-            color: #3B5323;
-        }}
+        }
 
-        .input-group input[type="text"], 
-        .input-group textarea {{
+        .input-group input[type="text"],
+        .input-group textarea {
             height: 100px;
             width: 100%;
             padding: 5px;
             border: 1px solid #ccc;
             border-radius: 3px;
             margin-bottom: 5px;
-        }}
+        }
 
-        .input-group .title-textarea {{
+        .input-group .title-textarea {
             height: 20px;
-        }}
+        }
 
-        button[type="submit"], button[type="button"] {{
-            padding: 10px 20px;
-            background-color: #3B5323;
-            color: #ffffff;
-            border: none;
-            border-radius: 3px;
-            curson: pointer;
-        }}
-
-        button:hover {{
-            background-color: #2A3F1B;
-        }}
-
-        p a {{
+        p a {
             color: #3B5323;
             text-decoration: none;
-        }}
-   </style>
-</head>
-<body>
-    <div class="form-container">
-        <h1>Submit a Newsletter</h1>
-        {msg_html}
+        }
+"#;
+
+#[derive(serde::Deserialize)]
+pub struct FormParameters {
+    newsletter_issue_id: Option<Uuid>,
+}
+
+struct Draft {
+    title: String,
+    markdown_content: Option<String>,
+    text_content: String,
+    html_content: String,
+    list_id: Option<Uuid>,
+    segment_signed_up_from: Option<NaiveDate>,
+    segment_signed_up_to: Option<NaiveDate>,
+    segment_tag: Option<String>,
+    segment_email_domain: Option<String>,
+}
+
+impl Draft {
+    // bodies rendered from the Markdown source are left out,
+    // so that only explicit overrides show up in the form
+    fn without_rendered_bodies(mut self) -> Self {
+        if let Some(markdown) = &self.markdown_content {
+            if self.text_content == markdown::render_text(markdown) {
+                self.text_content.clear();
+            }
+            if self.html_content == markdown::render_html(markdown) {
+                self.html_content.clear();
+            }
+        }
+        self
+    }
+}
+
+pub async fn new_newsletter_form(
+    parameters: web::Query<FormParameters>,
+    flash_messages: IncomingFlashMessages,
+    role: web::ReqData<Role>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let idempotency_key = uuid::Uuid::new_v4();
+
+    let (draft, draft_html) = match parameters.newsletter_issue_id {
+        Some(newsletter_issue_id) => {
+            let draft = get_draft(&connection_pool, newsletter_issue_id)
+                .await
+                .map_err(e500)?
+                .ok_or_else(|| e400("No draft found for this newsletter issue."))?
+                .without_rendered_bodies();
+            let draft_html = format!(
+                r#"<input hidden type="text" name="newsletter_issue_id" value="{}">"#,
+                newsletter_issue_id
+            );
+            (draft, draft_html)
+        }
+        None => (
+            Draft {
+                title: String::new(),
+                markdown_content: None,
+                text_content: String::new(),
+                html_content: String::new(),
+                list_id: None,
+                segment_signed_up_from: None,
+                segment_signed_up_to: None,
+                segment_tag: None,
+                segment_email_domain: None,
+            },
+            String::new(),
+        ),
+    };
+    let title = htmlescape::encode_minimal(&draft.title);
+    let markdown_content =
+        htmlescape::encode_minimal(draft.markdown_content.as_deref().unwrap_or_default());
+    let text_content = htmlescape::encode_minimal(&draft.text_content);
+    let html_content = htmlescape::encode_minimal(&draft.html_content);
+    let mut list_options = String::from(r#"<option value="">All confirmed subscribers</option>"#);
+    for list in get_subscriber_lists(&connection_pool).await.map_err(e500)? {
+        let selected = if draft.list_id == Some(list.list_id) {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            list_options,
+            r#"<option value="{}"{}>{} ({} members)</option>"#,
+            list.list_id,
+            selected,
+            htmlescape::encode_minimal(&list.name),
+            list.n_members
+        )
+        .unwrap();
+    }
+    let date_value = |date: Option<NaiveDate>| date.map(|d| d.to_string()).unwrap_or_default();
+    let segment_signed_up_from = date_value(draft.segment_signed_up_from);
+    let segment_signed_up_to = date_value(draft.segment_signed_up_to);
+    let segment_tag = htmlescape::encode_minimal(draft.segment_tag.as_deref().unwrap_or_default());
+    let segment_email_domain =
+        htmlescape::encode_minimal(draft.segment_email_domain.as_deref().unwrap_or_default());
+    // editors can only save drafts for an owner to publish
+    let publish_button = if *role >= Role::Owner {
+        r#"<button type="submit" name="action" value="publish">Publish</button>"#
+    } else {
+        ""
+    };
+
+    Ok(admin_page(
+        "Submit a Newsletter",
+        STYLE,
+        &format!(
+            r#"{msg_html}
         <form action="/admin/newsletter" method="post">
             <div class="input-group">
                 <label for="title"> Title:<br></label> 
                 <textarea id="title" 
                     name="title" 
                     placeholder="Enter the Newsletter title" 
-                    class="title-textarea">
-                </textarea>
+                    class="title-textarea">{title}</textarea>
+            </div>
+            <br>
+            <div class="input-group">
+                <label for="markdown_content"> Markdown Submission:</label> 
+                <textarea id="markdown_content" 
+                    name="markdown_content" 
+                    placeholder="Enter the content in Markdown, it fills in both the text and HTML submissions" 
+                    class="input-group">{markdown_content}</textarea>
             </div>
             <br>
+            <p>Text and HTML submissions override the rendered Markdown when filled in.</p>
             <div class="input-group">
                 <label for="text_content"> Text Submission:</label> 
                 <textarea id="text_content" 
                     name="text_content" 
                     placeholder="Enter the content in plain text" 
-                    class="input-group">
-                </textarea>
+                    class="input-group">{text_content}</textarea>
             </div>
             <br>
             <div class="input-group">
@@ -116,18 +189,68 @@ pub async fn new_newsletter_form(
                 <textarea id="html_content" 
                     name="html_content" 
                     placeholder="Enter the content in HTML format" 
-                    class="input-group">
-                </textarea>
+                    class="input-group">{html_content}</textarea>
+            </div>
+            <br>
+            <p>Audience, leave the rules empty to send to the whole list.</p>
+            <div class="input-group">
+                <label for="list_id"> List:<br></label>
+                <select id="list_id" name="list_id">{list_options}</select>
+            </div>
+            <br>
+            <div class="input-group">
+                <label for="segment_signed_up_from"> Signed up from:<br></label>
+                <input type="date" id="segment_signed_up_from" name="segment_signed_up_from" value="{segment_signed_up_from}">
+                <label for="segment_signed_up_to"> Signed up until:<br></label>
+                <input type="date" id="segment_signed_up_to" name="segment_signed_up_to" value="{segment_signed_up_to}">
+            </div>
+            <br>
+            <div class="input-group">
+                <label for="segment_tag"> Tagged with:<br></label>
+                <input type="text" id="segment_tag" name="segment_tag" value="{segment_tag}">
+            </div>
+            <br>
+            <div class="input-group">
+                <label for="segment_email_domain"> Email domain:<br></label>
+                <input type="text" id="segment_email_domain" name="segment_email_domain" placeholder="example.com" value="{segment_email_domain}">
+            </div>
+            <br>
+            <div class="input-group">
+                <label for="send_at"> Send at (UTC, leave empty to send now):<br></label>
+                <input type="datetime-local" id="send_at" name="send_at">
             </div>
             <br>
             <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
+            {draft_html}
             <div class="button-container">
-                <button type="submit">Publish</button>
-                <a href="/admin/dashboard"><button type="button">Back</button>
+                {publish_button}
+                <button type="submit" name="action" value="save_draft">Save draft</button>
+                <a href="/admin/dashboard"><button type="button">Back</button></a>
             </div>
-        </form>
-    </div>
-</body>
-</html>"#,
-        )))
+        </form>"#
+        ),
+    ))
+}
+
+#[tracing::instrument(skip(connection_pool))]
+async fn get_draft(
+    connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<Draft>, anyhow::Error> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT
+            title, markdown_content, text_content, html_content, list_id,
+            segment_signed_up_from, segment_signed_up_to, segment_tag,
+            segment_email_domain
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to retrieve the draft.")?;
+    Ok(draft)
 }
@@ -1,26 +1,110 @@
-use crate::authentication::UserId;
-use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
-use crate::utils::{e400, e500, see_other};
+use crate::authentication::{Role, UserId};
+use crate::configuration::IdempotencySettings;
+use crate::idempotency::{
+    save_response, try_processing, IdempotencyKey, NextAction, RequestFingerprint,
+};
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::markdown;
+use crate::subscriber_lists::{save_audience, Audience};
+use crate::templates::{Template, TemplateError, ISSUE_FIELDS};
+use crate::utils::{e400, e500, parse_datetime_local, see_other};
+use actix_web::error::{ErrorConflict, ErrorForbidden, ErrorUnprocessableEntity};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-fn success_message() -> FlashMessage {
-    FlashMessage::info(
-        "The newsletter issue has been accepted -> \
-        emails will be delivered shortly.",
-    )
+fn success_message(action: &FormAction, send_at: Option<DateTime<Utc>>) -> FlashMessage {
+    match (action, send_at) {
+        (FormAction::SaveDraft, _) => FlashMessage::info("The draft has been saved."),
+        (FormAction::Publish, Some(send_at)) => FlashMessage::info(format!(
+            "The newsletter issue has been scheduled -> \
+            emails will be delivered from {}.",
+            send_at.to_rfc2822()
+        )),
+        (FormAction::Publish, None) => FlashMessage::info(
+            "The newsletter issue has been accepted -> \
+            emails will be delivered shortly.",
+        ),
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FormAction {
+    #[default]
+    Publish,
+    SaveDraft,
 }
 
 #[allow(dead_code)]
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
+    markdown_content: Option<String>,
+    #[serde(default)]
     text_content: String,
+    #[serde(default)]
     html_content: String,
     idempotency_key: String,
+    send_at: Option<String>,
+    // set when the form was opened on an existing draft
+    newsletter_issue_id: Option<Uuid>,
+    #[serde(default)]
+    action: FormAction,
+    list_id: Option<String>,
+    segment_signed_up_from: Option<String>,
+    segment_signed_up_to: Option<String>,
+    segment_tag: Option<String>,
+    segment_email_domain: Option<String>,
+}
+
+struct IssueContent {
+    title: String,
+    markdown_content: Option<String>,
+    text_content: String,
+    html_content: String,
+}
+
+impl IssueContent {
+    // Markdown renders both bodies, unless either one is filled in explicitly
+    fn new(
+        title: String,
+        markdown_content: Option<String>,
+        text_content: String,
+        html_content: String,
+    ) -> Self {
+        let markdown_content = markdown_content.filter(|m| !m.trim().is_empty());
+        let (text_content, html_content) = match &markdown_content {
+            Some(markdown) => (
+                if text_content.trim().is_empty() {
+                    markdown::render_text(markdown)
+                } else {
+                    text_content
+                },
+                if html_content.trim().is_empty() {
+                    markdown::render_html(markdown)
+                } else {
+                    html_content
+                },
+            ),
+            None => (text_content, html_content),
+        };
+        Self {
+            title,
+            markdown_content,
+            text_content,
+            html_content,
+        }
+    }
+
+    fn validate(&self) -> Result<(), TemplateError> {
+        Template::parse(&self.html_content, ISSUE_FIELDS)?;
+        Template::parse(&self.text_content, ISSUE_FIELDS)?;
+        Ok(())
+    }
 }
 
 #[tracing::instrument(
@@ -31,52 +115,143 @@ pub struct FormData {
 pub async fn publish_newsletter(
     form: web::Form<FormData>,
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
     connection_pool: web::Data<PgPool>,
+    idempotency: web::Data<IdempotencySettings>,
+    fingerprint: web::ReqData<RequestFingerprint>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let FormData {
         title,
+        markdown_content,
         text_content,
         html_content,
         idempotency_key,
+        send_at,
+        newsletter_issue_id,
+        action,
+        list_id,
+        segment_signed_up_from,
+        segment_signed_up_to,
+        segment_tag,
+        segment_email_domain,
     } = form.0;
+    if matches!(action, FormAction::Publish) && *role < Role::Owner {
+        return Err(ErrorForbidden("Only owners can publish newsletter issues."));
+    }
+    let content = IssueContent::new(title, markdown_content, text_content, html_content);
+    let audience = Audience::parse(
+        list_id.as_deref(),
+        segment_signed_up_from.as_deref(),
+        segment_signed_up_to.as_deref(),
+        segment_tag.as_deref(),
+        segment_email_domain.as_deref(),
+    );
+    let validation = content.validate().map_err(|e| e.to_string()).and(audience);
+    let audience = match validation {
+        Ok(audience) => audience,
+        Err(e) => {
+            FlashMessage::error(format!(
+                "The newsletter issue could not be saved -> {}",
+                htmlescape::encode_minimal(&e)
+            ))
+            .send();
+            return Ok(match newsletter_issue_id {
+                Some(issue_id) => see_other(&format!(
+                    "/admin/newsletter?newsletter_issue_id={}",
+                    issue_id
+                )),
+                None => see_other("/admin/newsletter"),
+            });
+        }
+    };
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let send_at = send_at
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_datetime_local(s.trim()))
+        .transpose()
+        .map_err(e400)?;
 
-    let mut transaction = match try_processing(&connection_pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
+    let mut transaction = match try_processing(
+        &connection_pool,
+        &idempotency_key,
+        *user_id,
+        &fingerprint,
+        &idempotency,
+    )
+    .await
+    .map_err(e500)?
     {
         NextAction::StartProcessing(t) => t,
         NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
+            success_message(&action, send_at).send();
             return Ok(saved_response);
         }
+        NextAction::RequestInProgress => {
+            return Err(ErrorConflict(
+                "This newsletter issue is still being saved, try again in a moment.",
+            ));
+        }
+        NextAction::RejectReusedKey => {
+            return Err(ErrorUnprocessableEntity(
+                "The idempotency key was already used for another newsletter issue.",
+            ));
+        }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let issue_id = match newsletter_issue_id {
+        Some(issue_id) => {
+            let is_draft = update_draft(&mut transaction, issue_id, &content)
+                .await
+                .context("Failed to update the draft")
+                .map_err(e500)?;
+            if !is_draft {
+                return Err(e400("The newsletter issue is no longer a draft."));
+            }
+            issue_id
+        }
+        None => insert_newsletter_issue(&mut transaction, &content)
+            .await
+            .context("Failed to store newsletter issue details")
+            .map_err(e500)?,
+    };
+    save_audience(&mut transaction, issue_id, &audience)
         .await
-        .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
 
-    let response = see_other("/admin/newsletter");
+    let response = match action {
+        FormAction::SaveDraft => see_other(&format!(
+            "/admin/issues/preview?newsletter_issue_id={}",
+            issue_id
+        )),
+        FormAction::Publish => {
+            let is_scheduled = publish_issue(&mut transaction, issue_id, send_at)
+                .await
+                .context("Failed to publish the newsletter issue")
+                .map_err(e500)?;
+            // scheduled issues are picked up by the delivery worker once due
+            if !is_scheduled {
+                enqueue_delivery_tasks(&mut transaction, issue_id)
+                    .await
+                    .context("Failed to enqueue delivery tasks")
+                    .map_err(e500)?;
+            }
+            see_other("/admin/newsletter")
+        }
+    };
+
     let response = save_response(transaction, &idempotency_key, *user_id, response)
         .await
         .map_err(e500)?;
 
-    success_message().send();
+    success_message(&action, send_at).send();
     Ok(response)
 }
 
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
+    content: &IssueContent,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     sqlx::query!(
@@ -86,14 +261,16 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            markdown_content,
+            status
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5, 'draft')
         "#,
         newsletter_issue_id,
-        title,
-        text_content,
-        html_content,
+        content.title,
+        content.text_content,
+        content.html_content,
+        content.markdown_content,
     )
     .execute(transaction)
     .await?;
@@ -101,25 +278,58 @@ async fn insert_newsletter_issue(
     Ok(newsletter_issue_id)
 }
 
+// returns false when the issue has already left the draft state
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+async fn update_draft(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+    content: &IssueContent,
+) -> Result<bool, sqlx::Error> {
+    let n_updated = sqlx::query!(
         r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        ) 
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        UPDATE newsletter_issues
+        SET
+            title = $2,
+            text_content = $3,
+            html_content = $4,
+            markdown_content = $5
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
         "#,
         newsletter_issue_id,
+        content.title,
+        content.text_content,
+        content.html_content,
+        content.markdown_content,
     )
     .execute(transaction)
+    .await?
+    .rows_affected();
+
+    Ok(n_updated > 0)
+}
+
+// returns true when the issue waits for its send_at
+#[tracing::instrument(skip_all)]
+async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    send_at: Option<DateTime<Utc>>,
+) -> Result<bool, sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            published_at = now(),
+            send_at = COALESCE($2, now()),
+            status = CASE WHEN $2 > now() THEN 'scheduled' ELSE 'publishing' END
+        WHERE newsletter_issue_id = $1
+        RETURNING status
+        "#,
+        newsletter_issue_id,
+        send_at,
+    )
+    .fetch_one(transaction)
     .await?;
 
-    Ok(())
+    Ok(issue.status == "scheduled")
 }
@@ -1,5 +1,10 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
+use crate::authentication::{
+    set_recovery_email, validate_credentials, AuthError, Credentials, PasswordHashing,
+    PasswordPolicy, SessionRegistry, UserId,
+};
+use crate::domain::SubscriberEmail;
 use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
@@ -17,6 +22,10 @@ pub struct FormData {
 pub async fn change_password(
     form: web::Form<FormData>,
     connection_pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashing>,
+    session_registry: web::Data<SessionRegistry>,
+    session: TypedSession,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
@@ -34,12 +43,20 @@ pub async fn change_password(
     let username = get_username(*user_id, &connection_pool)
         .await
         .map_err(e500)?;
+    if let Err(rejection) = password_policy.check(
+        &form.new_password,
+        Some(&form.current_password),
+        &[&username],
+    ) {
+        FlashMessage::error(rejection.to_string()).send();
+        return Ok(see_other("/admin/password"));
+    }
     let credentials = Credentials {
         username,
         password: form.0.current_password,
     };
 
-    if let Err(e) = validate_credentials(credentials, &connection_pool).await {
+    if let Err(e) = validate_credentials(credentials, &password_hashing, &connection_pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -48,9 +65,73 @@ pub async fn change_password(
             AuthError::UnexpectedError(_) => Err(e500(e)),
         };
     }
-    crate::authentication::change_password(*user_id, form.0.new_password, &connection_pool)
+    crate::authentication::change_password(
+        *user_id,
+        form.0.new_password,
+        &password_hashing,
+        &connection_pool,
+    )
+    .await
+    .map_err(e500)?;
+    // whoever else knew the old password is logged out
+    let current_session_id = session
+        .get_session_id()
+        .map_err(e500)?
+        .ok_or_else(|| e500("The session id is missing from the session."))?;
+    session_registry
+        .revoke_others(*user_id, current_session_id)
         .await
         .map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct EmailFormData {
+    email: String,
+    current_password: Secret<String>,
+}
+
+// whoever controls the recovery email can reset the password,
+// so changing it takes the password as well as a session
+pub async fn change_recovery_email(
+    form: web::Form<EmailFormData>,
+    connection_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match form.email.trim() {
+        "" => None,
+        email => match SubscriberEmail::parse(email.to_string()) {
+            Ok(email) => Some(email),
+            Err(_) => {
+                FlashMessage::error("That is not a valid email address.").send();
+                return Ok(see_other("/admin/password"));
+            }
+        },
+    };
+
+    let username = get_username(**user_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form.0.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &password_hashing, &connection_pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                FlashMessage::error("The current password is incorrect.").send();
+                Ok(see_other("/admin/password"))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    set_recovery_email(**user_id, email.as_ref(), &connection_pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Your recovery email has been updated.").send();
+    Ok(see_other("/admin/password"))
+}
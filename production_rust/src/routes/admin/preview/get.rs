@@ -0,0 +1,119 @@
+use crate::routes::admin_page;
+use crate::utils::{e400, e500};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const STYLE: &str = r#"
+        .form-container {
+            width: 800px;
+        }
+
+        iframe, pre {
+            width: 100%;
+            box-sizing: border-box;
+            border: 1px solid #ccc;
+            background-color: #ffffff;
+        }
+
+        iframe {
+            height: 300px;
+        }
+
+        pre {
+            padding: 10px;
+            white-space: pre-wrap;
+        }
+
+        input[type="email"] {
+            padding: 5px;
+            border: 1px solid #ccc;
+            border-radius: 3px;
+        }
+"#;
+
+#[derive(serde::Deserialize)]
+pub struct PreviewParameters {
+    newsletter_issue_id: Uuid,
+}
+
+struct IssuePreview {
+    title: String,
+    text_content: String,
+    html_content: String,
+    status: String,
+}
+
+pub async fn issue_preview(
+    parameters: web::Query<PreviewParameters>,
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let newsletter_issue_id = parameters.newsletter_issue_id;
+    let issue = get_issue_preview(&connection_pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e400("No newsletter issue found."))?;
+
+    let edit_html = if issue.status == "draft" {
+        format!(
+            r#"<a href="/admin/newsletter?newsletter_issue_id={newsletter_issue_id}"><button type="button">Edit</button></a>"#
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(admin_page(
+        &htmlescape::encode_minimal(&issue.title),
+        STYLE,
+        &format!(
+            r#"{msg_html}
+        <p>Status: {status}</p>
+        <h2>HTML</h2>
+        <iframe sandbox srcdoc="{html_content}"></iframe>
+        <h2>Plain text</h2>
+        <pre>{text_content}</pre>
+        <form action="/admin/issues/test_send" method="post">
+            <input hidden type="text" name="newsletter_issue_id" value="{newsletter_issue_id}">
+            <input type="email" name="email" placeholder="Send a test copy to" required>
+            <button type="submit">Send test</button>
+        </form>
+        <br>
+        <div class="button-container">
+            {edit_html}
+            <a href="/admin/drafts"><button type="button">Back</button></a>
+        </div>"#,
+            status = issue.status,
+            html_content = htmlescape::encode_attribute(&issue.html_content),
+            text_content = htmlescape::encode_minimal(&issue.text_content),
+        ),
+    ))
+}
+
+#[tracing::instrument(skip(connection_pool))]
+async fn get_issue_preview(
+    connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssuePreview>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        IssuePreview,
+        r#"
+        SELECT title, text_content, html_content, status
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?;
+    Ok(issue)
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::issue_preview;
+pub use post::send_test_issue;
@@ -0,0 +1,102 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::ApplicationBaseUrl;
+use crate::templates::{MergeFields, Templates};
+use crate::utils::{e400, e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    newsletter_issue_id: Uuid,
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Send a test copy of a newsletter issue",
+    skip(form, connection_pool, email_client, templates, base_url)
+)]
+pub async fn send_test_issue(
+    form: web::Form<FormData>,
+    connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        newsletter_issue_id,
+        email,
+    } = form.0;
+    let preview_url = format!(
+        "/admin/issues/preview?newsletter_issue_id={}",
+        newsletter_issue_id
+    );
+
+    let recipient = match SubscriberEmail::parse(email) {
+        Ok(recipient) => recipient,
+        Err(_) => {
+            FlashMessage::error("Please enter a valid email address.").send();
+            return Ok(see_other(&preview_url));
+        }
+    };
+
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .fetch_optional(connection_pool.get_ref())
+    .await
+    .map_err(e500)?
+    .ok_or_else(|| e400("No newsletter issue found."))?;
+
+    // test copies carry placeholder merge fields, there is no subscriber behind them
+    let unsubscribe_url = format!("{}/subscriptions/unsubscribe", base_url.0);
+    let fields = MergeFields {
+        name: "Test subscriber",
+        email: recipient.as_ref(),
+        unsubscribe_url: &unsubscribe_url,
+    };
+    let rendered = templates
+        .render_issue(
+            &issue.title,
+            &issue.html_content,
+            &issue.text_content,
+            &fields,
+        )
+        .map_err(e400)?;
+
+    match email_client
+        .send_email(
+            &recipient,
+            &format!("[Test] {}", issue.title),
+            &rendered.html,
+            &rendered.text,
+            None,
+        )
+        .await
+    {
+        Ok(()) => {
+            FlashMessage::info(format!(
+                "A test copy has been sent to {}.",
+                htmlescape::encode_minimal(recipient.as_ref())
+            ))
+            .send();
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a test copy of a newsletter issue.",
+            );
+            FlashMessage::error("Failed to send the test copy.").send();
+        }
+    }
+    Ok(see_other(&preview_url))
+}
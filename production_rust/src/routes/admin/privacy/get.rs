@@ -0,0 +1,84 @@
+use crate::routes::admin_page;
+use crate::subscriber_data::{export_personal_data, PersonalData};
+use crate::utils::{e400, e500};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+        .form-container {
+            max-width: 600px;
+        }
+
+        input[type="email"] {
+            width: 100%;
+            padding: 10px;
+            margin-bottom: 10px;
+            border: 1px solid #ccc;
+            border-radius: 3px;
+        }
+"#;
+
+pub async fn data_requests_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(admin_page(
+        "Data Requests",
+        STYLE,
+        &format!(
+            r#"{msg_html}
+        <h2>Access</h2>
+        <p>Download, as JSON, everything held about an address.</p>
+        <form action="/admin/privacy/export" method="get">
+            <input type="email" name="email" placeholder="Email address" required>
+            <button type="submit">Export</button>
+        </form>
+        <h2>Erasure</h2>
+        <p>Delete everything held about an address. This cannot be undone,
+        and the address will be skipped by later imports.</p>
+        <form action="/admin/privacy/erase" method="post">
+            <input type="email" name="email" placeholder="Email address" required>
+            <button type="submit">Erase</button>
+        </form>
+        <br>
+        <a href="/admin/dashboard"><button type="button">Back</button></a>"#
+        ),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestQuery {
+    email: String,
+}
+
+#[tracing::instrument(name = "Export subscriber data from the admin page", skip_all)]
+pub async fn export_subscriber_data(
+    query: web::Query<DataRequestQuery>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = query.email.trim();
+    if email.is_empty() {
+        return Err(e400("The email address is missing."));
+    }
+    let personal_data = export_personal_data(email, &connection_pool)
+        .await
+        .map_err(e500)?;
+    Ok(personal_data_response(&personal_data))
+}
+
+/// The data as a JSON attachment.
+pub fn personal_data_response(personal_data: &PersonalData) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("personal-data.json".into())],
+        })
+        .json(personal_data)
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{data_requests_form, export_subscriber_data, personal_data_response};
+pub use post::erase_subscriber_data;
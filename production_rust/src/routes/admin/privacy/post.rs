@@ -0,0 +1,34 @@
+use crate::startup::SuppressionHashKey;
+use crate::subscriber_data::erase_personal_data;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct EraseFormData {
+    email: String,
+}
+
+#[tracing::instrument(name = "Erase subscriber data from the admin page", skip_all)]
+pub async fn erase_subscriber_data(
+    form: web::Form<EraseFormData>,
+    connection_pool: web::Data<PgPool>,
+    suppression_hash_key: web::Data<SuppressionHashKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = form.email.trim();
+    if email.is_empty() {
+        FlashMessage::error("The email address is missing.").send();
+        return Ok(see_other("/admin/privacy"));
+    }
+    let n_deleted = erase_personal_data(email, &suppression_hash_key.0, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info(format!(
+        "{} records have been erased, the address will not be imported again.",
+        n_deleted
+    ))
+    .send();
+    Ok(see_other("/admin/privacy"))
+}
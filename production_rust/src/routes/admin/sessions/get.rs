@@ -0,0 +1,86 @@
+use crate::authentication::{SessionRegistry, UserId};
+use crate::routes::admin_page;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+pub async fn active_sessions(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let sessions = session_registry.list(**user_id).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for active_session in &sessions {
+        let action = if Some(active_session.session_id) == current_session_id {
+            "This session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/revoke" method="post">
+                        <input hidden type="text" name="session_id" value="{}">
+                        <button type="submit">Revoke</button>
+                    </form>"#,
+                active_session.session_id
+            )
+        };
+        let metadata = active_session.metadata.as_ref();
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{created_at}</td>
+                <td>{last_seen}</td>
+                <td>{ip_address}</td>
+                <td>{user_agent}</td>
+                <td>{action}</td>
+            </tr>"#,
+            created_at = metadata
+                .map(|m| m.created_at.to_rfc2822())
+                .unwrap_or_else(|| "Unknown".into()),
+            last_seen = active_session
+                .last_seen
+                .map(|t| t.to_rfc2822())
+                .unwrap_or_else(|| "Unknown".into()),
+            ip_address = metadata
+                .and_then(|m| m.ip_address.as_deref())
+                .unwrap_or("Unknown"),
+            user_agent = htmlescape::encode_minimal(
+                metadata
+                    .and_then(|m| m.user_agent.as_deref())
+                    .unwrap_or("Unknown")
+            ),
+        )
+        .unwrap();
+    }
+
+    Ok(admin_page(
+        "Active Sessions",
+        "",
+        &format!(
+            r#"{msg_html}
+        <table>
+            <tr>
+                <th>Logged in at</th>
+                <th>Last seen at</th>
+                <th>IP address</th>
+                <th>Browser</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <form action="/admin/sessions/revoke_others" method="post">
+            <button type="submit">Log out all other sessions</button>
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </form>"#
+        ),
+    ))
+}
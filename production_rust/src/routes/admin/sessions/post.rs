@@ -0,0 +1,62 @@
+use crate::authentication::{SessionRegistry, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct RevokeFormData {
+    session_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Revoke a session from the admin page",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_session(
+    form: web::Form<RevokeFormData>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let revoked = session_registry
+        .remove(**user_id, form.session_id)
+        .await
+        .map_err(e500)?;
+
+    if !revoked {
+        FlashMessage::error("No matching active session found.").send();
+    } else if session.get_session_id().map_err(e500)? == Some(form.session_id) {
+        session.logout();
+        FlashMessage::info("You have successfully logged out.").send();
+        return Ok(see_other("/login"));
+    } else {
+        FlashMessage::info("The session has been revoked.").send();
+    }
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(
+    name = "Revoke all other sessions from the admin page",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_other_sessions(
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session_id = session
+        .get_session_id()
+        .map_err(e500)?
+        .ok_or_else(|| e500("The session id is missing from the session."))?;
+    session_registry
+        .revoke_others(**user_id, current_session_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("All your other sessions have been logged out.").send();
+    Ok(see_other("/admin/sessions"))
+}
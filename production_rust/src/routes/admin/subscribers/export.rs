@@ -0,0 +1,124 @@
+use super::parse_status;
+use crate::utils::{e400, e500};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const BATCH_SIZE: i64 = 500;
+
+#[derive(serde::Deserialize)]
+pub struct ExportQuery {
+    status: Option<String>,
+}
+
+struct ExportRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// Streams the subscribers as CSV, oldest first, one batch at a time:
+/// the whole list is never held in memory.
+#[tracing::instrument(name = "Export subscribers", skip(query, connection_pool))]
+pub async fn export_subscribers(
+    query: web::Query<ExportQuery>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let status = parse_status(&query.status).map_err(e400)?;
+    let connection_pool = connection_pool.into_inner();
+
+    // `None` once the last batch has been sent
+    let first_batch = Some(ExportCursor {
+        after: None,
+        with_headers: true,
+    });
+    let body = futures_util::stream::try_unfold(first_batch, move |cursor| {
+        let connection_pool = Arc::clone(&connection_pool);
+        let status = status.clone();
+        async move {
+            let cursor = match cursor {
+                Some(cursor) => cursor,
+                None => return Ok(None),
+            };
+            let rows = get_batch(&connection_pool, status.as_deref(), cursor.after)
+                .await
+                .map_err(e500)?;
+            let chunk = write_csv(&rows, cursor.with_headers).map_err(e500)?;
+            let next = match rows.last() {
+                Some(last) if rows.len() as i64 == BATCH_SIZE => Some(ExportCursor {
+                    after: Some((last.subscribed_at, last.id)),
+                    with_headers: false,
+                }),
+                _ => None,
+            };
+            Ok::<_, actix_web::Error>(Some((chunk, next)))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.csv".into())],
+        })
+        .streaming(body))
+}
+
+struct ExportCursor {
+    after: Option<(DateTime<Utc>, Uuid)>,
+    with_headers: bool,
+}
+
+fn write_csv(rows: &[ExportRow], with_headers: bool) -> Result<Bytes, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if with_headers {
+        writer.write_record(["email", "name", "status", "subscribed_at"])?;
+    }
+    for row in rows {
+        writer.write_record([
+            row.email.as_str(),
+            row.name.as_str(),
+            row.status.as_str(),
+            &row.subscribed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ])?;
+    }
+    let csv = writer
+        .into_inner()
+        .context("Failed to write subscribers as CSV.")?;
+    Ok(Bytes::from(csv))
+}
+
+async fn get_batch(
+    connection_pool: &PgPool,
+    status: Option<&str>,
+    after: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<Vec<ExportRow>, anyhow::Error> {
+    let (after_subscribed_at, after_id) = after.unzip();
+    let rows = sqlx::query_as!(
+        ExportRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE
+            ($1::text IS NULL OR status = $1)
+            AND ($2::timestamptz IS NULL OR (subscribed_at, id) > ($2, $3::uuid))
+        ORDER BY subscribed_at, id
+        LIMIT $4
+        "#,
+        status,
+        after_subscribed_at,
+        after_id,
+        BATCH_SIZE,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve a batch of subscribers to export.")?;
+    Ok(rows)
+}
@@ -0,0 +1,332 @@
+use super::{non_empty, parse_status, STATUSES};
+use crate::routes::admin_page;
+use crate::subscriber_lists::get_subscriber_lists;
+use crate::utils::{e400, e500};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// Filters arrive from a plain HTML form, empty fields are left out.
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+pub struct SubscribersQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    q: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signed_up_from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signed_up_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    per_page: Option<i64>,
+    // the last row of the previous page, subscribers are listed newest first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after_subscribed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after_id: Option<Uuid>,
+}
+
+struct SubscriberFilter {
+    search: Option<String>,
+    status: Option<String>,
+    signed_up_from: Option<NaiveDate>,
+    signed_up_to: Option<NaiveDate>,
+    page_size: i64,
+    after: Option<(DateTime<Utc>, Uuid)>,
+}
+
+impl TryFrom<&SubscribersQuery> for SubscriberFilter {
+    type Error = String;
+
+    fn try_from(query: &SubscribersQuery) -> Result<Self, Self::Error> {
+        let status = parse_status(&query.status)?;
+        let parse_date = |date: &Option<String>| {
+            non_empty(date)
+                .map(|d| {
+                    NaiveDate::parse_from_str(&d, "%Y-%m-%d")
+                        .map_err(|_| format!("{} is not a valid date.", d))
+                })
+                .transpose()
+        };
+        let after = match (query.after_subscribed_at, query.after_id) {
+            (Some(subscribed_at), Some(id)) => Some((subscribed_at, id)),
+            (None, None) => None,
+            _ => return Err("Both parts of the page cursor are required.".into()),
+        };
+
+        Ok(Self {
+            search: non_empty(&query.q),
+            status,
+            signed_up_from: parse_date(&query.signed_up_from)?,
+            signed_up_to: parse_date(&query.signed_up_to)?,
+            page_size: query
+                .per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            after,
+        })
+    }
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    list_ids: Vec<Uuid>,
+    tags: Vec<String>,
+}
+
+pub async fn manage_subscribers(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<SubscribersQuery>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let filter = SubscriberFilter::try_from(&query.0).map_err(e400)?;
+    let mut subscribers = get_subscribers(&filter, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let lists = get_subscriber_lists(&connection_pool).await.map_err(e500)?;
+    // one row more than a page tells whether there is a next one
+    let next_page = if subscribers.len() as i64 > filter.page_size {
+        subscribers.truncate(filter.page_size as usize);
+        subscribers.last().map(|last| {
+            let next = SubscribersQuery {
+                q: filter.search.clone(),
+                status: filter.status.clone(),
+                signed_up_from: non_empty(&query.signed_up_from),
+                signed_up_to: non_empty(&query.signed_up_to),
+                per_page: query.per_page,
+                after_subscribed_at: Some(last.subscribed_at),
+                after_id: Some(last.id),
+            };
+            serde_urlencoded::to_string(next).unwrap()
+        })
+    } else {
+        None
+    };
+
+    let mut rows_html = String::new();
+    for subscriber in &subscribers {
+        let mut actions = String::new();
+        if subscriber.status == "pending_confirmation" {
+            actions.push_str(&action_form("resend", "Resend confirmation", subscriber.id));
+            actions.push_str(&action_form("confirm", "Confirm", subscriber.id));
+        }
+        if subscriber.status != "unsubscribed" {
+            actions.push_str(&action_form("unsubscribe", "Unsubscribe", subscriber.id));
+        }
+        actions.push_str(&action_form("delete", "Delete", subscriber.id));
+        let mut lists_html = String::new();
+        let mut list_options = String::new();
+        for list in &lists {
+            let name = htmlescape::encode_minimal(&list.name);
+            if subscriber.list_ids.contains(&list.list_id) {
+                writeln!(
+                    lists_html,
+                    r#"<form action="/admin/subscribers/remove_from_list" method="post">
+                        {name}
+                        <input hidden type="text" name="subscriber_id" value="{}">
+                        <input hidden type="text" name="list_id" value="{}">
+                        <button type="submit">Remove</button>
+                    </form>"#,
+                    subscriber.id, list.list_id
+                )
+                .unwrap();
+            } else {
+                write!(
+                    list_options,
+                    r#"<option value="{}">{name}</option>"#,
+                    list.list_id
+                )
+                .unwrap();
+            }
+        }
+        if !list_options.is_empty() {
+            write!(
+                lists_html,
+                r#"<form action="/admin/subscribers/add_to_list" method="post">
+                        <input hidden type="text" name="subscriber_id" value="{}">
+                        <select name="list_id">{list_options}</select>
+                        <button type="submit">Add to list</button>
+                    </form>"#,
+                subscriber.id
+            )
+            .unwrap();
+        }
+        let mut tags_html = String::new();
+        for tag in &subscriber.tags {
+            writeln!(
+                tags_html,
+                r#"<form action="/admin/subscribers/untag" method="post">
+                        {tag}
+                        <input hidden type="text" name="subscriber_id" value="{}">
+                        <input hidden type="text" name="tag" value="{tag_value}">
+                        <button type="submit">Remove</button>
+                    </form>"#,
+                subscriber.id,
+                tag = htmlescape::encode_minimal(tag),
+                tag_value = htmlescape::encode_minimal(tag),
+            )
+            .unwrap();
+        }
+        write!(
+            tags_html,
+            r#"<form action="/admin/subscribers/tag" method="post">
+                        <input hidden type="text" name="subscriber_id" value="{}">
+                        <input type="text" name="tag" placeholder="New tag">
+                        <button type="submit">Tag</button>
+                    </form>"#,
+            subscriber.id
+        )
+        .unwrap();
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{email}</td>
+                <td>{name}</td>
+                <td>{status}</td>
+                <td>{subscribed_at}</td>
+                <td>{lists_html}</td>
+                <td>{tags_html}</td>
+                <td>{actions}</td>
+            </tr>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            name = htmlescape::encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber
+                .subscribed_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+        .unwrap();
+    }
+    if subscribers.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="7">No matching subscribers.</td></tr>"#);
+    }
+
+    let mut status_options = String::from(r#"<option value="">Any status</option>"#);
+    for status in STATUSES {
+        let selected = if filter.status.as_deref() == Some(status) {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            status_options,
+            r#"<option value="{status}"{selected}>{status}</option>"#
+        )
+        .unwrap();
+    }
+    let next_page_html = match next_page {
+        Some(next_page) => format!(
+            r#"<a href="/admin/subscribers?{}">Next page</a>"#,
+            htmlescape::encode_minimal(&next_page)
+        ),
+        None => String::new(),
+    };
+    let date_value = |date: Option<NaiveDate>| date.map(|d| d.to_string()).unwrap_or_default();
+
+    Ok(admin_page(
+        "Subscribers",
+        "",
+        &format!(
+            r#"{msg_html}
+        <form action="/admin/subscribers" method="get">
+            <input type="text" name="q" placeholder="Email or name" value="{search}">
+            <select name="status">{status_options}</select>
+            <label>Signed up from <input type="date" name="signed_up_from" value="{signed_up_from}"></label>
+            <label>to <input type="date" name="signed_up_to" value="{signed_up_to}"></label>
+            <button type="submit">Search</button>
+        </form>
+        <table>
+            <tr>
+                <th>Email</th>
+                <th>Name</th>
+                <th>Status</th>
+                <th>Signed up</th>
+                <th>Lists</th>
+                <th>Tags</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        {next_page_html}
+        <div class="button-container">
+            <a href="/admin/subscribers/import"><button type="button">Import</button></a>
+            <a href="/admin/subscribers/export?status={status}"><button type="button">Export</button></a>
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </div>"#,
+            search = htmlescape::encode_minimal(filter.search.as_deref().unwrap_or_default()),
+            signed_up_from = date_value(filter.signed_up_from),
+            signed_up_to = date_value(filter.signed_up_to),
+            status = filter.status.as_deref().unwrap_or_default(),
+        ),
+    ))
+}
+
+fn action_form(action: &str, label: &str, subscriber_id: Uuid) -> String {
+    format!(
+        r#"<form action="/admin/subscribers/{action}" method="post">
+                        <input hidden type="text" name="subscriber_id" value="{subscriber_id}">
+                        <button type="submit">{label}</button>
+                    </form>"#
+    )
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subscribers(
+    filter: &SubscriberFilter,
+    connection_pool: &PgPool,
+) -> Result<Vec<SubscriberRow>, anyhow::Error> {
+    let (after_subscribed_at, after_id) = filter.after.unzip();
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT
+            id, email, name, status, subscribed_at,
+            ARRAY(
+                SELECT list_id FROM subscriber_list_memberships m
+                WHERE m.subscriber_id = s.id
+            ) AS "list_ids!",
+            ARRAY(
+                SELECT tag FROM subscriber_tags t
+                WHERE t.subscriber_id = s.id ORDER BY tag
+            ) AS "tags!"
+        FROM subscriptions s
+        WHERE
+            ($1::text IS NULL
+                OR strpos(lower(email), lower($1)) > 0
+                OR strpos(lower(name), lower($1)) > 0)
+            AND ($2::text IS NULL OR status = $2)
+            AND ($3::date IS NULL OR subscribed_at >= $3::date)
+            AND ($4::date IS NULL OR subscribed_at < $4::date + 1)
+            AND ($5::timestamptz IS NULL OR (subscribed_at, id) < ($5, $6::uuid))
+        ORDER BY subscribed_at DESC, id DESC
+        LIMIT $7
+        "#,
+        filter.search,
+        filter.status,
+        filter.signed_up_from,
+        filter.signed_up_to,
+        after_subscribed_at,
+        after_id,
+        filter.page_size + 1,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve subscribers.")?;
+    Ok(subscribers)
+}
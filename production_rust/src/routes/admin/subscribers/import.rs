@@ -0,0 +1,418 @@
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::routes::{
+    admin_page, generate_subscription_token, send_confirmation_email, store_token,
+    store_unsubscribe_token,
+};
+use crate::startup::{ApplicationBaseUrl, SuppressionHashKey};
+use crate::subscriber_data::is_suppressed;
+use crate::subscriber_lists::{add_tags, add_to_list, get_subscriber_lists, parse_tag};
+use crate::templates::Templates;
+use crate::utils::{e400, e500, see_other};
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use futures_util::TryStreamExt;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+use std::fmt::Write;
+use tracing::Instrument;
+use uuid::Uuid;
+
+const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+// the report lists this many row errors at most
+const MAX_REPORTED_ERRORS: usize = 100;
+
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut list_options = String::from(r#"<option value="">No list</option>"#);
+    for list in get_subscriber_lists(&connection_pool).await.map_err(e500)? {
+        write!(
+            list_options,
+            r#"<option value="{}">{}</option>"#,
+            list.list_id,
+            htmlescape::encode_minimal(&list.name)
+        )
+        .unwrap();
+    }
+
+    let form = format!(
+        r#"{msg_html}
+        <p>The file needs an <code>email</code> and a <code>name</code> column, other columns are ignored.
+        An optional <code>tags</code> column holds tags separated by <code>;</code>.
+        Addresses that are already subscribed, or whose data was erased on request, are skipped.</p>
+        <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+            <label>Imported subscribers
+                <select name="initial_status">
+                    <option value="pending_confirmation">are sent a confirmation email</option>
+                    <option value="confirmed">are confirmed right away</option>
+                </select>
+            </label>
+            <br>
+            <label>Add them to the list
+                <select name="list_id">{list_options}</select>
+            </label>
+            <br>
+            <input type="file" name="csv" accept=".csv,text/csv" required>
+            <br>
+            <button type="submit">Import</button>
+            <a href="/admin/subscribers"><button type="button">Back</button></a>
+        </form>"#
+    );
+    Ok(admin_page("Import Subscribers", "", &form))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum InitialStatus {
+    Confirmed,
+    PendingConfirmation,
+}
+
+impl InitialStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InitialStatus::Confirmed => "confirmed",
+            InitialStatus::PendingConfirmation => "pending_confirmation",
+        }
+    }
+}
+
+impl TryFrom<&str> for InitialStatus {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s.trim() {
+            "confirmed" => Ok(InitialStatus::Confirmed),
+            "pending_confirmation" => Ok(InitialStatus::PendingConfirmation),
+            other => Err(format!("{} is not a valid initial status.", other)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ImportReport {
+    n_imported: usize,
+    n_duplicates: usize,
+    // addresses whose data was erased on request
+    n_suppressed: usize,
+    // line number in the file and what was wrong with it
+    errors: Vec<(u64, String)>,
+}
+
+/// Rows are parsed and inserted one at a time, in a single transaction.
+/// Invalid rows are reported and skipped, they do not stop the import.
+/// Subscribers who need to opt in are emailed once the import is committed.
+#[tracing::instrument(
+    name = "Import subscribers",
+    skip(
+        payload,
+        connection_pool,
+        email_client,
+        templates,
+        base_url,
+        suppression_hash_key
+    )
+)]
+pub async fn import_subscribers(
+    payload: Multipart,
+    connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    suppression_hash_key: web::Data<SuppressionHashKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Upload {
+        initial_status,
+        list_id,
+        csv,
+    } = read_upload(payload).await?;
+    let initial_status =
+        InitialStatus::try_from(initial_status.as_deref().unwrap_or_default()).map_err(e400)?;
+    let list_id = match list_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(list_id) => Some(Uuid::parse_str(list_id).map_err(e400)?),
+    };
+    let csv = match csv {
+        Some(csv) if !csv.is_empty() => csv,
+        _ => {
+            FlashMessage::error("Please choose a CSV file to import.").send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_slice());
+    let columns = reader.headers().ok().and_then(|headers| {
+        let find = |column: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+        };
+        find("email")
+            .zip(find("name"))
+            .map(|(email, name)| (email, name, find("tags")))
+    });
+    let (email_column, name_column, tags_column) = match columns {
+        Some(columns) => columns,
+        None => {
+            FlashMessage::error("The file needs an email and a name column.").send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+
+    let mut transaction = connection_pool.begin().await.map_err(e500)?;
+    let mut report = ImportReport::default();
+    let mut seen = HashSet::new();
+    let mut to_confirm = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or_default();
+                report.errors.push((line, e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let field = |column: usize| record.get(column).unwrap_or_default().to_string();
+        let new_subscriber = match SubscriberEmail::parse(field(email_column))
+            .and_then(|email| Ok((email, SubscriberName::parse(field(name_column))?)))
+        {
+            Ok((email, name)) => NewSubscriber { email, name },
+            Err(e) => {
+                report.errors.push((line, e));
+                continue;
+            }
+        };
+        let tags = match tags_column.map(|column| parse_tags(&field(column))) {
+            Some(Ok(tags)) => tags,
+            Some(Err(e)) => {
+                report.errors.push((line, e));
+                continue;
+            }
+            None => Vec::new(),
+        };
+        if !seen.insert(new_subscriber.email.as_ref().to_lowercase()) {
+            report.n_duplicates += 1;
+            continue;
+        }
+
+        if is_suppressed(
+            new_subscriber.email.as_ref(),
+            &suppression_hash_key.0,
+            &mut transaction,
+        )
+        .await
+        .map_err(e500)?
+        {
+            report.n_suppressed += 1;
+            continue;
+        }
+
+        match import_subscriber(&mut transaction, &new_subscriber, initial_status)
+            .await
+            .map_err(e500)?
+        {
+            Some((subscriber_id, subscription_token)) => {
+                if !tags.is_empty() {
+                    add_tags(&mut transaction, subscriber_id, &tags)
+                        .await
+                        .map_err(e500)?;
+                }
+                if let Some(list_id) = list_id {
+                    add_to_list(subscriber_id, list_id, &mut transaction)
+                        .await
+                        .map_err(e500)?;
+                }
+                if let Some(subscription_token) = subscription_token {
+                    to_confirm.push((new_subscriber, subscription_token));
+                }
+                report.n_imported += 1;
+            }
+            None => report.n_duplicates += 1,
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the subscriber import.")
+        .map_err(e500)?;
+
+    if !to_confirm.is_empty() {
+        let email_client = email_client.into_inner();
+        let templates = templates.into_inner();
+        let base_url = base_url.into_inner();
+        tokio::spawn(
+            async move {
+                for (new_subscriber, subscription_token) in to_confirm {
+                    if let Err(e) = send_confirmation_email(
+                        &email_client,
+                        &templates,
+                        new_subscriber,
+                        &base_url.0,
+                        &subscription_token,
+                    )
+                    .await
+                    {
+                        tracing::error!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Failed to send a confirmation email to an imported subscriber"
+                        );
+                    }
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    Ok(admin_page(
+        "Import Subscribers",
+        "",
+        &render_report(&report, initial_status),
+    ))
+}
+
+struct Upload {
+    initial_status: Option<String>,
+    list_id: Option<String>,
+    csv: Option<Vec<u8>>,
+}
+
+async fn read_upload(mut payload: Multipart) -> Result<Upload, actix_web::Error> {
+    let mut upload = Upload {
+        initial_status: None,
+        list_id: None,
+        csv: None,
+    };
+    while let Some(mut field) = payload.try_next().await? {
+        let mut content = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            if content.len() + chunk.len() > MAX_IMPORT_BYTES {
+                return Err(e400(format!(
+                    "The file is larger than {} MiB.",
+                    MAX_IMPORT_BYTES / 1024 / 1024
+                )));
+            }
+            content.extend_from_slice(&chunk);
+        }
+        match field.name() {
+            "initial_status" => {
+                upload.initial_status = Some(String::from_utf8_lossy(&content).into())
+            }
+            "list_id" => upload.list_id = Some(String::from_utf8_lossy(&content).into()),
+            "csv" => upload.csv = Some(content),
+            _ => {}
+        }
+    }
+    Ok(upload)
+}
+
+// an empty cell means no tags
+fn parse_tags(tags: &str) -> Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = tags
+        .split(';')
+        .filter(|tag| !tag.trim().is_empty())
+        .map(parse_tag)
+        .collect::<Result<_, _>>()?;
+    parsed.sort();
+    parsed.dedup();
+    Ok(parsed)
+}
+
+/// `None` when the address is already subscribed, whatever its case.
+/// Otherwise the new subscriber's id, along with the token to put
+/// in the confirmation email if one is needed.
+async fn import_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    initial_status: InitialStatus,
+) -> Result<Option<(Uuid, Option<String>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT $1, $2, $3, now(), $4
+        WHERE NOT EXISTS (SELECT 1 FROM subscriptions WHERE lower(email) = lower($2))
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        new_subscriber.email.as_ref(),
+        new_subscriber.name.as_ref(),
+        initial_status.as_str(),
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to insert an imported subscriber.")?;
+    let subscriber_id = match row {
+        Some(row) => row.id,
+        None => return Ok(None),
+    };
+
+    store_unsubscribe_token(transaction, subscriber_id, &generate_subscription_token())
+        .await
+        .context("Failed to store the unsubscribe token of an imported subscriber.")?;
+    if initial_status == InitialStatus::Confirmed {
+        return Ok(Some((subscriber_id, None)));
+    }
+    let subscription_token = generate_subscription_token();
+    store_token(transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token of an imported subscriber.")?;
+    Ok(Some((subscriber_id, Some(subscription_token))))
+}
+
+fn render_report(report: &ImportReport, initial_status: InitialStatus) -> String {
+    let mut html = format!(
+        "<p>{} subscribers imported as {}, {} duplicates skipped, {} rows rejected.</p>",
+        report.n_imported,
+        initial_status.as_str(),
+        report.n_duplicates,
+        report.errors.len(),
+    );
+    if report.n_suppressed > 0 {
+        write!(
+            html,
+            "<p>{} addresses were skipped, their owners asked for their data to be erased.</p>",
+            report.n_suppressed
+        )
+        .unwrap();
+    }
+    if initial_status == InitialStatus::PendingConfirmation && report.n_imported > 0 {
+        html.push_str("<p>Confirmation emails are being sent to the imported subscribers.</p>");
+    }
+    if !report.errors.is_empty() {
+        html.push_str("<table><tr><th>Line</th><th>Error</th></tr>");
+        for (line, error) in report.errors.iter().take(MAX_REPORTED_ERRORS) {
+            write!(
+                html,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                line,
+                htmlescape::encode_minimal(error)
+            )
+            .unwrap();
+        }
+        html.push_str("</table>");
+        if report.errors.len() > MAX_REPORTED_ERRORS {
+            write!(
+                html,
+                "<p>... and {} more.</p>",
+                report.errors.len() - MAX_REPORTED_ERRORS
+            )
+            .unwrap();
+        }
+    }
+    html.push_str(
+        r#"<a href="/admin/subscribers"><button type="button">Back to subscribers</button></a>"#,
+    );
+    html
+}
@@ -0,0 +1,33 @@
+mod export;
+mod get;
+mod import;
+mod post;
+
+pub use export::export_subscribers;
+pub use get::manage_subscribers;
+pub use import::{import_subscribers, import_subscribers_form};
+pub use post::{
+    add_subscriber_to_list, confirm_subscriber_manually, delete_subscriber,
+    remove_subscriber_from_list, resend_subscriber_confirmation, tag_subscriber,
+    unsubscribe_subscriber_manually, untag_subscriber,
+};
+
+const STATUSES: [&str; 3] = ["pending_confirmation", "confirmed", "unsubscribed"];
+
+// fields of a plain HTML form are sent even when left empty
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn parse_status(status: &Option<String>) -> Result<Option<String>, String> {
+    match non_empty(status) {
+        Some(status) if !STATUSES.contains(&status.as_str()) => {
+            Err(format!("{} is not a known status.", status))
+        }
+        status => Ok(status),
+    }
+}
@@ -0,0 +1,288 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::{confirm_subscriber, renew_confirmation_token, send_confirmation_email};
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_lists::{add_tags, add_to_list, parse_tag, remove_from_list, remove_tag};
+use crate::templates::Templates;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct SubscriberFormData {
+    subscriber_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Resend a confirmation email from the admin page",
+    skip(form, connection_pool, email_client, templates, base_url)
+)]
+pub async fn resend_subscriber_confirmation(
+    form: web::Form<SubscriberFormData>,
+    connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = get_subscriber_email(form.subscriber_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let renewed = match email {
+        Some(email) => renew_confirmation_token(&connection_pool, email)
+            .await
+            .map_err(e500)?,
+        None => None,
+    };
+
+    match renewed {
+        Some((pending_subscriber, subscription_token)) => {
+            send_confirmation_email(
+                &email_client,
+                &templates,
+                pending_subscriber,
+                &base_url.0,
+                &subscription_token,
+            )
+            .await
+            .map_err(e500)?;
+            FlashMessage::info("A new confirmation link has been sent.").send();
+        }
+        None => FlashMessage::error("No matching pending subscriber found.").send(),
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Confirm a subscriber manually", skip(form, connection_pool))]
+pub async fn confirm_subscriber_manually(
+    form: web::Form<SubscriberFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let confirmed = confirm_subscriber(form.subscriber_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    if confirmed {
+        FlashMessage::info("The subscriber has been confirmed.").send();
+    } else {
+        FlashMessage::error("No matching pending subscriber found.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber from the admin page",
+    skip(form, connection_pool)
+)]
+pub async fn unsubscribe_subscriber_manually(
+    form: web::Form<SubscriberFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let n_unsubscribed = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE id = $1 AND status <> 'unsubscribed'
+        "#,
+        form.subscriber_id,
+    )
+    .execute(connection_pool.get_ref())
+    .await
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_unsubscribed > 0 {
+        FlashMessage::info("The subscriber has been unsubscribed.").send();
+    } else {
+        FlashMessage::error("No matching subscribed subscriber found.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+/// Removes every trace of the subscriber, including deliveries
+/// still waiting in the queue for their address.
+#[tracing::instrument(name = "Delete a subscriber", skip(form, connection_pool))]
+pub async fn delete_subscriber(
+    form: web::Form<SubscriberFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let deleted = delete(form.subscriber_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    if deleted {
+        FlashMessage::info("The subscriber has been deleted.").send();
+    } else {
+        FlashMessage::error("No matching subscriber found.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct TagFormData {
+    subscriber_id: Uuid,
+    tag: String,
+}
+
+#[tracing::instrument(
+    name = "Tag a subscriber from the admin page",
+    skip(form, connection_pool)
+)]
+pub async fn tag_subscriber(
+    form: web::Form<TagFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tag = match parse_tag(&form.tag) {
+        Ok(tag) => tag,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    add_tags(&mut transaction, form.subscriber_id, &[tag])
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the tagging of a subscriber.")
+        .map_err(e500)?;
+    FlashMessage::info("The subscriber has been tagged.").send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Remove a tag from the admin page", skip(form, connection_pool))]
+pub async fn untag_subscriber(
+    form: web::Form<TagFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let removed = remove_tag(form.subscriber_id, &form.tag, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    if removed {
+        FlashMessage::info("The tag has been removed.").send();
+    } else {
+        FlashMessage::error("No matching tag found.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct MembershipFormData {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Add a subscriber to a list from the admin page",
+    skip(form, connection_pool)
+)]
+pub async fn add_subscriber_to_list(
+    form: web::Form<MembershipFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let added = add_to_list(form.subscriber_id, form.list_id, connection_pool.get_ref())
+        .await
+        .map_err(e500)?;
+
+    if added {
+        FlashMessage::info("The subscriber has been added to the list.").send();
+    } else {
+        FlashMessage::error("The subscriber could not be added to this list.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+    name = "Remove a subscriber from a list from the admin page",
+    skip(form, connection_pool)
+)]
+pub async fn remove_subscriber_from_list(
+    form: web::Form<MembershipFormData>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let removed = remove_from_list(form.subscriber_id, form.list_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    if removed {
+        FlashMessage::info("The subscriber has been removed from the list.").send();
+    } else {
+        FlashMessage::error("The subscriber is not on this list.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+async fn get_subscriber_email(
+    subscriber_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Option<SubscriberEmail>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .context("Failed to retrieve the email of a subscriber.")?;
+    row.map(|r| SubscriberEmail::parse(r.email).map_err(anyhow::Error::msg))
+        .transpose()
+}
+
+async fn delete(subscriber_id: Uuid, connection_pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the confirmation tokens of a subscriber.")?;
+    sqlx::query!(
+        "DELETE FROM unsubscribe_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the unsubscribe tokens of a subscriber.")?;
+    let row = sqlx::query!(
+        "DELETE FROM subscriptions WHERE id = $1 RETURNING email",
+        subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to delete a subscriber.")?;
+    let email = match row {
+        Some(row) => row.email,
+        None => return Ok(false),
+    };
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the queued deliveries of a subscriber.")?;
+    sqlx::query!(
+        "DELETE FROM issue_delivery_failures WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the delivery failures of a subscriber.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion of a subscriber.")?;
+    Ok(true)
+}
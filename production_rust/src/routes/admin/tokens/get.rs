@@ -0,0 +1,104 @@
+use crate::authentication::{get_api_tokens, UserId, API_TOKEN_SCOPES};
+use crate::routes::admin_page;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn api_tokens(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    tokens_page(*user_id.into_inner(), &connection_pool, &msg_html).await
+}
+
+// also rendered straight from `create_token`, the only time a new token is shown
+pub(super) async fn tokens_page(
+    user_id: uuid::Uuid,
+    connection_pool: &PgPool,
+    msg_html: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tokens = get_api_tokens(user_id, connection_pool)
+        .await
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for token in &tokens {
+        let action = match token.revoked_at {
+            Some(revoked_at) => format!("Revoked {}", revoked_at.to_rfc2822()),
+            None => format!(
+                r#"<form action="/admin/tokens/revoke" method="post">
+                        <input hidden type="text" name="token_id" value="{}">
+                        <button type="submit">Revoke</button>
+                    </form>"#,
+                token.token_id
+            ),
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{name}</td>
+                <td>{scopes}</td>
+                <td>{created_at}</td>
+                <td>{last_used_at}</td>
+                <td>{action}</td>
+            </tr>"#,
+            name = htmlescape::encode_minimal(&token.name),
+            scopes = token.scopes.join(", "),
+            created_at = token.created_at.to_rfc2822(),
+            last_used_at = token
+                .last_used_at
+                .map(|t| t.to_rfc2822())
+                .unwrap_or_else(|| "Never".into()),
+        )
+        .unwrap();
+    }
+    if tokens.is_empty() {
+        rows_html.push_str(r#"<tr><td colspan="5">No API tokens.</td></tr>"#);
+    }
+
+    let mut scopes_html = String::new();
+    for scope in API_TOKEN_SCOPES {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scope" value="{scope}"> {scope}</label>"#
+        )
+        .unwrap();
+    }
+
+    Ok(admin_page(
+        "API Tokens",
+        "",
+        &format!(
+            r#"{msg_html}
+        <table>
+            <tr>
+                <th>Name</th>
+                <th>Scopes</th>
+                <th>Created at</th>
+                <th>Last used at</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <h2>New token</h2>
+        <form action="/admin/tokens" method="post">
+            <div class="input-group">
+                <label for="name">Name:</label>
+                <input type="text" id="name" name="name" placeholder="e.g. CI publisher">
+            </div>
+            <div class="input-group">
+                {scopes_html}
+            </div>
+            <button type="submit">Create token</button>
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </form>"#
+        ),
+    ))
+}
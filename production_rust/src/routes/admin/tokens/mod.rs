@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::api_tokens;
+pub use post::{create_token, revoke_token};
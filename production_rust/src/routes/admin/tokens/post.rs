@@ -0,0 +1,83 @@
+use super::get::tokens_page;
+use crate::authentication::{
+    create_api_token, revoke_api_token, PasswordHashing, UserId, API_TOKEN_SCOPES,
+};
+use crate::utils::{e500, no_store, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// a form with repeated `scope` fields, one per ticked checkbox
+#[tracing::instrument(
+    name = "Create an API token from the admin page",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn create_token(
+    form: web::Form<Vec<(String, String)>>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let name = form
+        .iter()
+        .find(|(field, _)| field == "name")
+        .map(|(_, value)| value.trim())
+        .unwrap_or_default();
+    let scopes: Vec<String> = form
+        .iter()
+        .filter(|(field, value)| field == "scope" && API_TOKEN_SCOPES.contains(&value.as_str()))
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    if name.is_empty() {
+        FlashMessage::error("The API token needs a name.").send();
+        return Ok(see_other("/admin/tokens"));
+    }
+    if scopes.is_empty() {
+        FlashMessage::error("The API token needs at least one scope.").send();
+        return Ok(see_other("/admin/tokens"));
+    }
+
+    let token = create_api_token(*user_id, name, &scopes, &password_hashing, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg_html = format!(
+        "<p><i>Copy the new token now, it will not be shown again:</i></p>\n<p><code>{}</code></p>",
+        token.expose_secret()
+    );
+    tokens_page(*user_id, &connection_pool, &msg_html)
+        .await
+        .map(no_store)
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct RevokeFormData {
+    token_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Revoke an API token from the admin page",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn revoke_token(
+    form: web::Form<RevokeFormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let revoked = revoke_api_token(**user_id, form.token_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+
+    if revoked {
+        FlashMessage::info("The API token has been revoked.").send();
+    } else {
+        FlashMessage::error("No matching active API token found.").send();
+    }
+    Ok(see_other("/admin/tokens"))
+}
@@ -0,0 +1,85 @@
+use crate::authentication::{
+    base32_encode, get_totp_status, provisioning_uri, TotpCipher, TotpStatus, UserId,
+};
+use crate::routes::{admin_page, get_username};
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+        .form-container {
+            max-width: 600px;
+            overflow-wrap: break-word;
+        }
+
+        a {
+            color: #3B5323;
+        }
+"#;
+
+const ISSUER: &str = "Newsletter";
+
+pub async fn totp_settings(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    totp_cipher: web::Data<TotpCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let status = get_totp_status(**user_id, &totp_cipher, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let content = match status {
+        TotpStatus::Disabled => r#"<p>Two-factor authentication is off.</p>
+        <form action="/admin/totp/enroll" method="post">
+            <button type="submit">Set up</button>
+        </form>"#
+            .to_string(),
+        TotpStatus::Pending(secret) => {
+            let username = get_username(**user_id, &connection_pool)
+                .await
+                .map_err(e500)?;
+            let uri = htmlescape::encode_minimal(&provisioning_uri(&secret, ISSUER, &username));
+            format!(
+                r#"<p>Scan the QR code of this link with your authenticator app:</p>
+        <p><a href="{uri}">{uri}</a></p>
+        <p>or enter this secret by hand: <code id="totp-secret">{secret}</code></p>
+        <form action="/admin/totp/confirm" method="post">
+            <label for="code">Code from the app:</label>
+            <input type="text" id="code" name="code" autocomplete="one-time-code">
+            <button type="submit">Confirm</button>
+        </form>"#,
+                secret = base32_encode(secret.expose_secret()),
+            )
+        }
+        TotpStatus::Enabled => r#"<p>Two-factor authentication is on.</p>
+        <form action="/admin/totp/disable" method="post">
+            <label for="code">Current code or recovery code:</label>
+            <input type="text" id="code" name="code" autocomplete="one-time-code">
+            <button type="submit">Turn off</button>
+        </form>"#
+            .to_string(),
+    };
+
+    Ok(totp_page(&msg_html, &content))
+}
+
+// also used by `confirm_totp` to show the recovery codes once
+pub(super) fn totp_page(msg_html: &str, content: &str) -> HttpResponse {
+    admin_page(
+        "Two-factor authentication",
+        STYLE,
+        &format!(
+            r#"{msg_html}
+        {content}
+        <p><a href="/admin/dashboard"><button type="button">Back</button></a></p>"#
+        ),
+    )
+}
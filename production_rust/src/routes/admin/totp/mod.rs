@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::totp_settings;
+pub use post::{confirm_totp, disable_two_factor, enroll_totp};
@@ -0,0 +1,104 @@
+use super::get::totp_page;
+use crate::authentication::{
+    begin_totp_enrollment, confirm_totp_enrollment, disable_totp, verify_second_factor,
+    PasswordHashing, TotpCipher, UserId,
+};
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Enroll in TOTP",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn enroll_totp(
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    totp_cipher: web::Data<TotpCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    begin_totp_enrollment(**user_id, &totp_cipher, &connection_pool)
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/totp"))
+}
+
+#[tracing::instrument(
+    name = "Confirm TOTP enrollment",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn confirm_totp(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    totp_cipher: web::Data<TotpCipher>,
+    password_hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recovery_codes = confirm_totp_enrollment(
+        **user_id,
+        form.code.expose_secret(),
+        &totp_cipher,
+        &password_hashing,
+        &connection_pool,
+    )
+    .await
+    .map_err(e500)?;
+
+    let recovery_codes = match recovery_codes {
+        Some(recovery_codes) => recovery_codes,
+        None => {
+            FlashMessage::error("Invalid authentication code.").send();
+            return Ok(see_other("/admin/totp"));
+        }
+    };
+    let mut content = String::from(
+        "<p>Two-factor authentication is on. Keep these recovery codes somewhere safe, \
+        each of them can be used once instead of a code and they will not be shown again:</p>\n<ul>\n",
+    );
+    for recovery_code in &recovery_codes {
+        writeln!(
+            content,
+            "<li><code>{}</code></li>",
+            recovery_code.expose_secret()
+        )
+        .unwrap();
+    }
+    content.push_str("</ul>");
+    Ok(totp_page("", &content))
+}
+
+#[tracing::instrument(
+    name = "Disable TOTP",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn disable_two_factor(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    totp_cipher: web::Data<TotpCipher>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let is_valid = verify_second_factor(**user_id, form.0.code, &totp_cipher, &connection_pool)
+        .await
+        .map_err(e500)?;
+    if !is_valid {
+        FlashMessage::error("Invalid authentication code.").send();
+        return Ok(see_other("/admin/totp"));
+    }
+
+    disable_totp(**user_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been turned off.").send();
+    Ok(see_other("/admin/totp"))
+}
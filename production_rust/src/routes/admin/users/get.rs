@@ -0,0 +1,122 @@
+use crate::authentication::{Role, UserId};
+use crate::routes::admin_page;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct User {
+    user_id: Uuid,
+    username: String,
+    role: String,
+    disabled: bool,
+}
+
+pub async fn manage_users(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    users_page(*user_id.into_inner(), &connection_pool, &msg_html).await
+}
+
+// also rendered straight from `invite_user`, the only time a temporary password is shown
+pub(super) async fn users_page(
+    current_user_id: Uuid,
+    connection_pool: &PgPool,
+    msg_html: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let users = get_users(connection_pool).await.map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for user in &users {
+        // owners cannot lock themselves out
+        let actions = if user.user_id == current_user_id {
+            "(you)".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/users/disable" method="post">
+                        <input hidden type="text" name="user_id" value="{user_id}">
+                        <input hidden type="text" name="disabled" value="{disable}">
+                        <button type="submit">{disable_label}</button>
+                    </form>
+                    <form action="/admin/users/delete" method="post">
+                        <input hidden type="text" name="user_id" value="{user_id}">
+                        <button type="submit">Delete</button>
+                    </form>"#,
+                user_id = user.user_id,
+                disable = !user.disabled,
+                disable_label = if user.disabled { "Enable" } else { "Disable" },
+            )
+        };
+        writeln!(
+            rows_html,
+            r#"<tr>
+                <td>{username}</td>
+                <td>{role}</td>
+                <td>{status}</td>
+                <td>{actions}</td>
+            </tr>"#,
+            username = htmlescape::encode_minimal(&user.username),
+            role = user.role,
+            status = if user.disabled { "Disabled" } else { "Active" },
+        )
+        .unwrap();
+    }
+
+    let mut roles_html = String::new();
+    for role in Role::ALL {
+        writeln!(roles_html, r#"<option value="{role}">{role}</option>"#).unwrap();
+    }
+
+    Ok(admin_page(
+        "Users",
+        "",
+        &format!(
+            r#"{msg_html}
+        <table>
+            <tr>
+                <th>Username</th>
+                <th>Role</th>
+                <th>Status</th>
+                <th></th>
+            </tr>
+            {rows_html}
+        </table>
+        <h2>Invite a collaborator</h2>
+        <form action="/admin/users" method="post">
+            <div class="input-group">
+                <label for="username">Username:</label>
+                <input type="text" id="username" name="username" placeholder="Enter a username">
+            </div>
+            <div class="input-group">
+                <label for="role">Role:</label>
+                <select id="role" name="role">
+                    {roles_html}
+                </select>
+            </div>
+            <button type="submit">Invite</button>
+            <a href="/admin/dashboard"><button type="button">Back</button></a>
+        </form>"#
+        ),
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_users(connection_pool: &PgPool) -> Result<Vec<User>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        User,
+        "SELECT user_id, username, role, disabled FROM users ORDER BY username",
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve users.")?;
+    Ok(users)
+}
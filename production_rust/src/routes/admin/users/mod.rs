@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::manage_users;
+pub use post::{delete_user, disable_user, invite_user};
@@ -0,0 +1,242 @@
+use super::get::users_page;
+use crate::authentication::{create_user, PasswordHashing, Role, SessionRegistry, UserId};
+use crate::utils::{e400, e500, no_store, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    role: String,
+}
+
+// the new user gets a temporary password to change from `/admin/password`
+#[tracing::instrument(
+    name = "Invite a user",
+    skip(form, user_id, connection_pool, password_hashing),
+    fields(user_id=%*user_id, username=%form.username)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = form.username.trim();
+    if username.is_empty() {
+        FlashMessage::error("The new user needs a username.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let role = Role::try_from(form.role.as_str()).map_err(e400)?;
+
+    let password = Secret::new(generate_temporary_password());
+    match create_user(
+        username,
+        password.clone(),
+        role,
+        &password_hashing,
+        &connection_pool,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) if is_unique_violation(&e) => {
+            FlashMessage::error("That username is already taken.").send();
+            return Ok(see_other("/admin/users"));
+        }
+        Err(e) => return Err(e500(e)),
+    }
+
+    let msg_html = format!(
+        "<p><i>{} has been invited as {}. Share their temporary password, \
+        it will not be shown again:</i></p>\n<p><code>{}</code></p>",
+        htmlescape::encode_minimal(username),
+        role,
+        password.expose_secret()
+    );
+    users_page(**user_id, &connection_pool, &msg_html)
+        .await
+        .map(no_store)
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct DisableFormData {
+    user_id: Uuid,
+    disabled: bool,
+}
+
+#[tracing::instrument(
+    name = "Disable or enable a user",
+    skip(form, user_id, connection_pool, session_registry),
+    fields(user_id=%*user_id, target_user_id=%form.user_id)
+)]
+pub async fn disable_user(
+    form: web::Form<DisableFormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if form.user_id == **user_id {
+        FlashMessage::error("You cannot disable your own account.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let updated = set_disabled(form.user_id, form.disabled, &connection_pool)
+        .await
+        .map_err(e500)?;
+    // a disabled user keeps no way in, be it a browser session or an API token
+    if updated && form.disabled {
+        session_registry
+            .revoke_all(form.user_id)
+            .await
+            .map_err(e500)?;
+    }
+
+    if !updated {
+        FlashMessage::error("No matching user found.").send();
+    } else if form.disabled {
+        FlashMessage::info("The user has been disabled.").send();
+    } else {
+        FlashMessage::info("The user has been enabled.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+// disabling also revokes the API tokens of the user, enabling them again does not bring them back
+#[tracing::instrument(skip(connection_pool))]
+async fn set_disabled(
+    user_id: Uuid,
+    disabled: bool,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let n_updated = sqlx::query!(
+        "UPDATE users SET disabled = $1 WHERE user_id = $2",
+        disabled,
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update the user.")?
+    .rows_affected();
+    if disabled {
+        sqlx::query!(
+            r#"
+            UPDATE api_tokens SET revoked_at = now()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id,
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to revoke the API tokens of the user.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the change to the user.")?;
+    Ok(n_updated > 0)
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    user_id: Uuid,
+}
+
+#[tracing::instrument(
+    name = "Delete a user",
+    skip(form, user_id, connection_pool),
+    fields(user_id=%*user_id, target_user_id=%form.user_id)
+)]
+pub async fn delete_user(
+    form: web::Form<DeleteFormData>,
+    user_id: web::ReqData<UserId>,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if form.user_id == **user_id {
+        FlashMessage::error("You cannot delete your own account.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let deleted = remove_user(form.user_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+    if deleted {
+        FlashMessage::info("The user has been deleted.").send();
+    } else {
+        FlashMessage::error("No matching user found.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+// everything else referencing the user goes with them
+#[tracing::instrument(skip(connection_pool))]
+async fn remove_user(user_id: Uuid, connection_pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!("DELETE FROM api_tokens WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the API tokens of the user.")?;
+    sqlx::query!("DELETE FROM idempotency WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the saved responses of the user.")?;
+    sqlx::query!(
+        "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the recovery codes of the user.")?;
+    sqlx::query!("DELETE FROM totp_credentials WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the second factor of the user.")?;
+    sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the password reset tokens of the user.")?;
+    let n_deleted = sqlx::query!("DELETE FROM users WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the user.")?
+        .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion of the user.")?;
+    Ok(n_deleted > 0)
+}
+
+fn is_unique_violation(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) => e.code().as_deref() == Some("23505"),
+        _ => false,
+    }
+}
+
+fn generate_temporary_password() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(20)
+        .collect()
+}
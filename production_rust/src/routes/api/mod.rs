@@ -0,0 +1,34 @@
+mod subscribers;
+mod subscriptions;
+
+pub use subscribers::*;
+pub use subscriptions::*;
+
+use actix_web::http::StatusCode;
+use actix_web::{error, web, HttpRequest, HttpResponse};
+
+#[derive(serde::Serialize)]
+struct ApiErrorBody<'a> {
+    error: ApiErrorDetails<'a>,
+}
+
+#[derive(serde::Serialize)]
+struct ApiErrorDetails<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+/// The machine-readable error body shared by every `/api` endpoint.
+pub fn api_error(status: StatusCode, code: &str, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(ApiErrorBody {
+        error: ApiErrorDetails { code, message },
+    })
+}
+
+// malformed payloads get the same error body as validation failures
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|e: error::JsonPayloadError, _: &HttpRequest| {
+        let response = api_error(StatusCode::BAD_REQUEST, "invalid_payload", &e.to_string());
+        error::InternalError::from_response(e, response).into()
+    })
+}
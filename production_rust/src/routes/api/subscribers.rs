@@ -0,0 +1,81 @@
+use crate::routes::api_error;
+use crate::routes::subscribe::subscriptions::error_chain_fmt;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct SubscribersQuery {
+    status: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct Subscriber {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+}
+
+#[derive(serde::Serialize)]
+struct SubscribersResponse {
+    subscribers: Vec<Subscriber>,
+}
+
+#[tracing::instrument(name = "List subscribers through the API", skip(query, pool))]
+pub async fn api_subscribers(
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ListSubscribersError> {
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE $1::text IS NULL OR status = $1
+        ORDER BY subscribed_at
+        "#,
+        query.status,
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve subscribers.")?
+    .into_iter()
+    .map(|r| Subscriber {
+        email: r.email,
+        name: r.name,
+        status: r.status,
+        subscribed_at: r.subscribed_at.to_rfc3339(),
+    })
+    .collect();
+
+    Ok(HttpResponse::Ok().json(SubscribersResponse { subscribers }))
+}
+
+#[derive(thiserror::Error)]
+pub enum ListSubscribersError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for ListSubscribersError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ListSubscribersError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // the cause chain is logged, never sent back to the caller
+        api_error(
+            self.status_code(),
+            "unexpected_error",
+            "An unexpected error occurred.",
+        )
+    }
+}
+impl std::fmt::Debug for ListSubscribersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::domain::NewSubscriber;
+use crate::email_client::EmailClient;
+use crate::routes::api_error;
+use crate::routes::subscribe::subscriptions::{
+    register_subscriber, send_confirmation_email, FormData, SubscribeError,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::templates::Templates;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+
+/// A `SubscribeError` answered with the JSON body shared by every `/api` endpoint,
+/// the subscribe form keeps redirecting with a flash message instead.
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ApiSubscribeError(#[from] SubscribeError);
+
+impl From<anyhow::Error> for ApiSubscribeError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(e.into())
+    }
+}
+
+impl ResponseError for ApiSubscribeError {
+    fn status_code(&self) -> StatusCode {
+        self.0.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let (code, message) = match &self.0 {
+            SubscribeError::ValidationError(e) => ("validation_error", e.as_str()),
+            SubscribeError::AlreadySubscribed => (
+                "already_subscribed",
+                "This email address is already subscribed.",
+            ),
+            // the cause chain is logged, never sent back to the caller
+            SubscribeError::UnexpectedError(_) => {
+                ("unexpected_error", "An unexpected error occurred.")
+            }
+        };
+        api_error(self.status_code(), code, message)
+    }
+}
+impl std::fmt::Debug for ApiSubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionResponse<'a> {
+    email: &'a str,
+    name: &'a str,
+    status: &'a str,
+}
+
+#[tracing::instrument(
+    name = "Adding a new subscriber through the API",
+    skip(body, pool, email_client, templates, base_url),
+    fields(
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty
+    )
+)]
+pub async fn api_subscribe(
+    body: web::Json<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, ApiSubscribeError> {
+    let new_subscriber: NewSubscriber =
+        body.0.try_into().map_err(SubscribeError::ValidationError)?;
+    tracing::Span::current()
+        .record("subscriber_email", new_subscriber.email.as_ref())
+        .record("subscriber_name", new_subscriber.name.as_ref());
+    let subscription_token = register_subscriber(&pool, &new_subscriber).await?;
+
+    let response = HttpResponse::Created().json(SubscriptionResponse {
+        email: new_subscriber.email.as_ref(),
+        name: new_subscriber.name.as_ref(),
+        status: "pending_confirmation",
+    });
+    send_confirmation_email(
+        &email_client,
+        &templates,
+        new_subscriber,
+        &base_url.0,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email.")?;
+
+    Ok(response)
+}
@@ -0,0 +1,148 @@
+use actix_web::http::header::ContentType;
+use actix_web::HttpResponse;
+
+/// An admin page: a light card on a dark background, headed by `title`.
+/// `style` holds the rules only the page needs, they come after the shared ones.
+/// Neither `title` nor `content` are escaped.
+pub fn admin_page(title: &str, style: &str, content: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+    <style>
+        body {{
+            font-family: Arial, sans-serif;
+            margin: 0;
+            background-color: #000000;
+            display: flex;
+            justify-content: center;
+            align-items: center;
+            min-height: 100vh;
+        }}
+
+        .form-container {{
+            background-color: #F8F8F8;
+            padding: 20px;
+            border-radius: 5px;
+            box-shadow: 0 2px 5px rgba(0, 0, 0, 0.1);
+            max-width: 1000px;
+            margin: 0 auto;
+        }}
+
+        h1, h2 {{
+            text-align: center;
+            color: #3B5323;
+        }}
+
+        table {{
+            border-collapse: collapse;
+            margin-bottom: 20px;
+        }}
+
+        th, td {{
+            padding: 5px 10px;
+            border-bottom: 1px solid #ccc;
+            text-align: left;
+        }}
+
+        td form {{
+            display: inline;
+        }}
+
+        .input-group {{
+            margin-bottom: 20px;
+        }}
+
+        label {{
+            margin-right: 10px;
+            color: #3B5323;
+        }}
+
+        button[type="submit"], button[type="button"] {{
+            padding: 10px 20px;
+            background-color: #3B5323;
+            color: #ffffff;
+            border: none;
+            border-radius: 3px;
+            cursor: pointer;
+        }}
+
+        button:hover {{
+            background-color: #2A3F1B;
+        }}
+{style}
+    </style>
+</head>
+<body>
+    <div class="form-container">
+        <h1>{title}</h1>
+        {content}
+    </div>
+</body>
+</html>"#
+        ))
+}
+
+/// A page for visitors who are not logged in, headed by `title`.
+/// `style` and the escaping work as for `admin_page`.
+pub fn public_page(title: &str, style: &str, content: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <style>
+        body {{
+            margin: 0;
+            text-align: center;
+            font-family: "Merriweather", serif;
+            background-color: #111;
+            color: #fff;
+        }}
+
+        h2 {{
+            color: #007bff;
+            font-size: 2.5rem;
+            font-family: "Montserrat", sans-serif;
+            font-weight: normal;
+        }}
+
+        p {{
+            font-family: "Roboto", sans-serif;
+            font-size: 16px;
+            color: #ccc;
+        }}
+
+        button[type="submit"] {{
+            padding: 10px 20px;
+            border: 1px;
+            border-radius: 3px;
+            cursor: pointer;
+            background-color: #007bff;
+            color: #fff;
+            transition: background-color 0.3s ease;
+        }}
+
+        button:hover {{
+            background-color: #003d5a;
+        }}
+{style}
+    </style>
+</head>
+<body>
+    <main>
+        <h2>{title}</h2>
+        {content}
+    </main>
+</body>
+</html>"#
+        ))
+}
@@ -268,6 +268,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
                                     </div>
                                     <button type="submit">Login</button>
                                 </form>
+                                <p><a href="/password_reset">Forgot your password?</a></p>
                                 </div>
                             </div>
                         </div>
@@ -1,14 +1,19 @@
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::audit::{record_audit_event, AuditEvent};
+use crate::authentication::{
+    get_totp_status, validate_credentials, AuthError, Credentials, FailedLogin, LoginThrottle,
+    PasswordHashing, SessionRegistry, TotpCipher, TotpStatus,
+};
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
 use actix_web::error::InternalError;
-use actix_web::http::header::LOCATION;
+use actix_web::http::header::{LOCATION, USER_AGENT};
 use actix_web::http::StatusCode;
-use actix_web::HttpResponse;
 use actix_web::{web, ResponseError};
+use actix_web::{HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
+use std::time::Duration;
 
 #[allow(dead_code)]
 #[derive(serde::Deserialize)]
@@ -17,27 +22,81 @@ pub struct FormData {
     password: Secret<String>,
 }
 
+// every extractor is a piece of shared state the login flow needs
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
-    skip(form, connection_pool, session),
+    skip(
+        request,
+        form,
+        connection_pool,
+        password_hashing,
+        totp_cipher,
+        login_throttle,
+        session_registry,
+        session
+    ),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     connection_pool: web::Data<PgPool>,
+    password_hashing: web::Data<PasswordHashing>,
+    totp_cipher: web::Data<TotpCipher>,
+    login_throttle: web::Data<LoginThrottle>,
+    session_registry: web::Data<SessionRegistry>,
     session: TypedSession,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let ip_address = login_throttle.client_ip(&request);
+    let username = form.0.username.clone();
+    tracing::Span::current().record("username", tracing::field::display(&username));
+
+    // blocked attempts are turned away before paying for a password hash
+    if let Some(retry_after) = login_throttle
+        .retry_after(&username, ip_address.as_deref())
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        return Err(login_redirect(LoginError::throttled(retry_after)));
+    }
+
     let credentials = Credentials {
         username: form.0.username,
         password: form.0.password,
     };
 
-    match validate_credentials(credentials, &connection_pool).await {
+    match validate_credentials(credentials, &password_hashing, &connection_pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let totp_status = get_totp_status(user_id, &totp_cipher, &connection_pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
 
             session.renew();
+            // failures are only forgotten once the second factor is through as well
+            if let TotpStatus::Enabled = totp_status {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/totp"))
+                    .finish());
+            }
+            login_throttle
+                .record_success(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            let user_agent = request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok());
+            let session_id = session_registry
+                .register(user_id, ip_address.as_deref(), user_agent)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session
                 .insert_user_id(user_id)
+                .and_then(|_| session.insert_session_id(session_id))
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
@@ -45,7 +104,24 @@ pub async fn login(
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    let failed_login = login_throttle
+                        .record_failure(&username, ip_address.as_deref())
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    audit_lockouts(
+                        &failed_login,
+                        &username,
+                        ip_address.as_deref(),
+                        &connection_pool,
+                    )
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    match failed_login.retry_after {
+                        Some(retry_after) => LoginError::throttled(retry_after),
+                        None => LoginError::AuthError(e.into()),
+                    }
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
 
@@ -54,6 +130,40 @@ pub async fn login(
     }
 }
 
+pub async fn audit_lockouts(
+    failed_login: &FailedLogin,
+    username: &str,
+    ip_address: Option<&str>,
+    connection_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let lockout_seconds = failed_login.retry_after.unwrap_or_default().as_secs();
+    if failed_login.account_locked {
+        let event = AuditEvent {
+            action: "account_locked",
+            username: Some(username),
+            ip_address,
+            details: format!(
+                "Too many failed login attempts, account locked for {} seconds.",
+                lockout_seconds
+            ),
+        };
+        record_audit_event(event, connection_pool).await?;
+    }
+    if failed_login.address_locked {
+        let event = AuditEvent {
+            action: "address_locked",
+            username: Some(username),
+            ip_address,
+            details: format!(
+                "Too many failed login attempts from this address, locked for {} seconds.",
+                lockout_seconds
+            ),
+        };
+        record_audit_event(event, connection_pool).await?;
+    }
+    Ok(())
+}
+
 fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
     let response = HttpResponse::SeeOther()
@@ -66,10 +176,18 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, try again in {0} seconds.")]
+    Throttled(u64),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
 
+impl LoginError {
+    pub fn throttled(retry_after: Duration) -> Self {
+        Self::Throttled(retry_after.as_secs_f64().ceil() as u64)
+    }
+}
+
 impl std::fmt::Debug for LoginError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         error_chain_fmt(self, f)
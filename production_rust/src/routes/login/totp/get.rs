@@ -0,0 +1,50 @@
+use crate::routes::public_page;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+        input[type="text"] {
+            box-sizing: border-box;
+            height: 50px;
+            width: 200px;
+            padding: 5px;
+            border: 1px solid #ccc;
+            border-radius: 3px;
+            margin-bottom: 10px;
+        }
+"#;
+
+pub async fn totp_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(public_page(
+        "Two-factor authentication",
+        STYLE,
+        &format!(
+            r#"{msg_html}
+        <p>Enter the code from your authenticator app, or one of your recovery codes.</p>
+        <form action="/login/totp" method="post">
+            <input type="text"
+                id="code"
+                name="code"
+                autocomplete="one-time-code"
+                placeholder="123456"
+            ><br>
+            <button type="submit">Verify</button>
+        </form>"#
+        ),
+    ))
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::totp_form;
+pub use post::login_totp;
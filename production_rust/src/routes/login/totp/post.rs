@@ -0,0 +1,113 @@
+use crate::authentication::{verify_second_factor, LoginThrottle, SessionRegistry, TotpCipher};
+use crate::routes::{audit_lockouts, get_username, LoginError};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::USER_AGENT;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::time::Duration;
+
+// past this many wrong codes the password has to be entered again
+const MAX_FAILED_SECOND_FACTORS: u32 = 5;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: Secret<String>,
+}
+
+#[tracing::instrument(
+    skip(
+        request,
+        form,
+        connection_pool,
+        totp_cipher,
+        login_throttle,
+        session_registry,
+        session
+    ),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn login_totp(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    connection_pool: web::Data<PgPool>,
+    totp_cipher: web::Data<TotpCipher>,
+    login_throttle: web::Data<LoginThrottle>,
+    session_registry: web::Data<SessionRegistry>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = match session.get_pending_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
+        None => return Ok(see_other("/login")),
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    // wrong codes count as failed logins, a new password login must not
+    // be enough to get another round of guesses
+    let username = get_username(user_id, &connection_pool)
+        .await
+        .map_err(e500)?;
+    let ip_address = login_throttle.client_ip(&request);
+    if let Some(retry_after) = login_throttle
+        .retry_after(&username, ip_address.as_deref())
+        .await
+        .map_err(e500)?
+    {
+        return Ok(throttled(retry_after, &session));
+    }
+
+    if verify_second_factor(user_id, form.0.code, &totp_cipher, &connection_pool)
+        .await
+        .map_err(e500)?
+    {
+        login_throttle
+            .record_success(&username)
+            .await
+            .map_err(e500)?;
+        session.renew();
+        session.remove_pending_user_id();
+        session.insert_user_id(user_id).map_err(e500)?;
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok());
+        let session_id = session_registry
+            .register(user_id, ip_address.as_deref(), user_agent)
+            .await
+            .map_err(e500)?;
+        session.insert_session_id(session_id).map_err(e500)?;
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    let failed_login = login_throttle
+        .record_failure(&username, ip_address.as_deref())
+        .await
+        .map_err(e500)?;
+    audit_lockouts(
+        &failed_login,
+        &username,
+        ip_address.as_deref(),
+        &connection_pool,
+    )
+    .await
+    .map_err(e500)?;
+    if let Some(retry_after) = failed_login.retry_after {
+        return Ok(throttled(retry_after, &session));
+    }
+    if session.record_failed_second_factor().map_err(e500)? >= MAX_FAILED_SECOND_FACTORS {
+        session.logout();
+        FlashMessage::error("Too many invalid codes, please log in again.").send();
+        return Ok(see_other("/login"));
+    }
+    FlashMessage::error("Invalid authentication code.").send();
+    Ok(see_other("/login/totp"))
+}
+
+fn throttled(retry_after: Duration, session: &TypedSession) -> HttpResponse {
+    session.logout();
+    FlashMessage::error(LoginError::throttled(retry_after).to_string()).send();
+    see_other("/login")
+}
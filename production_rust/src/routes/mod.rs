@@ -1,9 +1,15 @@
 mod admin;
+mod api;
 mod health_check;
+mod layout;
 mod login;
+mod password_reset;
 mod subscribe;
 
 pub use admin::*;
+pub use api::*;
 pub use health_check::*;
+pub use layout::*;
 pub use login::*;
+pub use password_reset::*;
 pub use subscribe::*;
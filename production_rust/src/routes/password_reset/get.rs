@@ -0,0 +1,82 @@
+use crate::authentication::is_valid_reset_token;
+use crate::routes::public_page;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+        a {
+            color: #fff;
+        }
+
+        input[type="text"],
+        input[type="password"] {
+            box-sizing: border-box;
+            height: 50px;
+            width: 200px;
+            padding: 5px;
+            border: 1px solid #ccc;
+            border-radius: 3px;
+            margin-bottom: 10px;
+        }
+"#;
+
+pub async fn password_reset_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let content = r#"<p>Enter your username, a reset link will be sent to your recovery email.</p>
+        <form action="/password_reset" method="post">
+            <input type="text" id="username" name="username" placeholder="admin username"><br>
+            <button type="submit">Send reset link</button>
+        </form>"#;
+    reset_page(&flash_messages, content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: Secret<String>,
+}
+
+pub async fn reset_password_form(
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !is_valid_reset_token(&parameters.token, &connection_pool)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("This reset link is invalid or has expired.").send();
+        return Ok(see_other("/password_reset"));
+    }
+
+    let content = format!(
+        r#"<p>Choose a new password.</p>
+        <form action="/password_reset/confirm" method="post">
+            <input type="hidden" name="token" value="{}">
+            <input type="password" id="new_password" name="new_password" placeholder="New password"><br>
+            <input type="password" id="new_password_check" name="new_password_check" placeholder="Confirm password"><br>
+            <button type="submit">Reset password</button>
+        </form>"#,
+        htmlescape::encode_attribute(parameters.token.expose_secret())
+    );
+    Ok(reset_page(&flash_messages, &content))
+}
+
+fn reset_page(flash_messages: &IncomingFlashMessages, content: &str) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    public_page(
+        "Reset your password",
+        STYLE,
+        &format!(
+            r#"{msg_html}
+        {content}
+        <p><a href="/login">Back to login</a></p>"#
+        ),
+    )
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{password_reset_form, reset_password_form};
+pub use post::{request_password_reset, reset_password};
@@ -0,0 +1,172 @@
+use crate::authentication::{
+    create_password_reset_token, reset_password as reset_user_password, PasswordHashing,
+    PasswordPolicy, SessionRegistry, RESET_TOKEN_LIFETIME_MINUTES,
+};
+use crate::email_client::EmailClient;
+use crate::rate_limiter::{RateLimitedAction, RateLimiter};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+// the answer is the same whether the account exists or not, and the email
+// is sent in the background so that response times do not tell either
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(request, form, connection_pool, email_client, base_url, rate_limiter),
+    fields(username=%form.username)
+)]
+pub async fn request_password_reset(
+    request: HttpRequest,
+    form: web::Form<RequestFormData>,
+    connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = form.0.username;
+    let ip_address = rate_limiter.client_ip(&request);
+    if rate_limiter
+        .retry_after(
+            RateLimitedAction::PasswordReset,
+            &username,
+            ip_address.as_deref(),
+        )
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        FlashMessage::error("Too many reset requests, please try again later.").send();
+        return Ok(see_other("/password_reset"));
+    }
+    rate_limiter
+        .record(
+            RateLimitedAction::PasswordReset,
+            &username,
+            ip_address.as_deref(),
+        )
+        .await
+        .map_err(e500)?;
+
+    tokio::spawn(
+        async move {
+            if let Err(e) =
+                send_password_reset(&username, &connection_pool, &email_client, &base_url.0).await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send a password reset email."
+                );
+            }
+        }
+        .in_current_span(),
+    );
+
+    FlashMessage::info("If that account has a recovery email, a reset link has been sent to it.")
+        .send();
+    Ok(see_other("/login"))
+}
+
+async fn send_password_reset(
+    username: &str,
+    connection_pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    let request = match create_password_reset_token(username, connection_pool).await? {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    let reset_link = format!(
+        "{}/password_reset/confirm?token={}",
+        base_url,
+        request.token.expose_secret()
+    );
+    let html_body = format!(
+        "<p>Someone asked to reset the password of {}.</p>\
+        <p>Click <a href=\"{}\">here</a> to choose a new one, \
+        the link expires in {} minutes.</p>\
+        <p>If it was not you, you can ignore this email.</p>",
+        htmlescape::encode_minimal(username),
+        reset_link,
+        RESET_TOKEN_LIFETIME_MINUTES
+    );
+    let text_body = format!(
+        "Someone asked to reset the password of {}.\n\
+        Visit {} to choose a new one, the link expires in {} minutes.\n\
+        If it was not you, you can ignore this email.",
+        username, reset_link, RESET_TOKEN_LIFETIME_MINUTES
+    );
+    email_client
+        .send_email(
+            &request.recipient,
+            "Reset your password",
+            &html_body,
+            &text_body,
+            None,
+        )
+        .await
+        .context("Failed to send the password reset email.")
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    connection_pool: web::Data<PgPool>,
+    password_policy: web::Data<PasswordPolicy>,
+    password_hashing: web::Data<PasswordHashing>,
+    session_registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let retry_location = format!(
+        "/password_reset/confirm?token={}",
+        urlencoding::encode(form.token.expose_secret())
+    );
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        FlashMessage::error("Two different passwords entered - the field values must match.")
+            .send();
+        return Ok(see_other(&retry_location));
+    }
+    if let Err(rejection) = password_policy.check(&form.new_password, None, &[]) {
+        FlashMessage::error(rejection.to_string()).send();
+        return Ok(see_other(&retry_location));
+    }
+
+    let user_id = match reset_user_password(
+        form.0.token,
+        form.0.new_password,
+        &password_hashing,
+        &connection_pool,
+    )
+    .await
+    .map_err(e500)?
+    {
+        Some(user_id) => user_id,
+        None => {
+            FlashMessage::error("This reset link is invalid or has expired.").send();
+            return Ok(see_other("/password_reset"));
+        }
+    };
+    // whoever knew the old password is logged out as well
+    session_registry.revoke_all(user_id).await.map_err(e500)?;
+
+    FlashMessage::info("Your password has been reset, you can now log in.").send();
+    Ok(see_other("/login"))
+}
@@ -1,15 +1,42 @@
+use crate::subscriber_lists::get_subscriber_lists;
+use crate::utils::e500;
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::fmt::Write;
 
 pub async fn get_subscribe(
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    // the selector only shows up once there are lists to pick from
+    let lists = get_subscriber_lists(&pool).await.map_err(e500)?;
+    let list_html = if lists.is_empty() {
+        String::new()
+    } else {
+        let mut options = String::from(r#"<option value="">No particular list</option>"#);
+        for list in lists {
+            write!(
+                options,
+                r#"<option value="{}">{}</option>"#,
+                list.list_id,
+                htmlescape::encode_minimal(&list.name)
+            )
+            .unwrap();
+        }
+        format!(
+            r#"<div class="input-column">
+                                        <label for="list_id"><h3>List:</h3><br>
+                                            <select id="list_id" name="list_id">{options}</select>
+                                        </label>
+                                    </div>"#
+        )
+    };
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -255,6 +282,7 @@ pub async fn get_subscribe(
                                             >
                                         </label>
                                     </div>
+                                    {list_html}
                                     <button type="submit">Subscribe</button>
                                 </form>
                                 </div>
@@ -1,7 +1,11 @@
 pub mod get;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod subscriptions_privacy;
+pub mod subscriptions_unsubscribe;
 
 pub use get::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_privacy::*;
+pub use subscriptions_unsubscribe::*;
@@ -1,15 +1,19 @@
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, EmailError};
+use crate::rate_limiter::{RateLimitedAction, RateLimiter};
 use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_lists::join_list;
+use crate::templates::Templates;
 use crate::utils::see_other;
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
 use chrono::Utc;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction};
+use tracing::Instrument;
 use uuid::Uuid;
 
 #[allow(dead_code)]
@@ -17,6 +21,8 @@ use uuid::Uuid;
 pub struct FormData {
     email: String,
     name: String,
+    #[serde(default)]
+    list_id: Option<String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -29,69 +35,36 @@ impl TryFrom<FormData> for NewSubscriber {
     }
 }
 
-// TODO: Create separate API integration
-// hard http responses are useful here, just not for my website
-// #[tracing::instrument(
-//     name = "Adding a new subscriber",
-//     skip(form, pool, email_client),
-//     fields(
-//         subscriber_email = %form.email,
-//         subscriber_name = %form.name
-//     )
-// )]
-// pub async fn subscribe(
-//     form: web::Form<FormData>,
-//     pool: web::Data<PgPool>,
-//     email_client: web::Data<EmailClient>,
-//     base_url: web::Data<ApplicationBaseUrl>,
-// ) -> Result<HttpResponse, SubscribeError> {
-//     let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-//     let mut transaction = pool
-//         .begin()
-//         .await
-//         .context("Failed to aquire a Postgres connection from the pool.")?;
-//     let subscriber_id = insert_subscriber(&new_subscriber, &mut transaction)
-//         .await
-//         .context("Failed to insert a new subscriber in the database.")?;
-
-//     let subscription_token = generate_subscription_token();
-
-//     store_token(&mut transaction, subscriber_id, &subscription_token)
-//         .await
-//         .context("Failed to store the confirmation token for a new subscriber.")?;
-//     transaction
-//         .commit()
-//         .await
-//         .context("Failed to commit SQLX transaction to store a new subscriber.")?;
-//     send_confirmation_email(
-//         &email_client,
-//         new_subscriber,
-//         &base_url.0,
-//         &subscription_token,
-//     )
-//     .await
-//     .context("Failed to send a confirmation email.")?;
-
-//     Ok(HttpResponse::Ok().finish())
-// }
-
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client),
+    skip(request, form, pool, email_client, templates, base_url, rate_limiter),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
     )
 )]
 pub async fn subscribe(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
     base_url: web::Data<ApplicationBaseUrl>,
+    rate_limiter: web::Data<RateLimiter>,
 ) -> Result<HttpResponse, SubscribeError> {
     let response = see_other("/subscriptions");
 
-    let new_subscriber = match form.0.try_into() {
+    let list_id = match form.list_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(list_id) => match Uuid::parse_str(list_id) {
+            Ok(list_id) => Some(list_id),
+            Err(_) => {
+                FlashMessage::error("The selected list does not exist.").send();
+                return Ok(response);
+            }
+        },
+    };
+    let new_subscriber: NewSubscriber = match form.0.try_into() {
         Ok(subscriber) => subscriber,
         Err(e) => {
             FlashMessage::error(e).send();
@@ -99,22 +72,14 @@ pub async fn subscribe(
         }
     };
 
-    let mut transaction = pool
-        .begin()
-        .await
-        .context("Failed to aquire a Postgres connection from the pool.")?;
-    match insert_subscriber(&new_subscriber, &mut transaction).await {
-        Ok(subscriber_id) => {
-            let subscription_token = generate_subscription_token();
-            store_token(&mut transaction, subscriber_id, &subscription_token)
-                .await
-                .context("Failed to store the confirmation token for a new subscriber.")?;
-            transaction
-                .commit()
-                .await
-                .context("Failed to commit SQLX transaction to store a new subscriber.")?;
+    match register_subscriber(&pool, &new_subscriber).await {
+        Ok(subscription_token) => {
+            if let Some(list_id) = list_id {
+                join_list(new_subscriber.email.as_ref(), list_id, &pool).await?;
+            }
             if send_confirmation_email(
                 &email_client,
+                &templates,
                 new_subscriber,
                 &base_url.0,
                 &subscription_token,
@@ -130,41 +95,196 @@ pub async fn subscribe(
                 .send();
             }
         }
-        Err(_) => {
-            FlashMessage::error("It seems you're already subscribed!").send();
+        // the answer is the same whatever the state of the address, so that the form
+        // cannot be used to find out who subscribed; pending subscribers get a new link
+        // in the background, the first one may have expired or been lost
+        Err(SubscribeError::AlreadySubscribed) => {
+            let email = new_subscriber.email;
+            let ip_address = rate_limiter.client_ip(&request);
+            if rate_limiter
+                .retry_after(
+                    RateLimitedAction::ConfirmationResend,
+                    email.as_ref(),
+                    ip_address.as_deref(),
+                )
+                .await?
+                .is_none()
+            {
+                rate_limiter
+                    .record(
+                        RateLimitedAction::ConfirmationResend,
+                        email.as_ref(),
+                        ip_address.as_deref(),
+                    )
+                    .await?;
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = send_new_confirmation(
+                            email,
+                            list_id,
+                            &pool,
+                            &email_client,
+                            &templates,
+                            &base_url.0,
+                        )
+                        .await
+                        {
+                            tracing::error!(
+                                error.cause_chain = ?e,
+                                error.message = %e,
+                                "Failed to resend a confirmation email"
+                            );
+                        }
+                    }
+                    .in_current_span(),
+                );
+            }
+            FlashMessage::info("You are now subscribed!").send();
         }
+        Err(e) => return Err(e),
     }
 
     Ok(response)
 }
 
+/// Sends a new confirmation link to `email` if it is still pending confirmation,
+/// adding it to `list_id` along the way. Confirmed subscribers cannot be added
+/// to lists by anyone knowing their address.
+pub async fn send_new_confirmation(
+    email: SubscriberEmail,
+    list_id: Option<Uuid>,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+) -> Result<(), anyhow::Error> {
+    let (pending_subscriber, subscription_token) =
+        match renew_confirmation_token(pool, email).await? {
+            Some(renewed) => renewed,
+            None => return Ok(()),
+        };
+    if let Some(list_id) = list_id {
+        join_list(pending_subscriber.email.as_ref(), list_id, pool).await?;
+    }
+    send_confirmation_email(
+        email_client,
+        templates,
+        pending_subscriber,
+        base_url,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email.")
+}
+
+/// Stores a pending subscriber along with their tokens,
+/// returning the token to put in the confirmation link.
+#[tracing::instrument(name = "Register a new subscriber", skip(pool, new_subscriber))]
+pub async fn register_subscriber(
+    pool: &PgPool,
+    new_subscriber: &NewSubscriber,
+) -> Result<String, SubscribeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool.")?;
+    let subscriber_id = match insert_subscriber(new_subscriber, &mut transaction).await {
+        Ok(subscriber_id) => subscriber_id,
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+            return Err(SubscribeError::AlreadySubscribed);
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to insert a new subscriber in the database.")
+                .into())
+        }
+    };
+
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a new subscriber.")?;
+    store_unsubscribe_token(
+        &mut transaction,
+        subscriber_id,
+        &generate_subscription_token(),
+    )
+    .await
+    .context("Failed to store the unsubscribe token for a new subscriber.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQLX transaction to store a new subscriber.")?;
+    Ok(subscription_token)
+}
+
+/// Issues a new confirmation token for a subscriber who has yet to confirm,
+/// returning who to send it to. `None` if `email` is not pending confirmation.
+#[tracing::instrument(name = "Renew a confirmation token", skip(pool))]
+pub async fn renew_confirmation_token(
+    pool: &PgPool,
+    email: SubscriberEmail,
+) -> Result<Option<(NewSubscriber, String)>, SubscribeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to aquire a Postgres connection from the pool.")?;
+    let row = sqlx::query!(
+        r#"
+        SELECT id, name FROM subscriptions
+        WHERE email = $1 AND status = 'pending_confirmation'
+        "#,
+        email.as_ref(),
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to look up a pending subscriber.")?;
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, row.id, &subscription_token)
+        .await
+        .context("Failed to store a renewed confirmation token.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQLX transaction to store a renewed confirmation token.")?;
+
+    let pending_subscriber = NewSubscriber {
+        email,
+        name: SubscriberName::parse(row.name).map_err(SubscribeError::ValidationError)?,
+    };
+    Ok(Some((pending_subscriber, subscription_token)))
+}
+
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(email_client, templates, new_subscriber, base_url, subscription_token)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
+    templates: &Templates,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), EmailError> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
     );
-
-    let html_body = format!(
-        "Welcome to our newsletter!<br />
-        Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    let text_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
+    let email = templates.render_confirmation(new_subscriber.name.as_ref(), &confirmation_link);
 
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &text_body)
+        .send_email(
+            &new_subscriber.email,
+            "Welcome!",
+            &email.html,
+            &email.text,
+            None,
+        )
         .await
 }
 
@@ -191,7 +311,7 @@ pub async fn insert_subscriber(
     Ok(subscriber_id)
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -220,10 +340,33 @@ pub async fn store_token(
     Ok(())
 }
 
+#[tracing::instrument(
+    name = "Saving unsubscribe token to database",
+    skip(unsubscribe_token, transaction)
+)]
+pub async fn store_unsubscribe_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    unsubscribe_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id)
+        VALUES($1, $2)"#,
+        unsubscribe_token,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("This email address is already subscribed.")]
+    AlreadySubscribed,
     #[error(transparent)] // impl Display, source
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -232,6 +375,7 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::AlreadySubscribed => StatusCode::CONFLICT,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
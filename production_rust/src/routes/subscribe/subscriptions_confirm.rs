@@ -1,5 +1,19 @@
-use actix_web::{web, HttpResponse};
+use super::subscriptions::send_new_confirmation;
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::rate_limiter::{RateLimitedAction, RateLimiter};
+use crate::routes::{error_chain_fmt, public_page};
+use crate::startup::ApplicationBaseUrl;
+use crate::templates::Templates;
+use crate::utils::{e500, see_other};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use tracing::Instrument;
 use uuid::Uuid;
 
 #[allow(dead_code)]
@@ -8,31 +22,132 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&parameters.subscription_token, &pool).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, pool, settings)
+)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, ConfirmError> {
+    let token = get_subscription_token(&parameters.subscription_token, &pool)
+        .await
+        .context("Failed to retrieve the subscriber associated with the confirmation token.")?
+        .filter(|token| token.consumed_at.is_none())
+        .ok_or(ConfirmError::UnknownToken)?;
+    let lifetime = chrono::Duration::from_std(settings.confirmation_token_lifetime())
+        .context("The confirmation token lifetime is out of range.")?;
+    if token.created_at + lifetime < Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
+
+    // The subscriber may have unsubscribed while the link was still valid
+    if !confirm_subscriber(token.subscriber_id, &pool)
+        .await
+        .context("Failed to confirm the subscriber.")?
+    {
+        return Err(ConfirmError::UnknownToken);
+    }
+
+    Ok(public_page(
+        "Subscription confirmed",
+        "",
+        "<p>Thank you, your subscription is confirmed.</p>",
+    ))
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct ResendFormData {
+    email: String,
+}
+
+/// Answers the same whether or not the address is awaiting confirmation,
+/// so the form cannot be used to find out who subscribed.
+/// The email is sent in the background so that response times do not tell either.
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(request, form, pool, email_client, templates, base_url, rate_limiter)
+)]
+pub async fn resend_confirmation(
+    request: HttpRequest,
+    form: web::Form<ResendFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    rate_limiter: web::Data<RateLimiter>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let response = see_other("/subscriptions");
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(response);
+        }
     };
 
-    match id {
-        None => HttpResponse::InternalServerError().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(subscriber_id, &pool).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+    let ip_address = rate_limiter.client_ip(&request);
+    if rate_limiter
+        .retry_after(
+            RateLimitedAction::ConfirmationResend,
+            email.as_ref(),
+            ip_address.as_deref(),
+        )
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        FlashMessage::error("Too many requests, please try again later.").send();
+        return Ok(response);
+    }
+    rate_limiter
+        .record(
+            RateLimitedAction::ConfirmationResend,
+            email.as_ref(),
+            ip_address.as_deref(),
+        )
+        .await
+        .map_err(e500)?;
+
+    tokio::spawn(
+        async move {
+            if let Err(e) =
+                send_new_confirmation(email, None, &pool, &email_client, &templates, &base_url.0)
+                    .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to resend a confirmation email"
+                );
             }
-            HttpResponse::Ok().finish()
         }
-    }
+        .in_current_span(),
+    );
+
+    FlashMessage::info(
+        "If that address is awaiting confirmation, a new confirmation link has been sent to it.",
+    )
+    .send();
+    Ok(response)
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
 }
 
-#[tracing::instrument(name = "Get subscriber id from token", skip(subscription_token, pool))]
-pub async fn get_subscriber_id_from_token(
+#[tracing::instrument(name = "Get subscription token", skip(subscription_token, pool))]
+async fn get_subscription_token(
     subscription_token: &str,
     pool: &PgPool,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        "SELECT subscriber_id FROM subscription_tokens \
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        SubscriptionToken,
+        "SELECT subscriber_id, created_at, consumed_at FROM subscription_tokens \
         WHERE subscription_token = $1",
         subscription_token,
     )
@@ -42,20 +157,88 @@ pub async fn get_subscriber_id_from_token(
         tracing::error!("Failed to execute query {:?}", e);
         e
     })?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(result)
 }
 
+/// Every token of the subscriber is used up along the way,
+/// a confirmation link cannot be followed twice.
+/// `false` if the subscriber was not pending confirmation.
 #[tracing::instrument(name = "Confirm subscriber", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(subscriber_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+pub async fn confirm_subscriber(subscriber_id: Uuid, pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let n_confirmed = sqlx::query!(
+        "UPDATE subscriptions SET status = 'confirmed' \
+        WHERE id = $1 AND status = 'pending_confirmation'",
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query {:?}", e);
+        e
+    })?
+    .rows_affected();
     sqlx::query!(
-        "UPDATE subscriptions SET status = 'confirmed' WHERE id = $1",
+        "UPDATE subscription_tokens SET consumed_at = now() \
+        WHERE subscriber_id = $1 AND consumed_at IS NULL",
         subscriber_id
     )
-    .fetch_optional(pool)
+    .execute(&mut transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query {:?}", e);
         e
     })?;
-    Ok(())
+    transaction.commit().await?;
+    Ok(n_confirmed > 0)
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("This confirmation link is invalid or has already been used.")]
+    UnknownToken,
+    #[error("This confirmation link has expired.")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for ConfirmError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmError::UnknownToken => StatusCode::NOT_FOUND,
+            ConfirmError::ExpiredToken => StatusCode::GONE,
+            ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = match self {
+            ConfirmError::UnexpectedError(_) => public_page(
+                "Something went wrong",
+                "",
+                "<p>We could not confirm your subscription, please try again later.</p>",
+            ),
+            _ => public_page(
+                "Confirmation failed",
+                "",
+                &format!(
+                    r#"<p>{self}</p>
+                <p>Enter your email address to receive a new one.</p>
+                <form action="/subscriptions/confirm/resend" method="post">
+                    <input type="email" name="email" placeholder="Your email" required>
+                    <button type="submit">Resend confirmation</button>
+                </form>"#
+                ),
+            ),
+        };
+        *response.status_mut() = self.status_code();
+        response
+    }
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
 }
@@ -0,0 +1,261 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::{personal_data_response, public_page};
+use crate::startup::{ApplicationBaseUrl, HmacSecret, SuppressionHashKey};
+use crate::subscriber_data::{
+    erase_personal_data, export_personal_data, sign_data_request, verify_data_request, DataRequest,
+    DATA_REQUEST_LINK_LIFETIME_HOURS,
+};
+use crate::utils::{e500, see_other};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::fmt::Write;
+use tracing::Instrument;
+
+pub async fn data_request_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(public_page(
+        "Your data",
+        "",
+        &format!(
+            r#"{msg_html}
+                <p>Ask for a copy of the data we hold about you, or for all of it to be erased.
+                We will email you a link to confirm the request.</p>
+                <form action="/subscriptions/privacy" method="post">
+                    <input type="email" name="email" placeholder="Your email" required>
+                    <select name="request">
+                        <option value="export">Send me a copy</option>
+                        <option value="erase">Erase my data</option>
+                    </select>
+                    <button type="submit">Send link</button>
+                </form>"#
+        ),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestFormData {
+    email: String,
+    request: String,
+}
+
+/// Answers the same whether or not anything is held about the address,
+/// the form cannot be used to find out who subscribed.
+/// The lookup and the email happen in the background so that response times do not tell either.
+#[tracing::instrument(
+    name = "Request personal data",
+    skip(form, connection_pool, email_client, base_url, hmac_secret)
+)]
+pub async fn request_personal_data(
+    form: web::Form<DataRequestFormData>,
+    connection_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let response = see_other("/subscriptions/privacy");
+    let form = form.0;
+    let request = match DataRequest::try_from(form.request.as_str()) {
+        Ok(request) => request,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(response);
+        }
+    };
+    let recipient = match SubscriberEmail::parse(form.email) {
+        Ok(recipient) => recipient,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(response);
+        }
+    };
+
+    tokio::spawn(
+        async move {
+            if let Err(e) = send_data_request_link_if_known(
+                request,
+                &recipient,
+                &connection_pool,
+                &email_client,
+                &base_url.0,
+                &hmac_secret.0,
+            )
+            .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send a data request link"
+                );
+            }
+        }
+        .in_current_span(),
+    );
+
+    FlashMessage::info(
+        "If we hold data about that address, a link to confirm the request has been sent to it.",
+    )
+    .send();
+    Ok(response)
+}
+
+async fn send_data_request_link_if_known(
+    request: DataRequest,
+    recipient: &SubscriberEmail,
+    connection_pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &secrecy::Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let personal_data = export_personal_data(recipient.as_ref(), connection_pool).await?;
+    if personal_data.is_empty() {
+        return Ok(());
+    }
+    send_data_request_link(request, recipient, email_client, base_url, hmac_secret).await
+}
+
+async fn send_data_request_link(
+    request: DataRequest,
+    recipient: &SubscriberEmail,
+    email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &secrecy::Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let expires_at = Utc::now().timestamp() + DATA_REQUEST_LINK_LIFETIME_HOURS * 60 * 60;
+    let signature = sign_data_request(hmac_secret, request, recipient.as_ref(), expires_at);
+    let query = serde_urlencoded::to_string([
+        ("email", recipient.as_ref()),
+        ("expires_at", &expires_at.to_string()),
+        ("signature", &signature),
+    ])?;
+    let link = format!(
+        "{}/subscriptions/privacy/{}?{}",
+        base_url,
+        request.as_str(),
+        query
+    );
+    let what = match request {
+        DataRequest::Export => "a copy of the data we hold about you",
+        DataRequest::Erase => "all the data we hold about you to be erased",
+    };
+    let html_body = format!(
+        "<p>Someone asked for {}.</p>\
+        <p>Click <a href=\"{}\">here</a> to confirm, \
+        the link expires in {} hours.</p>\
+        <p>If it was not you, you can ignore this email.</p>",
+        what,
+        htmlescape::encode_minimal(&link),
+        DATA_REQUEST_LINK_LIFETIME_HOURS
+    );
+    let text_body = format!(
+        "Someone asked for {}.\n\
+        Visit {} to confirm, the link expires in {} hours.\n\
+        If it was not you, you can ignore this email.",
+        what, link, DATA_REQUEST_LINK_LIFETIME_HOURS
+    );
+    email_client
+        .send_email(recipient, "Your data request", &html_body, &text_body, None)
+        .await
+        .context("Failed to send the data request email.")
+}
+
+#[derive(serde::Deserialize)]
+pub struct SignedDataRequest {
+    email: String,
+    expires_at: i64,
+    signature: String,
+}
+
+impl SignedDataRequest {
+    fn is_valid(&self, request: DataRequest, hmac_secret: &HmacSecret) -> bool {
+        verify_data_request(
+            &hmac_secret.0,
+            request,
+            &self.email,
+            self.expires_at,
+            &self.signature,
+        )
+    }
+}
+
+#[tracing::instrument(name = "Export personal data from a signed link", skip_all)]
+pub async fn export_own_data(
+    query: web::Query<SignedDataRequest>,
+    connection_pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !query.is_valid(DataRequest::Export, &hmac_secret) {
+        return Ok(invalid_link_page());
+    }
+    let personal_data = export_personal_data(&query.email, &connection_pool)
+        .await
+        .map_err(e500)?;
+    Ok(personal_data_response(&personal_data))
+}
+
+// following the link only asks for confirmation, link previews must not erase anything
+pub async fn erase_own_data_form(
+    query: web::Query<SignedDataRequest>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !query.is_valid(DataRequest::Erase, &hmac_secret) {
+        return Ok(invalid_link_page());
+    }
+    Ok(public_page(
+        "Erase your data",
+        "",
+        &format!(
+            r#"<p>All the data we hold about {email} will be erased, and you will no longer receive our newsletter.</p>
+                <form action="/subscriptions/privacy/erase" method="post">
+                    <input hidden type="text" name="email" value="{email}">
+                    <input hidden type="text" name="expires_at" value="{expires_at}">
+                    <input hidden type="text" name="signature" value="{signature}">
+                    <button type="submit">Erase my data</button>
+                </form>"#,
+            email = htmlescape::encode_minimal(&query.email),
+            expires_at = query.expires_at,
+            signature = htmlescape::encode_minimal(&query.signature),
+        ),
+    ))
+}
+
+#[tracing::instrument(name = "Erase personal data from a signed link", skip_all)]
+pub async fn erase_own_data(
+    form: web::Form<SignedDataRequest>,
+    connection_pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    suppression_hash_key: web::Data<SuppressionHashKey>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !form.is_valid(DataRequest::Erase, &hmac_secret) {
+        return Ok(invalid_link_page());
+    }
+    erase_personal_data(&form.email, &suppression_hash_key.0, &connection_pool)
+        .await
+        .map_err(e500)?;
+    Ok(public_page(
+        "Your data has been erased",
+        "",
+        "<p>All the data we held about you has been erased.</p>",
+    ))
+}
+
+fn invalid_link_page() -> HttpResponse {
+    let mut response = public_page(
+        "Invalid link",
+        "",
+        r#"<p>This link is invalid or has expired.</p>
+                <p><a href="/subscriptions/privacy">Ask for a new one</a></p>"#,
+    );
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
@@ -0,0 +1,182 @@
+use crate::routes::{error_chain_fmt, public_page};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Builds the unsubscribe links put in emails and checks the ones coming back.
+/// Links carry the stored token along with its signature, so that only
+/// links we issued reach the database.
+#[derive(Clone)]
+pub struct UnsubscribeLinks {
+    base_url: String,
+    signing_key: Secret<String>,
+}
+
+impl UnsubscribeLinks {
+    pub fn new(base_url: String, signing_key: Secret<String>) -> Self {
+        Self {
+            base_url,
+            signing_key,
+        }
+    }
+
+    pub fn link(&self, unsubscribe_token: &str) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            self.base_url,
+            self.sign(unsubscribe_token)
+        )
+    }
+
+    pub fn sign(&self, unsubscribe_token: &str) -> String {
+        let signature = self.mac(unsubscribe_token).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            unsubscribe_token,
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// The stored token, `None` if the signature does not match.
+    pub fn verify<'a>(&self, signed_token: &'a str) -> Option<&'a str> {
+        let (unsubscribe_token, signature) = signed_token.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(unsubscribe_token)
+            .verify_slice(&signature)
+            .ok()
+            .map(|_| unsubscribe_token)
+    }
+
+    fn mac(&self, unsubscribe_token: &str) -> Hmac<Sha256> {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(self.signing_key.expose_secret().as_bytes())
+                .expect("HMAC accepts keys of any size");
+        mac.update(unsubscribe_token.as_bytes());
+        mac
+    }
+}
+
+#[allow(dead_code)]
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(
+    name = "Show the unsubscribe form",
+    skip(parameters, pool, unsubscribe_links)
+)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    unsubscribe_links: web::Data<UnsubscribeLinks>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let unsubscribe_token = unsubscribe_links
+        .verify(&parameters.unsubscribe_token)
+        .ok_or(UnsubscribeError::UnknownToken)?;
+    get_subscriber_id_from_unsubscribe_token(unsubscribe_token, &pool)
+        .await
+        .context("Failed to retrieve the subscriber associated with the unsubscribe token.")?
+        .ok_or(UnsubscribeError::UnknownToken)?;
+
+    let unsubscribe_token = htmlescape::encode_attribute(&parameters.unsubscribe_token);
+    Ok(public_page(
+        "Unsubscribe",
+        "",
+        &format!(
+            r#"<p>Click below to stop receiving the newsletter.</p>
+                <form action="/subscriptions/unsubscribe?unsubscribe_token={unsubscribe_token}" method="post">
+                    <button type="submit">Unsubscribe</button>
+                </form>"#
+        ),
+    ))
+}
+
+// mail clients honouring RFC 8058 POST `List-Unsubscribe=One-Click` to this route,
+// so it must complete without any further confirmation step
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, pool, unsubscribe_links)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    unsubscribe_links: web::Data<UnsubscribeLinks>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let unsubscribe_token = unsubscribe_links
+        .verify(&parameters.unsubscribe_token)
+        .ok_or(UnsubscribeError::UnknownToken)?;
+    let subscriber_id = get_subscriber_id_from_unsubscribe_token(unsubscribe_token, &pool)
+        .await
+        .context("Failed to retrieve the subscriber associated with the unsubscribe token.")?
+        .ok_or(UnsubscribeError::UnknownToken)?;
+
+    unsubscribe_subscriber(subscriber_id, &pool)
+        .await
+        .context("Failed to unsubscribe the subscriber.")?;
+
+    Ok(public_page(
+        "Unsubscribed",
+        "",
+        "<p>You have been unsubscribed and will no longer receive the newsletter.</p>",
+    ))
+}
+
+#[tracing::instrument(
+    name = "Get subscriber id from unsubscribe token",
+    skip(unsubscribe_token, pool)
+)]
+pub async fn get_subscriber_id_from_unsubscribe_token(
+    unsubscribe_token: &str,
+    pool: &PgPool,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let result = sqlx::query!(
+        "SELECT subscriber_id FROM unsubscribe_tokens \
+        WHERE unsubscribe_token = $1",
+        unsubscribe_token,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result.map(|r| r.subscriber_id))
+}
+
+#[tracing::instrument(name = "Unsubscribe subscriber", skip(subscriber_id, pool))]
+pub async fn unsubscribe_subscriber(subscriber_id: Uuid, pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        subscriber_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("The unsubscribe link is invalid.")]
+    UnknownToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::UnknownToken => StatusCode::NOT_FOUND,
+            UnsubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
@@ -8,6 +8,11 @@ pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    // the entry of this session in the `SessionRegistry`
+    const SESSION_ID_KEY: &'static str = "session_id";
+    // set between a correct password and a correct second factor
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const FAILED_SECOND_FACTORS_KEY: &'static str = "failed_second_factors";
 
     pub fn logout(&self) {
         self.0.purge()
@@ -24,6 +29,40 @@ impl TypedSession {
     pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
+
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::FAILED_SECOND_FACTORS_KEY);
+    }
+
+    /// Returns how many wrong second factors have been entered so far.
+    pub fn record_failed_second_factor(&self) -> Result<u32, SessionInsertError> {
+        let n_failures = self
+            .0
+            .get::<u32>(Self::FAILED_SECOND_FACTORS_KEY)
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+            + 1;
+        self.0.insert(Self::FAILED_SECOND_FACTORS_KEY, n_failures)?;
+        Ok(n_failures)
+    }
 }
 
 impl FromRequest for TypedSession {
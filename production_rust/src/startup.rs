@@ -1,19 +1,40 @@
-use crate::authentication::reject_anonymous_users;
+use crate::authentication::{
+    reject_anonymous_users, reject_unauthorized_api_clients, reject_unauthorized_roles,
+    LoginThrottle, PasswordHashing, PasswordPolicy, SessionRegistry, TotpCipher,
+};
 use crate::configuration::{DatabaseSettings, Settings};
 use crate::email_client::EmailClient;
+use crate::idempotency::idempotent_requests;
+use crate::rate_limiter::RateLimiter;
 use crate::routes::{
-    admin_dashboard, change_key_state, change_password, change_password_form, confirm,
-    get_subscribe, health_check, log_out, login, login_form, manage_settings_form,
-    new_newsletter_form, publish_newsletter, subscribe,
+    active_sessions, add_subscriber_to_list, admin_dashboard, api_subscribe, api_subscribers,
+    api_tokens, cancel_issue, change_key_state, change_password, change_password_form,
+    change_recovery_email, confirm, confirm_subscriber_manually, confirm_totp, create_list,
+    create_token, data_request_form, data_requests_form, delete_list, delete_subscriber,
+    delete_user, delivery_failures, disable_two_factor, disable_user, drafts, enroll_totp,
+    erase_own_data, erase_own_data_form, erase_subscriber_data, export_own_data,
+    export_subscriber_data, export_subscribers, get_subscribe, health_check, import_subscribers,
+    import_subscribers_form, invite_user, issue_preview, json_config, log_out, login, login_form,
+    login_totp, manage_lists, manage_settings_form, manage_subscribers, manage_users,
+    new_newsletter_form, password_reset_form, publish_newsletter, remove_subscriber_from_list,
+    request_password_reset, request_personal_data, requeue_all_failures, requeue_failure,
+    reschedule_issue, resend_confirmation, resend_subscriber_confirmation, reset_password,
+    reset_password_form, revoke_other_sessions, revoke_session, revoke_token, scheduled_issues,
+    send_test_issue, subscribe, tag_subscriber, totp_form, totp_settings, unsubscribe,
+    unsubscribe_form, unsubscribe_subscriber_manually, untag_subscriber, UnsubscribeLinks,
 };
+use crate::templates::Templates;
+use actix_session::config::BrowserSession;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
+use actix_web::cookie::time::Duration;
 use actix_web::cookie::Key;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_lab::middleware::from_fn;
+use redis::aio::ConnectionManager;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -31,10 +52,19 @@ pub struct ApplicationBaseUrl(pub String);
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+#[derive(Clone)]
+pub struct SuppressionHashKey(pub Secret<String>);
+
 impl Application {
     pub async fn build(config: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&config.database);
-        let email_client = config.email_client.client();
+        let email_client = config
+            .email_client
+            .clone()
+            .client()
+            .map_err(anyhow::Error::msg)?;
+        let templates = Templates::load(&config.templates.directory)?;
+        let password_policy = PasswordPolicy::load(config.password_policy.clone())?;
         let address = format!("{}:{}", config.application.host, config.application.port);
         let listener = TcpListener::bind(address).expect("Failed to bind tcp");
         let port = listener.local_addr().unwrap().port();
@@ -42,9 +72,9 @@ impl Application {
             listener,
             connection_pool,
             email_client,
-            config.application.base_url,
-            config.application.hmac_secret,
-            config.redis_uri,
+            templates,
+            password_policy,
+            config,
         )
         .await?;
 
@@ -75,49 +105,218 @@ async fn run(
     listener: TcpListener,
     pg_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    templates: Templates,
+    password_policy: PasswordPolicy,
+    config: Settings,
 ) -> Result<Server, anyhow::Error> {
+    let Settings {
+        application,
+        redis_uri,
+        login_throttle,
+        rate_limits,
+        password_hashing,
+        idempotency,
+        subscriptions,
+        ..
+    } = config;
     let connection_pool = web::Data::new(pg_pool);
     let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let templates = web::Data::new(templates);
+    let password_policy = web::Data::new(password_policy);
+    let password_hashing = web::Data::new(PasswordHashing::new(&password_hashing)?);
+    let idempotency = web::Data::new(idempotency);
+    let subscriptions = web::Data::new(subscriptions);
+    let session_ttl = application.session_ttl();
+    let unsubscribe_links = web::Data::new(UnsubscribeLinks::new(
+        application.base_url.clone(),
+        application.unsubscribe_signing_key,
+    ));
+    let base_url = web::Data::new(ApplicationBaseUrl(application.base_url));
+    let hmac_secret = web::Data::new(HmacSecret(application.hmac_secret.clone()));
+    let totp_cipher = web::Data::new(TotpCipher::new(&application.totp_encryption_key));
+    let suppression_hash_key = web::Data::new(SuppressionHashKey(application.suppression_hash_key));
 
-    let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
+    let secret_key = Key::from(application.hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
 
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_client = redis::Client::open(redis_uri.expose_secret().as_str())?;
+    let redis_connection = ConnectionManager::new(redis_client).await?;
+    let login_throttle = web::Data::new(LoginThrottle::new(
+        redis_connection.clone(),
+        login_throttle,
+        application.trust_proxy_headers,
+    ));
+    let rate_limiter = web::Data::new(RateLimiter::new(
+        redis_connection.clone(),
+        rate_limits,
+        application.trust_proxy_headers,
+    ));
+    let session_registry = web::Data::new(SessionRegistry::new(redis_connection, session_ttl));
 
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(
+                        BrowserSession::default()
+                            .state_ttl(Duration::seconds(session_ttl.as_secs() as i64)),
+                    )
+                    .build(),
+            )
             .wrap(TracingLogger::default())
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::get().to(get_subscribe))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/confirm/resend",
+                web::post().to(resend_confirmation),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/subscriptions/privacy", web::get().to(data_request_form))
+            .route(
+                "/subscriptions/privacy",
+                web::post().to(request_personal_data),
+            )
+            .route(
+                "/subscriptions/privacy/export",
+                web::get().to(export_own_data),
+            )
+            .route(
+                "/subscriptions/privacy/erase",
+                web::get().to(erase_own_data_form),
+            )
+            .route(
+                "/subscriptions/privacy/erase",
+                web::post().to(erase_own_data),
+            )
+            .service(
+                web::scope("/api/v1")
+                    .app_data(json_config())
+                    .service(
+                        web::resource("/subscriptions")
+                            .wrap(from_fn(idempotent_requests))
+                            .route(web::post().to(api_subscribe)),
+                    )
+                    .service(
+                        web::scope("/subscribers")
+                            .wrap(from_fn(reject_unauthorized_api_clients))
+                            .route("", web::get().to(api_subscribers)),
+                    ),
+            )
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/totp", web::get().to(totp_form))
+            .route("/login/totp", web::post().to(login_totp))
+            .route("/password_reset", web::get().to(password_reset_form))
+            .route("/password_reset", web::post().to(request_password_reset))
+            .route(
+                "/password_reset/confirm",
+                web::get().to(reset_password_form),
+            )
+            .route("/password_reset/confirm", web::post().to(reset_password))
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(idempotent_requests))
+                    .wrap(from_fn(reject_unauthorized_roles))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/newsletter", web::get().to(new_newsletter_form))
                     .route("/newsletter", web::post().to(publish_newsletter))
+                    .route("/drafts", web::get().to(drafts))
+                    .route("/issues", web::get().to(scheduled_issues))
+                    .route("/issues/preview", web::get().to(issue_preview))
+                    .route("/issues/test_send", web::post().to(send_test_issue))
+                    .route("/issues/reschedule", web::post().to(reschedule_issue))
+                    .route("/issues/cancel", web::post().to(cancel_issue))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
+                    .route("/password/email", web::post().to(change_recovery_email))
                     .route("/logout", web::post().to(log_out))
+                    .route("/sessions", web::get().to(active_sessions))
+                    .route("/sessions/revoke", web::post().to(revoke_session))
+                    .route(
+                        "/sessions/revoke_others",
+                        web::post().to(revoke_other_sessions),
+                    )
+                    .route("/subscribers", web::get().to(manage_subscribers))
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(import_subscribers_form),
+                    )
+                    .route("/subscribers/import", web::post().to(import_subscribers))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/resend",
+                        web::post().to(resend_subscriber_confirmation),
+                    )
+                    .route(
+                        "/subscribers/confirm",
+                        web::post().to(confirm_subscriber_manually),
+                    )
+                    .route(
+                        "/subscribers/unsubscribe",
+                        web::post().to(unsubscribe_subscriber_manually),
+                    )
+                    .route("/subscribers/delete", web::post().to(delete_subscriber))
+                    .route("/subscribers/tag", web::post().to(tag_subscriber))
+                    .route("/subscribers/untag", web::post().to(untag_subscriber))
+                    .route(
+                        "/subscribers/add_to_list",
+                        web::post().to(add_subscriber_to_list),
+                    )
+                    .route(
+                        "/subscribers/remove_from_list",
+                        web::post().to(remove_subscriber_from_list),
+                    )
+                    .route("/lists", web::get().to(manage_lists))
+                    .route("/lists", web::post().to(create_list))
+                    .route("/lists/delete", web::post().to(delete_list))
+                    .route("/privacy", web::get().to(data_requests_form))
+                    .route("/privacy/export", web::get().to(export_subscriber_data))
+                    .route("/privacy/erase", web::post().to(erase_subscriber_data))
                     .route("/settings", web::get().to(manage_settings_form))
-                    .route("/settings", web::post().to(change_key_state)),
+                    .route("/settings", web::post().to(change_key_state))
+                    .route("/failures", web::get().to(delivery_failures))
+                    .route("/failures/requeue", web::post().to(requeue_failure))
+                    .route(
+                        "/failures/requeue_all",
+                        web::post().to(requeue_all_failures),
+                    )
+                    .route("/tokens", web::get().to(api_tokens))
+                    .route("/tokens", web::post().to(create_token))
+                    .route("/tokens/revoke", web::post().to(revoke_token))
+                    .route("/totp", web::get().to(totp_settings))
+                    .route("/totp/enroll", web::post().to(enroll_totp))
+                    .route("/totp/confirm", web::post().to(confirm_totp))
+                    .route("/totp/disable", web::post().to(disable_two_factor))
+                    .route("/users", web::get().to(manage_users))
+                    .route("/users", web::post().to(invite_user))
+                    .route("/users/disable", web::post().to(disable_user))
+                    .route("/users/delete", web::post().to(delete_user)),
             )
             .app_data(connection_pool.clone())
             .app_data(email_client.clone())
+            .app_data(templates.clone())
+            .app_data(password_policy.clone())
+            .app_data(password_hashing.clone())
+            .app_data(idempotency.clone())
+            .app_data(subscriptions.clone())
+            .app_data(hmac_secret.clone())
+            .app_data(suppression_hash_key.clone())
             .app_data(base_url.clone())
+            .app_data(unsubscribe_links.clone())
+            .app_data(totp_cipher.clone())
+            .app_data(login_throttle.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(session_registry.clone())
     })
     .listen(listener)?
     .run();
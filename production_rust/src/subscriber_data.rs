@@ -0,0 +1,458 @@
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// How long a data request link emailed to a subscriber can be used for.
+pub const DATA_REQUEST_LINK_LIFETIME_HOURS: i64 = 24;
+
+/// Everything held about an email address, as handed out on an access request.
+#[derive(serde::Serialize)]
+pub struct PersonalData {
+    pub email: String,
+    pub exported_at: DateTime<Utc>,
+    pub subscriptions: Vec<SubscriptionRecord>,
+    pub subscription_tokens: Vec<SubscriptionTokenRecord>,
+    pub unsubscribe_tokens: Vec<String>,
+    pub lists: Vec<String>,
+    pub tags: Vec<String>,
+    pub queued_deliveries: Vec<QueuedDeliveryRecord>,
+    pub delivery_failures: Vec<DeliveryFailureRecord>,
+}
+
+impl PersonalData {
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+            && self.queued_deliveries.is_empty()
+            && self.delivery_failures.is_empty()
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriptionRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriptionTokenRecord {
+    pub subscription_token: String,
+    pub created_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+pub struct QueuedDeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct DeliveryFailureRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub n_retries: i32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Addresses are matched regardless of case.
+#[tracing::instrument(name = "Export personal data", skip(email, connection_pool))]
+pub async fn export_personal_data(
+    email: &str,
+    connection_pool: &PgPool,
+) -> Result<PersonalData, anyhow::Error> {
+    let subscriptions = sqlx::query_as!(
+        SubscriptionRecord,
+        r#"
+        SELECT id, email, name, status, subscribed_at FROM subscriptions
+        WHERE lower(email) = lower($1)
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the subscriptions of an address.")?;
+    let subscription_tokens = sqlx::query_as!(
+        SubscriptionTokenRecord,
+        r#"
+        SELECT t.subscription_token, t.created_at, t.consumed_at
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE lower(s.email) = lower($1)
+        ORDER BY t.created_at
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the confirmation tokens of an address.")?;
+    let unsubscribe_tokens = sqlx::query!(
+        r#"
+        SELECT t.unsubscribe_token
+        FROM unsubscribe_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE lower(s.email) = lower($1)
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the unsubscribe tokens of an address.")?
+    .into_iter()
+    .map(|r| r.unsubscribe_token)
+    .collect();
+    let lists = sqlx::query!(
+        r#"
+        SELECT l.name
+        FROM subscriber_lists l
+        JOIN subscriber_list_memberships m USING (list_id)
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE lower(s.email) = lower($1)
+        ORDER BY l.name
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the lists of an address.")?
+    .into_iter()
+    .map(|r| r.name)
+    .collect();
+    let tags = sqlx::query!(
+        r#"
+        SELECT t.tag
+        FROM subscriber_tags t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE lower(s.email) = lower($1)
+        ORDER BY t.tag
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the tags of an address.")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+    let queued_deliveries = sqlx::query_as!(
+        QueuedDeliveryRecord,
+        r#"
+        SELECT q.newsletter_issue_id, n.title
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE lower(q.subscriber_email) = lower($1)
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the queued deliveries of an address.")?;
+    let delivery_failures = sqlx::query_as!(
+        DeliveryFailureRecord,
+        r#"
+        SELECT f.newsletter_issue_id, n.title, f.n_retries, f.last_error, f.failed_at
+        FROM issue_delivery_failures f
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE lower(f.subscriber_email) = lower($1)
+        ORDER BY f.failed_at
+        "#,
+        email,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the delivery failures of an address.")?;
+
+    Ok(PersonalData {
+        email: email.to_string(),
+        exported_at: Utc::now(),
+        subscriptions,
+        subscription_tokens,
+        unsubscribe_tokens,
+        lists,
+        tags,
+        queued_deliveries,
+        delivery_failures,
+    })
+}
+
+/// Deletes everything held about `email` in a single transaction and
+/// records a keyed digest of the address, so that it is not imported again.
+/// Returns how many rows were deleted.
+#[tracing::instrument(
+    name = "Erase personal data",
+    skip(email, suppression_hash_key, connection_pool)
+)]
+pub async fn erase_personal_data(
+    email: &str,
+    suppression_hash_key: &Secret<String>,
+    connection_pool: &PgPool,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let mut n_deleted = 0;
+    n_deleted += sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens WHERE subscriber_id IN
+            (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the confirmation tokens of an address.")?
+    .rows_affected();
+    n_deleted += sqlx::query!(
+        r#"
+        DELETE FROM unsubscribe_tokens WHERE subscriber_id IN
+            (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the unsubscribe tokens of an address.")?
+    .rows_affected();
+    n_deleted += sqlx::query!(
+        "DELETE FROM subscriptions WHERE lower(email) = lower($1)",
+        email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the subscriptions of an address.")?
+    .rows_affected();
+    n_deleted += sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = lower($1)",
+        email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the queued deliveries of an address.")?
+    .rows_affected();
+    n_deleted += sqlx::query!(
+        "DELETE FROM issue_delivery_failures WHERE lower(subscriber_email) = lower($1)",
+        email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the delivery failures of an address.")?
+    .rows_affected();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_suppressions (email_hash, suppressed_at)
+        VALUES ($1, now())
+        ON CONFLICT (email_hash) DO NOTHING
+        "#,
+        suppression_hash(suppression_hash_key, email),
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to record the suppression of an address.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the erasure of personal data.")?;
+    Ok(n_deleted)
+}
+
+/// Whether the data of `email` was erased on request.
+/// Only imports honour it: an address subscribing again itself is let through.
+pub async fn is_suppressed(
+    email: &str,
+    suppression_hash_key: &Secret<String>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT 1 AS found FROM subscriber_suppressions WHERE email_hash = $1",
+        suppression_hash(suppression_hash_key, email),
+    )
+    .fetch_optional(transaction)
+    .await
+    .context("Failed to look up the suppression of an address.")?;
+    Ok(row.is_some())
+}
+
+fn suppression_hash(key: &Secret<String>, email: &str) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(email.trim().to_lowercase().as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// What a subscriber asked for by email.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DataRequest {
+    Export,
+    Erase,
+}
+
+impl DataRequest {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataRequest::Export => "export",
+            DataRequest::Erase => "erase",
+        }
+    }
+}
+
+impl TryFrom<&str> for DataRequest {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "export" => Ok(DataRequest::Export),
+            "erase" => Ok(DataRequest::Erase),
+            other => Err(format!("{} is not a known data request.", other)),
+        }
+    }
+}
+
+/// A signature over a request, an address and an expiry,
+/// proving that the link was emailed to that address.
+pub fn sign_data_request(
+    secret: &Secret<String>,
+    request: DataRequest,
+    email: &str,
+    expires_at: i64,
+) -> String {
+    URL_SAFE_NO_PAD.encode(
+        data_request_mac(secret, request, email, expires_at)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+pub fn verify_data_request(
+    secret: &Secret<String>,
+    request: DataRequest,
+    email: &str,
+    expires_at: i64,
+    signature: &str,
+) -> bool {
+    if expires_at < Utc::now().timestamp() {
+        return false;
+    }
+    let signature = match URL_SAFE_NO_PAD.decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    data_request_mac(secret, request, email, expires_at)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn data_request_mac(
+    secret: &Secret<String>,
+    request: DataRequest,
+    email: &str,
+    expires_at: i64,
+) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(format!("{}\n{}\n{}", request.as_str(), email, expires_at).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_data_request, suppression_hash, verify_data_request, DataRequest};
+    use chrono::Utc;
+    use secrecy::Secret;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-very-long-and-secret-key".to_string())
+    }
+
+    #[test]
+    fn signed_requests_are_verified() {
+        let expires_at = Utc::now().timestamp() + 60;
+        let signature = sign_data_request(&secret(), DataRequest::Erase, "a@b.com", expires_at);
+        assert!(verify_data_request(
+            &secret(),
+            DataRequest::Erase,
+            "a@b.com",
+            expires_at,
+            &signature
+        ));
+    }
+
+    #[test]
+    fn tampered_requests_are_rejected() {
+        let expires_at = Utc::now().timestamp() + 60;
+        let signature = sign_data_request(&secret(), DataRequest::Export, "a@b.com", expires_at);
+        let verify = |request, email, expires_at, signature: &str| {
+            verify_data_request(&secret(), request, email, expires_at, signature)
+        };
+        assert!(!verify(
+            DataRequest::Erase,
+            "a@b.com",
+            expires_at,
+            &signature
+        ));
+        assert!(!verify(
+            DataRequest::Export,
+            "c@d.com",
+            expires_at,
+            &signature
+        ));
+        assert!(!verify(
+            DataRequest::Export,
+            "a@b.com",
+            expires_at + 1,
+            &signature
+        ));
+        assert!(!verify(
+            DataRequest::Export,
+            "a@b.com",
+            expires_at,
+            "not base64!"
+        ));
+    }
+
+    #[test]
+    fn expired_requests_are_rejected() {
+        let expires_at = Utc::now().timestamp() - 1;
+        let signature = sign_data_request(&secret(), DataRequest::Export, "a@b.com", expires_at);
+        assert!(!verify_data_request(
+            &secret(),
+            DataRequest::Export,
+            "a@b.com",
+            expires_at,
+            &signature
+        ));
+    }
+
+    #[test]
+    fn suppressions_ignore_case_and_whitespace() {
+        assert_eq!(
+            suppression_hash(&secret(), " Ada@Example.com"),
+            suppression_hash(&secret(), "ada@example.com")
+        );
+        assert_ne!(
+            suppression_hash(&secret(), "ada@example.com"),
+            suppression_hash(&secret(), "bob@example.com")
+        );
+    }
+
+    #[test]
+    fn suppressions_depend_on_the_key() {
+        assert_ne!(
+            suppression_hash(&secret(), "ada@example.com"),
+            suppression_hash(&Secret::new("another key".to_string()), "ada@example.com")
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// The longest tag accepted, in characters.
+pub const MAX_TAG_LENGTH: usize = 64;
+
+/// A named list subscribers can join, a subscriber can be on many lists.
+pub struct SubscriberList {
+    pub list_id: Uuid,
+    pub name: String,
+    pub n_members: i64,
+}
+
+#[tracing::instrument(name = "Get subscriber lists", skip(connection_pool))]
+pub async fn get_subscriber_lists(
+    connection_pool: &PgPool,
+) -> Result<Vec<SubscriberList>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        SubscriberList,
+        r#"
+        SELECT l.list_id, l.name, count(m.subscriber_id) AS "n_members!"
+        FROM subscriber_lists l
+        LEFT JOIN subscriber_list_memberships m USING (list_id)
+        GROUP BY l.list_id
+        ORDER BY l.name
+        "#,
+    )
+    .fetch_all(connection_pool)
+    .await
+    .context("Failed to retrieve the subscriber lists.")?;
+    Ok(lists)
+}
+
+/// `false` if a list with the same name already exists.
+#[tracing::instrument(name = "Create a subscriber list", skip(connection_pool))]
+pub async fn create_subscriber_list(
+    name: &str,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_created = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_lists (list_id, name, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        name,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to create a subscriber list.")?
+    .rows_affected();
+    Ok(n_created > 0)
+}
+
+pub enum ListDeletion {
+    Deleted,
+    NotFound,
+    /// Unsent issues are aimed at the list, deleting it would send them to everyone.
+    InUse,
+}
+
+/// Memberships go with the list. Lists are only deleted once every issue aimed
+/// at them has been sent, sent issues then no longer name a list.
+#[tracing::instrument(name = "Delete a subscriber list", skip(connection_pool))]
+pub async fn delete_subscriber_list(
+    list_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<ListDeletion, anyhow::Error> {
+    let mut transaction = connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // locked, an issue cannot be aimed at the list until it is gone
+    let list = sqlx::query!(
+        "SELECT list_id FROM subscriber_lists WHERE list_id = $1 FOR UPDATE",
+        list_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to lock a subscriber list.")?;
+    if list.is_none() {
+        return Ok(ListDeletion::NotFound);
+    }
+    let n_unsent = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!" FROM newsletter_issues
+        WHERE list_id = $1 AND status <> 'sent'
+        "#,
+        list_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to count the unsent issues aimed at a subscriber list.")?
+    .count;
+    if n_unsent > 0 {
+        return Ok(ListDeletion::InUse);
+    }
+    sqlx::query!("DELETE FROM subscriber_lists WHERE list_id = $1", list_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete a subscriber list.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion of a subscriber list.")?;
+    Ok(ListDeletion::Deleted)
+}
+
+/// `false` if the subscriber or the list does not exist, or they already are a member.
+#[tracing::instrument(name = "Add a subscriber to a list", skip(executor))]
+pub async fn add_to_list(
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    executor: impl PgExecutor<'_>,
+) -> Result<bool, anyhow::Error> {
+    let n_added = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_list_memberships (list_id, subscriber_id)
+        SELECT l.list_id, s.id
+        FROM subscriber_lists l, subscriptions s
+        WHERE l.list_id = $1 AND s.id = $2
+        ON CONFLICT DO NOTHING
+        "#,
+        list_id,
+        subscriber_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to add a subscriber to a list.")?
+    .rows_affected();
+    Ok(n_added > 0)
+}
+
+#[tracing::instrument(name = "Remove a subscriber from a list", skip(connection_pool))]
+pub async fn remove_from_list(
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_removed = sqlx::query!(
+        r#"
+        DELETE FROM subscriber_list_memberships
+        WHERE list_id = $1 AND subscriber_id = $2
+        "#,
+        list_id,
+        subscriber_id,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to remove a subscriber from a list.")?
+    .rows_affected();
+    Ok(n_removed > 0)
+}
+
+/// `false` if the address is not subscribed or the list does not exist.
+#[tracing::instrument(name = "Join a subscriber list", skip(connection_pool))]
+pub async fn join_list(
+    email: &str,
+    list_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_joined = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_list_memberships (list_id, subscriber_id)
+        SELECT l.list_id, s.id
+        FROM subscriber_lists l, subscriptions s
+        WHERE l.list_id = $1 AND s.email = $2
+        ON CONFLICT DO NOTHING
+        "#,
+        list_id,
+        email,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to add a subscriber to a list.")?
+    .rows_affected();
+    Ok(n_joined > 0)
+}
+
+pub async fn add_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[String],
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $1, tag FROM unnest($2::text[]) AS tag
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        tags,
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to tag a subscriber.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Remove a tag from a subscriber", skip(connection_pool))]
+pub async fn remove_tag(
+    subscriber_id: Uuid,
+    tag: &str,
+    connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let n_removed = sqlx::query!(
+        "DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag = $2",
+        subscriber_id,
+        tag,
+    )
+    .execute(connection_pool)
+    .await
+    .context("Failed to remove a tag from a subscriber.")?
+    .rows_affected();
+    Ok(n_removed > 0)
+}
+
+/// Tags are matched regardless of case and surrounding whitespace.
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err("A tag cannot be empty.".into());
+    }
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!(
+            "A tag must be at most {} characters long.",
+            MAX_TAG_LENGTH
+        ));
+    }
+    Ok(tag)
+}
+
+/// Who a newsletter issue goes to, among the confirmed subscribers.
+/// Every rule left empty lets everyone through.
+#[derive(Debug, Default, PartialEq)]
+pub struct Audience {
+    pub list_id: Option<Uuid>,
+    pub signed_up_from: Option<NaiveDate>,
+    pub signed_up_to: Option<NaiveDate>,
+    pub tag: Option<String>,
+    pub email_domain: Option<String>,
+}
+
+impl Audience {
+    /// Parses the fields of the newsletter form, empty ones are left out.
+    pub fn parse(
+        list_id: Option<&str>,
+        signed_up_from: Option<&str>,
+        signed_up_to: Option<&str>,
+        tag: Option<&str>,
+        email_domain: Option<&str>,
+    ) -> Result<Self, String> {
+        fn non_empty(value: Option<&str>) -> Option<&str> {
+            value.map(str::trim).filter(|v| !v.is_empty())
+        }
+        let parse_date = |date: Option<&str>| {
+            non_empty(date)
+                .map(|d| {
+                    NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                        .map_err(|_| format!("{} is not a valid date.", d))
+                })
+                .transpose()
+        };
+        let audience = Self {
+            list_id: non_empty(list_id)
+                .map(|id| Uuid::parse_str(id).map_err(|_| format!("{} is not a valid list.", id)))
+                .transpose()?,
+            signed_up_from: parse_date(signed_up_from)?,
+            signed_up_to: parse_date(signed_up_to)?,
+            tag: non_empty(tag).map(parse_tag).transpose()?,
+            email_domain: non_empty(email_domain)
+                .map(|domain| domain.trim_start_matches('@').to_lowercase()),
+        };
+        if let (Some(from), Some(to)) = (audience.signed_up_from, audience.signed_up_to) {
+            if from > to {
+                return Err("The signup date range ends before it starts.".into());
+            }
+        }
+        Ok(audience)
+    }
+}
+
+#[tracing::instrument(name = "Save the audience of an issue", skip(transaction))]
+pub async fn save_audience(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    audience: &Audience,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            list_id = $2,
+            segment_signed_up_from = $3,
+            segment_signed_up_to = $4,
+            segment_tag = $5,
+            segment_email_domain = $6
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        audience.list_id,
+        audience.signed_up_from,
+        audience.signed_up_to,
+        audience.tag,
+        audience.email_domain,
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to save the audience of a newsletter issue.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_tag, Audience};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn tags_are_normalised() {
+        assert_eq!(parse_tag("  Rust "), Ok("rust".to_string()));
+        assert!(parse_tag("   ").is_err());
+        assert!(parse_tag(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn empty_fields_target_everyone() {
+        let audience = Audience::parse(Some(""), Some(" "), None, Some(""), Some("")).unwrap();
+        assert_eq!(audience, Audience::default());
+    }
+
+    #[test]
+    fn segment_rules_are_parsed() {
+        let audience = Audience::parse(
+            None,
+            Some("2026-01-01"),
+            Some("2026-02-01"),
+            Some("Beta"),
+            Some("@Example.COM"),
+        )
+        .unwrap();
+        assert_eq!(audience.signed_up_from, NaiveDate::from_ymd_opt(2026, 1, 1));
+        assert_eq!(audience.tag.as_deref(), Some("beta"));
+        assert_eq!(audience.email_domain.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(Audience::parse(Some("not-a-uuid"), None, None, None, None).is_err());
+        assert!(Audience::parse(None, Some("yesterday"), None, None, None).is_err());
+        assert!(Audience::parse(None, Some("2026-02-01"), Some("2026-01-01"), None, None).is_err());
+    }
+}
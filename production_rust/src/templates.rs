@@ -0,0 +1,228 @@
+use anyhow::Context;
+use std::path::Path;
+
+/// Merge fields available in the body of a newsletter issue.
+pub const ISSUE_FIELDS: &[&str] = &["name", "email", "unsubscribe_url"];
+/// Merge fields available in the layout wrapped around every issue.
+pub const LAYOUT_FIELDS: &[&str] = &["content", "title", "name", "email", "unsubscribe_url"];
+/// Merge fields available in the confirmation email.
+pub const CONFIRMATION_FIELDS: &[&str] = &["name", "confirmation_link"];
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("The merge field starting at character {0} is never closed.")]
+    Unclosed(usize),
+    #[error("`{{{{{0}}}}}` is not a known merge field.")]
+    UnknownField(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+/// A text with `{{field}}` placeholders, checked against the fields
+/// it is allowed to use when it is parsed rather than when it is rendered.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str, fields: &[&str]) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            let position = source.len() - rest.len() + start;
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| TemplateError::Unclosed(source[..position].chars().count()))?;
+            let field = rest[start + 2..start + end].trim();
+            if !fields.contains(&field) {
+                return Err(TemplateError::UnknownField(field.to_string()));
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            parts.push(Part::Field(field.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    // fields without a value render as an empty string
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => output.push_str(literal),
+                Part::Field(field) => {
+                    if let Some((_, value)) = values.iter().find(|(name, _)| name == field) {
+                        output.push_str(value);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+/// The per-recipient values of the merge fields.
+pub struct MergeFields<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub unsubscribe_url: &'a str,
+}
+
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub html: String,
+    pub text: String,
+}
+
+pub struct Templates {
+    layout_html: Template,
+    layout_text: Template,
+    confirmation_html: Template,
+    confirmation_text: Template,
+}
+
+impl Templates {
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let directory = directory.as_ref();
+        let load = |file: &str, fields: &[&str]| -> Result<Template, anyhow::Error> {
+            let path = directory.join(file);
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read template {}", path.display()))?;
+            Template::parse(&source, fields)
+                .with_context(|| format!("Invalid template {}", path.display()))
+        };
+
+        Ok(Self {
+            layout_html: load("layout.html", LAYOUT_FIELDS)?,
+            layout_text: load("layout.txt", LAYOUT_FIELDS)?,
+            confirmation_html: load("confirmation.html", CONFIRMATION_FIELDS)?,
+            confirmation_text: load("confirmation.txt", CONFIRMATION_FIELDS)?,
+        })
+    }
+
+    /// Resolves the merge fields of an issue for a single recipient
+    /// and wraps the result in the layout.
+    pub fn render_issue(
+        &self,
+        title: &str,
+        html_content: &str,
+        text_content: &str,
+        fields: &MergeFields,
+    ) -> Result<RenderedEmail, TemplateError> {
+        let html_values = [
+            ("name", htmlescape::encode_minimal(fields.name)),
+            ("email", htmlescape::encode_minimal(fields.email)),
+            (
+                "unsubscribe_url",
+                htmlescape::encode_minimal(fields.unsubscribe_url),
+            ),
+            ("title", htmlescape::encode_minimal(title)),
+        ];
+        let html_values: Vec<(&str, &str)> = html_values
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        let text_values = [
+            ("name", fields.name),
+            ("email", fields.email),
+            ("unsubscribe_url", fields.unsubscribe_url),
+            ("title", title),
+        ];
+
+        let html_content = Template::parse(html_content, ISSUE_FIELDS)?.render(&html_values);
+        let text_content = Template::parse(text_content, ISSUE_FIELDS)?.render(&text_values);
+
+        Ok(RenderedEmail {
+            html: self
+                .layout_html
+                .render(&[&html_values[..], &[("content", &html_content)]].concat()),
+            text: self
+                .layout_text
+                .render(&[&text_values[..], &[("content", &text_content)]].concat()),
+        })
+    }
+
+    pub fn render_confirmation(&self, name: &str, confirmation_link: &str) -> RenderedEmail {
+        RenderedEmail {
+            html: self.confirmation_html.render(&[
+                ("name", &htmlescape::encode_minimal(name)),
+                (
+                    "confirmation_link",
+                    &htmlescape::encode_minimal(confirmation_link),
+                ),
+            ]),
+            text: self
+                .confirmation_text
+                .render(&[("name", name), ("confirmation_link", confirmation_link)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Template, TemplateError, Templates, ISSUE_FIELDS};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn merge_fields_are_replaced() {
+        let template = Template::parse("Hi {{ name }}, you are {{email}}.", ISSUE_FIELDS).unwrap();
+        let output = template.render(&[("name", "Ursula"), ("email", "ursula@example.com")]);
+        assert_eq!(output, "Hi Ursula, you are ursula@example.com.");
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let outcome = Template::parse("Hi {{nmae}}", ISSUE_FIELDS);
+        assert_eq!(
+            outcome.unwrap_err(),
+            TemplateError::UnknownField("nmae".into())
+        );
+    }
+
+    #[test]
+    fn unclosed_fields_are_rejected() {
+        let outcome = Template::parse("Hi {{name", ISSUE_FIELDS);
+        assert_eq!(outcome.unwrap_err(), TemplateError::Unclosed(3));
+    }
+
+    #[test]
+    fn text_without_fields_is_left_untouched() {
+        let template = assert_ok!(Template::parse("Hi } there }}", ISSUE_FIELDS));
+        assert_eq!(template.render(&[]), "Hi } there }}");
+    }
+
+    #[test]
+    fn bundled_templates_are_valid() {
+        let templates = assert_ok!(Templates::load("configuration/templates"));
+        assert_err!(templates.render_issue("Title", "{{oops}}", "", &fields()));
+    }
+
+    #[test]
+    fn html_values_are_escaped_but_text_values_are_not() {
+        let templates = Templates::load("configuration/templates").unwrap();
+        let email = templates
+            .render_issue("Title", "<p>Hi {{name}}</p>", "Hi {{name}}", &fields())
+            .unwrap();
+        assert!(email.html.contains("<p>Hi Tom &amp; Jerry</p>"));
+        assert!(email.text.contains("Hi Tom & Jerry"));
+        assert!(email.text.contains("https://example.com/unsubscribe"));
+    }
+
+    fn fields() -> super::MergeFields<'static> {
+        super::MergeFields {
+            name: "Tom & Jerry",
+            email: "tom@example.com",
+            unsubscribe_url: "https://example.com/unsubscribe",
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use actix_web::http::header::LOCATION;
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL, LOCATION};
 use actix_web::HttpResponse;
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 pub fn e400<T>(e: T) -> actix_web::Error
 where
@@ -20,3 +21,19 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish()
 }
+
+/// For pages showing a secret that must not be seen again,
+/// neither from a cache nor from a saved idempotent response.
+pub fn no_store(mut response: HttpResponse) -> HttpResponse {
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+// `datetime-local` inputs carry no offset, their value is read as UTC
+pub fn parse_datetime_local(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .map(|naive| DateTime::<Utc>::from_utc(naive, Utc))
+}
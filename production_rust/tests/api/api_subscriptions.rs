@@ -0,0 +1,259 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn post_with_idempotency_key(
+    app: &TestApp,
+    idempotency_key: &str,
+    body: &serde_json::Value,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/api/v1/subscriptions", &app.address))
+        .header("Idempotency-Key", idempotency_key)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn valid_subscription_returns_the_pending_subscriber() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "Aeonid Thiel",
+            "email": "calth_invigilatus@gmail.com"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["email"], "calth_invigilatus@gmail.com");
+    assert_eq!(body["name"], "Aeonid Thiel");
+    assert_eq!(body["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribing_twice_returns_409() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "name": "Aeonid Thiel",
+        "email": "calth_invigilatus@gmail.com"
+    });
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_api_subscriptions(&body).await;
+    let response = app.post_api_subscriptions(&body).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "already_subscribed");
+}
+
+#[tokio::test]
+async fn validation_errors_have_a_machine_readable_body() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "Aeonid Thiel",
+            "email": "invalid-email"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "validation_error");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("invalid-email"));
+}
+
+#[tokio::test]
+async fn malformed_payloads_are_rejected_with_400() {
+    let app = spawn_app().await;
+
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "Aeonid Thiel"}),
+            "missing the email",
+        ),
+        (
+            serde_json::json!({"email": "calth_invigilatus@gmail.com"}),
+            "missing the name",
+        ),
+        (serde_json::json!("not an object"), "not an object"),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        let response = app.post_api_subscriptions(&invalid_body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400: {}",
+            error_message
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_payload");
+    }
+}
+
+#[tokio::test]
+async fn valid_subscriptions_are_persisted() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_api_subscriptions(&serde_json::json!({
+        "name": "Aeonid Thiel",
+        "email": "calth_invigilatus@gmail.com"
+    }))
+    .await;
+
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "calth_invigilatus@gmail.com");
+    assert_eq!(saved.name, "Aeonid Thiel");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn empty_fields_are_rejected_with_400() {
+    let app = spawn_app().await;
+
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "", "email": "raptor_imperialis@gmail.com"}),
+            "empty name",
+        ),
+        (
+            serde_json::json!({"name": "Aeonid", "email": ""}),
+            "empty email",
+        ),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        let response = app.post_api_subscriptions(&invalid_body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400: {}",
+            error_message
+        );
+    }
+}
+
+#[tokio::test]
+async fn database_errors_return_a_json_500() {
+    let app = spawn_app().await;
+
+    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "Aeonid Thiel",
+            "email": "calth_invigilata@gmail.com"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "unexpected_error");
+}
+
+#[tokio::test]
+async fn subscriptions_replayed_with_the_same_key_get_the_saved_response() {
+    let app = spawn_app().await;
+    let key = Uuid::new_v4().to_string();
+    let body = serde_json::json!({
+        "name": "Aeonid Thiel",
+        "email": "calth_invigilatus@gmail.com"
+    });
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response_1 = post_with_idempotency_key(&app, &key, &body).await;
+    let response_2 = post_with_idempotency_key(&app, &key, &body).await;
+
+    // without the key the second request would be a 409
+    assert_eq!(response_1.status().as_u16(), 201);
+    assert_eq!(response_2.status().as_u16(), 201);
+    assert_eq!(
+        response_1.text().await.unwrap(),
+        response_2.text().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn a_subscription_key_reused_with_another_payload_is_rejected() {
+    let app = spawn_app().await;
+    let key = Uuid::new_v4().to_string();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    post_with_idempotency_key(
+        &app,
+        &key,
+        &serde_json::json!({
+            "name": "Aeonid Thiel",
+            "email": "calth_invigilatus@gmail.com"
+        }),
+    )
+    .await;
+    let response = post_with_idempotency_key(
+        &app,
+        &key,
+        &serde_json::json!({
+            "name": "Ursula Le Guin",
+            "email": "ursula_le_guin@gmail.com"
+        }),
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "idempotency_key_reused");
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 1);
+}
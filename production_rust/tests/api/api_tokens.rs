@@ -0,0 +1,170 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_api_token, create_confirmed_subscriber, spawn_app,
+};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn new_tokens_are_shown_once_and_listed() {
+    let app = spawn_app().await;
+
+    let token = create_api_token(&app, &["subscribers:read"]).await;
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<td>CI</td>"));
+    assert!(html_page.contains("<td>subscribers:read</td>"));
+    assert!(!html_page.contains(&token));
+    let token_hash = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .token_hash;
+    assert!(!token_hash.contains(token.split_once('.').unwrap().1));
+}
+
+#[tokio::test]
+async fn tokens_need_a_scope() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_create_api_token(&[("name", "CI")]).await;
+    assert_is_redirect_to(&response, "/admin/tokens");
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token needs at least one scope.</i></p>"));
+}
+
+#[tokio::test]
+async fn bearer_tokens_can_list_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = create_api_token(&app, &["subscribers:read"]).await;
+
+    let response = app
+        .bearer_client(&token)
+        .get(format!("{}/api/v1/subscribers", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["subscribers"].as_array().unwrap().len(), 1);
+    assert_eq!(body["subscribers"][0]["status"], "confirmed");
+    let last_used_at = sqlx::query!("SELECT last_used_at FROM api_tokens")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .last_used_at;
+    assert!(last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn listing_subscribers_fails_with_a_json_500_on_database_errors() {
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["subscribers:read"]).await;
+    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .bearer_client(&token)
+        .get(format!("{}/api/v1/subscribers", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 500);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "unexpected_error");
+}
+
+#[tokio::test]
+async fn bearer_tokens_can_publish_issues() {
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["issues:write"]).await;
+
+    let response = app
+        .bearer_client(&token)
+        .post(format!("{}/admin/newsletter", &app.address))
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let status = sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "publishing");
+}
+
+#[tokio::test]
+async fn the_api_rejects_requests_without_a_token() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/api/v1/subscribers", &app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "missing_token");
+}
+
+#[tokio::test]
+async fn tokens_are_limited_to_their_scopes() {
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["issues:read"]).await;
+    let client = app.bearer_client(&token);
+
+    for path in ["/api/v1/subscribers", "/admin/tokens", "/admin/password"] {
+        let response = client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 403, "{} was let through", path);
+    }
+
+    let response = client
+        .get(format!("{}/admin/drafts", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn revoked_and_unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+    let token = create_api_token(&app, &["subscribers:read"]).await;
+    let token_id = token.split_once('.').unwrap().0.to_string();
+
+    let response = app
+        .post_revoke_api_token(&serde_json::json!({ "token_id": token_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token has been revoked.</i></p>"));
+
+    let forged_token = format!("{}.forged", token_id);
+    for token in [token.as_str(), forged_token.as_str(), "garbage"] {
+        let response = app
+            .bearer_client(token)
+            .get(format!("{}/api/v1/subscribers", &app.address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_token");
+    }
+}
@@ -119,3 +119,55 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let too_long = "x7#Q".repeat(33);
+    let with_username = format!("{}{}", app.test_user.username, app.test_user.username);
+
+    let cases = [
+        (
+            "tiny",
+            "The new password must be at least 12 characters long.",
+        ),
+        (
+            too_long.as_str(),
+            "The new password must be at most 128 characters long.",
+        ),
+        (
+            app.test_user.password.as_str(),
+            "The new password must be different from the current one.",
+        ),
+        (
+            "1q2w3e4r5t6y",
+            "This password has appeared in a data breach, please choose another one.",
+        ),
+        (
+            "passwordpassword",
+            "The new password is too easy to guess, try a longer phrase of unrelated words.",
+        ),
+        (
+            with_username.as_str(),
+            "The new password is too easy to guess, try a longer phrase of unrelated words.",
+        ),
+    ];
+    for (new_password, error_message) in cases {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": new_password,
+                "new_password_check": new_password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(&format!("<p><i>{}</i></p>", error_message)),
+            "{}",
+            new_password
+        );
+    }
+}
@@ -1,14 +1,23 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
 use once_cell::sync::Lazy;
-use production_rust::configuration::{get_configuration, DatabaseSettings};
+use production_rust::authentication::{base32_decode, time_step, totp_code};
+use production_rust::configuration::{
+    get_configuration, DatabaseSettings, IssueDeliverySettings, Settings,
+};
 use production_rust::email_client::EmailClient;
 use production_rust::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use production_rust::routes::UnsubscribeLinks;
 use production_rust::startup::{get_connection_pool, Application};
 use production_rust::telemetry::{get_subscriber, init_subscriber};
+use production_rust::templates::Templates;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
@@ -36,12 +45,15 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub issue_delivery: IssueDeliverySettings,
+    pub templates: Templates,
+    pub unsubscribe_links: UnsubscribeLinks,
 }
 
 impl TestApp {
     pub async fn get_subscribe(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/subscriptions", &self.address))
+            .get(format!("{}/subscriptions", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -53,7 +65,7 @@ impl TestApp {
 
     pub async fn post_subscribers(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -61,12 +73,30 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_confirmation(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/confirm/resend", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_subscriptions(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/v1/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
@@ -75,7 +105,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -86,7 +116,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -98,7 +128,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -109,7 +139,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -122,15 +152,89 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_email(
+        &self,
+        email: &str,
+        current_password: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/password/email", &self.address))
+            .form(&serde_json::json!({
+                "email": email,
+                "current_password": current_password,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_password_reset_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/password_reset", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_password_reset(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/password_reset", &self.address))
+            .form(&serde_json::json!({ "username": username }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_reset_password_form(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/password_reset/confirm", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password_reset/confirm", &self.address))
+            .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    /// Waits for an email sent outside of the request that triggered it.
+    pub async fn wait_for_email(&self) -> wiremock::Request {
+        self.wait_for_nth_email(1).await
+    }
+
+    /// Like `wait_for_email`, when `n - 1` emails were sent before.
+    pub async fn wait_for_nth_email(&self, n: usize) -> wiremock::Request {
+        for _ in 0..50 {
+            let mut requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= n {
+                return requests.swap_remove(n - 1);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("Only {} emails were sent.", n - 1);
+    }
+
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletter", &self.address))
+            .get(format!("{}/admin/newsletter", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -145,16 +249,259 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletter", &self.address))
+            .post(format!("{}/admin/newsletter", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_drafts(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/drafts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_drafts_html(&self) -> String {
+        self.get_drafts().await.text().await.unwrap()
+    }
+
+    pub async fn get_issue_preview_html(&self, newsletter_issue_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/issues/preview?newsletter_issue_id={}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_test_send<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/issues/test_send", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_scheduled_issues(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_scheduled_issues_html(&self) -> String {
+        self.get_scheduled_issues().await.text().await.unwrap()
+    }
+
+    pub async fn post_reschedule_issue<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/issues/reschedule", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_issue<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/issues/cancel", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_delivery_failures_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/failures", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_requeue_failure<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/failures/requeue", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_html(&self, query: &str) -> String {
+        self.get_subscribers(query).await.text().await.unwrap()
+    }
+
+    pub async fn post_import_subscribers(
+        &self,
+        csv: &str,
+        initial_status: &str,
+    ) -> reqwest::Response {
+        self.post_import_subscribers_to_list(csv, initial_status, "")
+            .await
+    }
+
+    pub async fn post_import_subscribers_to_list(
+        &self,
+        csv: &str,
+        initial_status: &str,
+        list_id: &str,
+    ) -> reqwest::Response {
+        let boundary = "subscriber-import-boundary";
+        let body = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"initial_status\"\r\n\r\n\
+            {initial_status}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"list_id\"\r\n\r\n\
+            {list_id}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"csv\"; filename=\"subscribers.csv\"\r\n\
+            Content-Type: text/csv\r\n\r\n\
+            {csv}\r\n\
+            --{boundary}--\r\n"
+        );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscriber_action(
+        &self,
+        action: &str,
+        subscriber_id: Uuid,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers/{}", &self.address, action))
+            .form(&serde_json::json!({ "subscriber_id": subscriber_id }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscriber_form(
+        &self,
+        action: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers/{}", &self.address, action))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_lists_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_list(&self, name: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(&serde_json::json!({ "name": name }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_list(&self, list_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists/delete", &self.address))
+            .form(&serde_json::json!({ "list_id": list_id }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_data_export(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/privacy/export", &self.address))
+            .query(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_data_erasure(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/privacy/erase", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_data_request(&self, email: &str, request: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/privacy", &self.address))
+            .form(&[("email", email), ("request", request)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_manage_settings(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/settings", &self.address))
+            .get(format!("{}/admin/settings", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -169,13 +516,212 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/settings", &self.address))
+            .post(format!("{}/admin/settings", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/tokens", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/tokens/revoke", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_manage_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_manage_users_html(&self) -> String {
+        self.get_manage_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users/disable", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users/delete", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_totp_settings_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/totp/confirm", &self.address))
+            .form(&[("code", code)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/totp/disable", &self.address))
+            .form(&[("code", code)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_totp(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/login/totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_login_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/totp", &self.address))
+            .form(&[("code", code)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke", &self.address))
+            .form(&[("session_id", session_id)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke_others", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // the test user logging in a second time, from another browser
+    pub async fn second_session(&self, user_agent: &str) -> reqwest::Client {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .user_agent(user_agent)
+            .build()
+            .unwrap();
+        client
+            .post(format!("{}/login", &self.address))
+            .form(&serde_json::json!({
+                "username": &self.test_user.username,
+                "password": &self.test_user.password,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        client
+    }
+
+    // a client without the session cookie, so the token is all it has to go on
+    pub fn bearer_client(&self, token: &str) -> reqwest::Client {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .default_headers(headers)
+            .build()
+            .unwrap()
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -192,8 +738,8 @@ impl TestApp {
             confirmation_link
         };
 
-        let html_link = get_link(&email_body["HtmlBody"].as_str().unwrap());
-        let text_link = get_link(&email_body["TextBody"].as_str().unwrap());
+        let html_link = get_link(email_body["HtmlBody"].as_str().unwrap());
+        let text_link = get_link(email_body["TextBody"].as_str().unwrap());
         ConfirmationLinks {
             html_link,
             text_link,
@@ -203,10 +749,15 @@ impl TestApp {
     #[allow(dead_code)]
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.pg_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.pg_pool,
+                &self.email_client,
+                &self.templates,
+                &self.unsubscribe_links,
+                &self.issue_delivery,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -251,8 +802,8 @@ impl TestUser {
         dbg!(&password_hash);
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, 'owner')",
             self.user_id,
             self.username,
             password_hash,
@@ -264,6 +815,10 @@ impl TestUser {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| ()).await
+}
+
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -273,6 +828,17 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // every test logs in from 127.0.0.1 and Redis outlives a test run
+        c.login_throttle.max_failures_per_ip = u32::MAX;
+        // and the same addresses come up again and again
+        for limit in [
+            &mut c.rate_limits.password_reset,
+            &mut c.rate_limits.confirmation_resend,
+        ] {
+            limit.max_requests = u32::MAX;
+            limit.max_requests_per_ip = u32::MAX;
+        }
+        configure(&mut c);
         c
     };
 
@@ -284,7 +850,7 @@ pub async fn spawn_app() -> TestApp {
     let application_port = application.port();
     let address = format!("http://localhost:{}", application_port);
 
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
@@ -292,6 +858,10 @@ pub async fn spawn_app() -> TestApp {
         .build()
         .unwrap();
 
+    let unsubscribe_links = UnsubscribeLinks::new(
+        address.clone(),
+        config.application.unsubscribe_signing_key.clone(),
+    );
     let test_app = TestApp {
         address,
         pg_pool: get_connection_pool(&config.database),
@@ -299,7 +869,13 @@ pub async fn spawn_app() -> TestApp {
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: config.email_client.client(),
+        email_client: config
+            .email_client
+            .client()
+            .expect("Invalid email client settings."),
+        issue_delivery: config.issue_delivery,
+        templates: Templates::load(&config.templates.directory).unwrap(),
+        unsubscribe_links,
     };
 
     test_app.test_user.store(&test_app.pg_pool).await;
@@ -328,6 +904,125 @@ async fn test_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email,
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscribers(body).await.error_for_status().unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Invites a collaborator as the test user, leaving the test user logged in,
+/// and returns the id and credentials of the new user.
+pub async fn invite_user(app: &TestApp, role: &str) -> (Uuid, serde_json::Value) {
+    app.test_user.login(app).await;
+    let username = Uuid::new_v4().to_string();
+    let html_page = app
+        .post_invite_user(&serde_json::json!({ "username": username, "role": role }))
+        .await
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let start = html_page.find("<code>").unwrap() + "<code>".len();
+    let end = html_page.find("</code>").unwrap();
+    let user_id = sqlx::query!("SELECT user_id FROM users WHERE username = $1", username)
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .user_id;
+    let credentials = serde_json::json!({
+        "username": username,
+        "password": &html_page[start..end],
+    });
+    (user_id, credentials)
+}
+
+/// Turns TOTP on for the test user, leaving them logged in,
+/// and returns the shared secret along with the recovery codes.
+pub async fn enable_totp(app: &TestApp) -> (Vec<u8>, Vec<String>) {
+    app.test_user.login(app).await;
+    app.post_enroll_totp().await;
+    let html_page = app.get_totp_settings_html().await;
+    let start =
+        html_page.find(r#"<code id="totp-secret">"#).unwrap() + r#"<code id="totp-secret">"#.len();
+    let end = start + html_page[start..].find("</code>").unwrap();
+    let secret = base32_decode(&html_page[start..end]).unwrap();
+
+    let html_page = app
+        .post_confirm_totp(&totp_code_at(&secret, 0))
+        .await
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s[..s.find("</code>").unwrap()].to_string())
+        .collect();
+    (secret, recovery_codes)
+}
+
+/// The code of the time step `offset` steps away from now.
+pub fn totp_code_at(secret: &[u8], offset: i64) -> String {
+    let step = time_step(chrono::Utc::now().timestamp() as u64) as i64 + offset;
+    totp_code(secret, step as u64)
+}
+
+/// Creates a token for the test user through the admin page
+/// and returns its plain-text value.
+pub async fn create_api_token(app: &TestApp, scopes: &[&str]) -> String {
+    app.test_user.login(app).await;
+    let mut body = vec![("name", "CI")];
+    body.extend(scopes.iter().map(|scope| ("scope", *scope)));
+    let html_page = app
+        .post_create_api_token(&body)
+        .await
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let start = html_page.find("<code>").unwrap() + "<code>".len();
+    let end = html_page.find("</code>").unwrap();
+    html_page[start..end].to_string()
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location)
@@ -0,0 +1,198 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use uuid::Uuid;
+
+async fn post_create_token(
+    app: &TestApp,
+    idempotency_key: Option<&str>,
+    name: &str,
+) -> reqwest::Response {
+    let mut request = app
+        .api_client
+        .post(format!("{}/admin/tokens", &app.address))
+        .form(&[("name", name), ("scope", "issues:read")]);
+    if let Some(key) = idempotency_key {
+        request = request.header("Idempotency-Key", key);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+async fn post_create_list(app: &TestApp, idempotency_key: &str, name: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/lists", &app.address))
+        .header("Idempotency-Key", idempotency_key)
+        .form(&[("name", name)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn count_lists(app: &TestApp) -> i64 {
+    sqlx::query!("SELECT COUNT(*) as \"count!\" FROM subscriber_lists")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+async fn count_tokens(app: &TestApp) -> i64 {
+    sqlx::query!("SELECT COUNT(*) as \"count!\" FROM api_tokens")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn requests_replayed_with_the_same_key_get_the_saved_response() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+
+    let response_1 = post_create_list(&app, &key, "Product updates").await;
+    let response_2 = post_create_list(&app, &key, "Product updates").await;
+
+    assert_is_redirect_to(&response_1, "/admin/lists");
+    assert_is_redirect_to(&response_2, "/admin/lists");
+    // running it again would have complained about the name being taken
+    let html_page = app.get_lists_html().await;
+    assert!(!html_page.contains("A list with this name already exists."));
+    assert_eq!(count_lists(&app).await, 1);
+}
+
+#[tokio::test]
+async fn a_key_reused_with_another_payload_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+
+    post_create_list(&app, &key, "Product updates").await;
+    let response = post_create_list(&app, &key, "Release notes").await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(count_lists(&app).await, 1);
+}
+
+#[tokio::test]
+async fn responses_revealing_secrets_are_not_saved() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+
+    let response_1 = post_create_token(&app, Some(&key), "CI").await;
+    assert_eq!(response_1.headers()["Cache-Control"], "no-store");
+    let response_2 = post_create_token(&app, Some(&key), "CI").await;
+
+    // the retry is run again, its token is not the one shown the first time
+    assert_ne!(
+        response_1.text().await.unwrap(),
+        response_2.text().await.unwrap()
+    );
+    assert_eq!(count_tokens(&app).await, 2);
+    let n_saved = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM idempotency")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_saved, 0);
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/users", &app.address))
+        .header("Idempotency-Key", Uuid::new_v4().to_string())
+        .form(&[("username", "ada"), ("role", "editor")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+}
+
+#[tokio::test]
+async fn requests_without_a_key_are_not_deduplicated() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    post_create_token(&app, None, "CI").await;
+    post_create_token(&app, None, "CI").await;
+
+    assert_eq!(count_tokens(&app).await, 2);
+}
+
+#[tokio::test]
+async fn newsletter_keys_cannot_be_reused_for_another_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+    let body = |title: &str| {
+        serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": key,
+            "action": "save_draft",
+        })
+    };
+
+    app.post_publish_newsletter(&body("First title")).await;
+    let response = app.post_publish_newsletter(&body("Second title")).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    let n_issues = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn a_retry_gets_a_409_while_the_first_request_is_in_flight_for_too_long() {
+    let app = spawn_app_with(|c| c.idempotency.in_flight_timeout_milliseconds = 200).await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+
+    // stands in for a first request that is still being processed
+    let mut in_flight = app.pg_pool.begin().await.unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, now())
+        "#,
+        app.test_user.user_id,
+        key,
+    )
+    .execute(&mut in_flight)
+    .await
+    .unwrap();
+
+    let response = post_create_token(&app, Some(&key), "CI").await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    in_flight.rollback().await.unwrap();
+    let response = post_create_token(&app, Some(&key), "CI").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count_tokens(&app).await, 1);
+}
+
+#[tokio::test]
+async fn a_key_saved_without_a_response_is_reported_in_progress() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let key = Uuid::new_v4().to_string();
+    sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, now())
+        "#,
+        app.test_user.user_id,
+        key,
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+
+    let response = post_create_token(&app, Some(&key), "CI").await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(count_tokens(&app).await, 0);
+}
@@ -0,0 +1,170 @@
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+use chrono::Utc;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn publish_newsletter(app: &TestApp) {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried_later() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let task = sqlx::query!("SELECT n_retries, execute_after FROM issue_delivery_queue")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("The delivery task should still be queued.");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.execute_after > Utc::now());
+
+    let n_failures = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_failures")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_failures, 0);
+}
+
+#[tokio::test]
+async fn exhausted_retries_are_dead_lettered() {
+    let mut app = spawn_app().await;
+    app.issue_delivery.max_attempts = 1;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let n_queued = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+
+    let failure = sqlx::query!("SELECT n_retries, last_error FROM issue_delivery_failures")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("The delivery should have been dead-lettered.");
+    assert_eq!(failure.n_retries, 0);
+    assert!(failure.last_error.contains("500"));
+}
+
+#[tokio::test]
+async fn permanent_failures_are_not_retried() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let failure = sqlx::query!("SELECT last_error FROM issue_delivery_failures")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("The delivery should have been dead-lettered.");
+    assert!(failure.last_error.contains("422"));
+}
+
+#[tokio::test]
+async fn failed_deliveries_can_be_requeued() {
+    let mut app = spawn_app().await;
+    app.issue_delivery.max_attempts = 1;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let failure =
+        sqlx::query!("SELECT newsletter_issue_id, subscriber_email FROM issue_delivery_failures")
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap();
+
+    let html_page = app.get_delivery_failures_html().await;
+    assert!(html_page.contains(&failure.subscriber_email));
+
+    let response = app
+        .post_requeue_failure(&serde_json::json!({
+            "newsletter_issue_id": failure.newsletter_issue_id,
+            "subscriber_email": failure.subscriber_email,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/failures");
+
+    let html_page = app.get_delivery_failures_html().await;
+    assert!(html_page.contains("The delivery has been requeued."));
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let n_failures = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_failures")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_failures, 0);
+}
+
+#[tokio::test]
+async fn login_required_to_see_failed_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/failures", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
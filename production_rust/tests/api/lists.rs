@@ -0,0 +1,357 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn insert_subscriber(app: &TestApp, email: &str, subscribed_at: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+        VALUES ($1, $2, $3, 'confirmed', $4::text::timestamptz)
+        "#,
+        subscriber_id,
+        email,
+        email.split('@').next().unwrap(),
+        subscribed_at,
+    )
+    .execute(&app.pg_pool)
+    .await
+    .expect("Failed to insert a subscriber.");
+    subscriber_id
+}
+
+async fn create_list(app: &TestApp, name: &str) -> Uuid {
+    let response = app.post_create_list(name).await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    sqlx::query!("SELECT list_id FROM subscriber_lists WHERE name = $1", name)
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to fetch the created list.")
+        .list_id
+}
+
+async fn list_members(app: &TestApp, list_id: Uuid) -> Vec<String> {
+    sqlx::query!(
+        r#"
+        SELECT s.email
+        FROM subscriber_list_memberships m
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE m.list_id = $1
+        ORDER BY s.email
+        "#,
+        list_id
+    )
+    .fetch_all(&app.pg_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.email)
+    .collect()
+}
+
+async fn subscriber_tags(app: &TestApp, subscriber_id: Uuid) -> Vec<String> {
+    sqlx::query!(
+        "SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag",
+        subscriber_id
+    )
+    .fetch_all(&app.pg_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.tag)
+    .collect()
+}
+
+// who the deliveries of the single published issue were queued for
+async fn queued_recipients(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue ORDER BY subscriber_email")
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.subscriber_email)
+        .collect()
+}
+
+fn newsletter_body(audience: serde_json::Value) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    body.as_object_mut()
+        .unwrap()
+        .extend(audience.as_object().unwrap().clone());
+    body
+}
+
+#[tokio::test]
+async fn lists_can_be_created_and_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let list_id = create_list(&app, "Product updates").await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>The list has been created.</i></p>"));
+    assert!(html_page.contains("Product updates"));
+
+    app.post_create_list("Product updates").await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>A list with this name already exists.</i></p>"));
+
+    let response = app.post_delete_list(list_id).await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>The list has been deleted.</i></p>"));
+    assert!(html_page.contains("No subscriber lists."));
+}
+
+#[tokio::test]
+async fn subscribing_with_a_list_joins_it() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains(&format!(
+        r#"<option value="{}">Product updates</option>"#,
+        list_id
+    )));
+
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={}",
+        list_id
+    );
+    let response = app.post_subscribers(body).await;
+    assert_is_redirect_to(&response, "/subscriptions");
+
+    assert_eq!(
+        list_members(&app, list_id).await,
+        vec!["ursula_le_guin@gmail.com"]
+    );
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_cannot_be_added_to_a_list_from_the_subscribe_form() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+    insert_subscriber(&app, "ursula_le_guin@gmail.com", "2026-01-01T00:00:00Z").await;
+
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&list_id={}",
+        list_id
+    );
+    app.post_subscribers(body).await;
+
+    assert!(list_members(&app, list_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn newsletters_sent_to_a_list_only_reach_its_members() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+    let member = insert_subscriber(&app, "member@example.com", "2026-01-01T00:00:00Z").await;
+    insert_subscriber(&app, "outsider@example.com", "2026-01-01T00:00:00Z").await;
+    app.post_subscriber_form(
+        "add_to_list",
+        &serde_json::json!({ "subscriber_id": member, "list_id": list_id }),
+    )
+    .await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_body(
+            serde_json::json!({ "list_id": list_id.to_string() }),
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    assert_eq!(queued_recipients(&app).await, vec!["member@example.com"]);
+}
+
+#[tokio::test]
+async fn segment_rules_are_combined() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let tagged = insert_subscriber(&app, "match@example.com", "2026-03-15T12:00:00Z").await;
+    let too_early = insert_subscriber(&app, "early@example.com", "2026-01-15T12:00:00Z").await;
+    let other_domain = insert_subscriber(&app, "match@other.org", "2026-03-15T12:00:00Z").await;
+    insert_subscriber(&app, "untagged@example.com", "2026-03-15T12:00:00Z").await;
+    for subscriber_id in [tagged, too_early, other_domain] {
+        app.post_subscriber_form(
+            "tag",
+            &serde_json::json!({ "subscriber_id": subscriber_id, "tag": "Beta" }),
+        )
+        .await;
+    }
+
+    let response = app
+        .post_publish_newsletter(&newsletter_body(serde_json::json!({
+            "segment_signed_up_from": "2026-03-01",
+            "segment_signed_up_to": "2026-03-15",
+            "segment_tag": "beta",
+            "segment_email_domain": "@EXAMPLE.com",
+        })))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    assert_eq!(queued_recipients(&app).await, vec!["match@example.com"]);
+}
+
+#[tokio::test]
+async fn reversed_signup_ranges_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "match@example.com", "2026-03-15T12:00:00Z").await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_body(serde_json::json!({
+            "segment_signed_up_from": "2026-03-15",
+            "segment_signed_up_to": "2026-03-01",
+        })))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("The signup date range ends before it starts."));
+    assert!(queued_recipients(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn drafts_keep_their_audience() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+
+    let mut body = newsletter_body(serde_json::json!({
+        "list_id": list_id.to_string(),
+        "segment_tag": "beta",
+        "segment_email_domain": "example.com",
+    }));
+    body["action"] = "save_draft".into();
+    app.post_publish_newsletter(&body).await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletter?newsletter_issue_id={}",
+            &app.address, newsletter_issue_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!(r#"<option value="{}" selected>"#, list_id)));
+    assert!(html_page.contains(r#"name="segment_tag" value="beta""#));
+    assert!(html_page
+        .contains(r#"name="segment_email_domain" placeholder="example.com" value="example.com""#));
+}
+
+#[tokio::test]
+async fn lists_with_unsent_issues_cannot_be_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+    let mut body = newsletter_body(serde_json::json!({ "list_id": list_id.to_string() }));
+    body["action"] = "save_draft".into();
+    app.post_publish_newsletter(&body).await;
+
+    let response = app.post_delete_list(list_id).await;
+
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains(
+        "Issues that have not been sent yet are aimed at this list, it cannot be deleted."
+    ));
+    let issue = sqlx::query!("SELECT list_id FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.list_id, Some(list_id));
+
+    // once sent, the issue no longer holds the list back
+    sqlx::query!("UPDATE newsletter_issues SET status = 'sent'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    app.post_delete_list(list_id).await;
+    assert!(app
+        .get_lists_html()
+        .await
+        .contains("<p><i>The list has been deleted.</i></p>"));
+}
+
+#[tokio::test]
+async fn tags_can_be_added_and_removed_from_the_console() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "alice@example.com", "2026-01-01T00:00:00Z").await;
+
+    let response = app
+        .post_subscriber_form(
+            "tag",
+            &serde_json::json!({ "subscriber_id": subscriber_id, "tag": " VIP " }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(subscriber_tags(&app, subscriber_id).await, vec!["vip"]);
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains(r#"name="tag" value="vip""#));
+
+    app.post_subscriber_form(
+        "tag",
+        &serde_json::json!({ "subscriber_id": subscriber_id, "tag": "  " }),
+    )
+    .await;
+    let html_page = app.get_subscribers_html("").await;
+    assert!(html_page.contains("<p><i>A tag cannot be empty.</i></p>"));
+
+    app.post_subscriber_form(
+        "untag",
+        &serde_json::json!({ "subscriber_id": subscriber_id, "tag": "vip" }),
+    )
+    .await;
+    assert!(subscriber_tags(&app, subscriber_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn imported_subscribers_get_their_tags_and_the_chosen_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list_id = create_list(&app, "Product updates").await;
+
+    let csv = "email,name,tags\n\
+        alice@example.com,Alice,Beta; VIP\n\
+        bob@example.com,Bob,\n";
+    let response = app
+        .post_import_subscribers_to_list(csv, "confirmed", &list_id.to_string())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        list_members(&app, list_id).await,
+        vec!["alice@example.com", "bob@example.com"]
+    );
+    let alice = sqlx::query!("SELECT id FROM subscriptions WHERE email = 'alice@example.com'")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .id;
+    assert_eq!(subscriber_tags(&app, alice).await, vec!["beta", "vip"]);
+}
@@ -1,11 +1,12 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn failure_sends_error_flash_message() {
     let app = spawn_app().await;
 
     let login_body = serde_json::json!({
-        "username": "random-username",
+        "username": Uuid::new_v4().to_string(),
         "password": "random-password",
     });
     let response = app.post_login(&login_body).await;
@@ -34,3 +35,58 @@ async fn success_redirect_to_admin_dashboard() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+async fn stored_password_hash(app: &TestApp) -> String {
+    sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.pg_pool)
+    .await
+    .unwrap()
+    .password_hash
+}
+
+#[tokio::test]
+async fn weaker_password_hashes_are_upgraded_on_login() {
+    let app = spawn_app_with(|c| c.password_hashing.iterations = 3).await;
+    let previous_hash = stored_password_hash(&app).await;
+    assert!(previous_hash.contains("t=2"));
+
+    app.test_user.login(&app).await;
+
+    let upgraded_hash = stored_password_hash(&app).await;
+    assert!(upgraded_hash.contains("$m=15000,t=3,p=1$"));
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn password_hashes_are_not_upgraded_by_failed_logins() {
+    let app = spawn_app_with(|c| c.password_hashing.iterations = 3).await;
+    let previous_hash = stored_password_hash(&app).await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": Uuid::new_v4().to_string(),
+    }))
+    .await;
+
+    assert_eq!(stored_password_hash(&app).await, previous_hash);
+}
+
+#[tokio::test]
+async fn stronger_password_hashes_are_left_alone() {
+    let app = spawn_app_with(|c| c.password_hashing.iterations = 1).await;
+    let previous_hash = stored_password_hash(&app).await;
+
+    app.test_user.login(&app).await;
+
+    assert_eq!(stored_password_hash(&app).await, previous_hash);
+}
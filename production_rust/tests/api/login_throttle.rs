@@ -0,0 +1,226 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use rand::Rng;
+use std::net::{IpAddr, Ipv4Addr};
+use uuid::Uuid;
+
+fn wrong_password(app: &TestApp) -> serde_json::Value {
+    serde_json::json!({
+        "username": &app.test_user.username,
+        "password": Uuid::new_v4().to_string(),
+    })
+}
+
+fn right_password(app: &TestApp) -> serde_json::Value {
+    serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    })
+}
+
+async fn audit_actions(app: &TestApp, username: &str) -> Vec<String> {
+    sqlx::query!(
+        "SELECT action FROM audit_log WHERE username = $1 ORDER BY occurred_at",
+        username,
+    )
+    .fetch_all(&app.pg_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.action)
+    .collect()
+}
+
+#[tokio::test]
+async fn failures_beyond_the_free_attempts_are_delayed() {
+    let app = spawn_app().await;
+
+    for _ in 0..2 {
+        let response = app.post_login(&wrong_password(&app)).await;
+        assert_is_redirect_to(&response, "/login");
+        assert!(app.get_login_html().await.contains("Authentication failed"));
+    }
+
+    let response = app.post_login(&wrong_password(&app)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts, try again in 1 seconds."));
+}
+
+#[tokio::test]
+async fn the_right_password_is_turned_away_while_delayed() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        app.post_login(&wrong_password(&app)).await;
+    }
+
+    let response = app.post_login(&right_password(&app)).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Too many failed login attempts"));
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let response = app.post_login(&right_password(&app)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_successful_login_forgets_previous_failures() {
+    let app = spawn_app().await;
+    for _ in 0..2 {
+        app.post_login(&wrong_password(&app)).await;
+    }
+    app.post_login(&right_password(&app)).await;
+    app.post_logout().await;
+
+    for _ in 0..2 {
+        app.post_login(&wrong_password(&app)).await;
+        assert!(app.get_login_html().await.contains("Authentication failed"));
+    }
+}
+
+#[tokio::test]
+async fn accounts_are_locked_out_and_audited_after_too_many_failures() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.free_attempts = 10;
+        c.login_throttle.max_failures = 3;
+    })
+    .await;
+
+    for _ in 0..3 {
+        app.post_login(&wrong_password(&app)).await;
+    }
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Too many failed login attempts, try again in 900 seconds."));
+
+    let response = app.post_login(&right_password(&app)).await;
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(
+        audit_actions(&app, &app.test_user.username).await,
+        vec!["account_locked"]
+    );
+}
+
+#[tokio::test]
+async fn addresses_are_locked_out_across_accounts() {
+    let app = spawn_app_with(|c| c.login_throttle.max_failures_per_ip = 3).await;
+    // a fresh loopback address, so that earlier runs do not count
+    let mut rng = rand::thread_rng();
+    let address = Ipv4Addr::new(127, rng.gen_range(1..255), rng.gen(), rng.gen_range(1..255));
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .local_address(IpAddr::V4(address))
+        .build()
+        .unwrap();
+    let login_url = format!("http://127.0.0.1:{}/login", app.port);
+
+    let mut last_username = String::new();
+    for _ in 0..3 {
+        last_username = Uuid::new_v4().to_string();
+        let body = serde_json::json!({
+            "username": &last_username,
+            "password": "wrong-password",
+        });
+        client.post(&login_url).form(&body).send().await.unwrap();
+    }
+
+    let response = client
+        .post(&login_url)
+        .form(&right_password(&app))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(
+        audit_actions(&app, &last_username).await,
+        vec!["address_locked"]
+    );
+
+    // other addresses are not affected
+    let response = app.post_login(&right_password(&app)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+fn random_address() -> String {
+    let mut rng = rand::thread_rng();
+    Ipv4Addr::new(10, rng.gen(), rng.gen(), rng.gen_range(1..255)).to_string()
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_ignored_by_default() {
+    let app = spawn_app_with(|c| c.login_throttle.max_failures_per_ip = 3).await;
+    // a fresh loopback address, so that earlier runs do not count
+    let mut rng = rand::thread_rng();
+    let address = Ipv4Addr::new(127, rng.gen_range(1..255), rng.gen(), rng.gen_range(1..255));
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .local_address(IpAddr::V4(address))
+        .build()
+        .unwrap();
+    let login_url = format!("http://127.0.0.1:{}/login", app.port);
+
+    let mut last_username = String::new();
+    for _ in 0..3 {
+        last_username = Uuid::new_v4().to_string();
+        let body = serde_json::json!({
+            "username": &last_username,
+            "password": "wrong-password",
+        });
+        // a new forwarded address every time does not get around the lockout
+        client
+            .post(&login_url)
+            .header("X-Forwarded-For", random_address())
+            .form(&body)
+            .send()
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        audit_actions(&app, &last_username).await,
+        vec!["address_locked"]
+    );
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_locked_out_behind_a_trusted_proxy() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.max_failures_per_ip = 3;
+        c.application.trust_proxy_headers = true;
+    })
+    .await;
+    let locked_address = random_address();
+
+    let mut last_username = String::new();
+    for _ in 0..3 {
+        last_username = Uuid::new_v4().to_string();
+        app.api_client
+            .post(format!("{}/login", &app.address))
+            .header("X-Forwarded-For", &locked_address)
+            .form(&serde_json::json!({
+                "username": &last_username,
+                "password": "wrong-password",
+            }))
+            .send()
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        audit_actions(&app, &last_username).await,
+        vec!["address_locked"]
+    );
+
+    // everyone else behind the same proxy can still log in
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", random_address())
+        .form(&right_password(&app))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,23 @@
 mod admin_dashboard;
+mod api_subscriptions;
+mod api_tokens;
 mod change_password;
 mod health_check;
 mod helpers;
+mod idempotency;
+mod issue_delivery;
+mod lists;
 mod login;
+mod login_throttle;
 mod newsletter;
+mod newsletter_drafts;
+mod password_reset;
+mod privacy;
+mod scheduled_issues;
+mod sessions;
+mod subscribers;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod totp;
+mod users;
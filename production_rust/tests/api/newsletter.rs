@@ -1,54 +1,14 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+    TestApp,
+};
 use chrono::Utc;
-use fake::faker::internet::en::SafeEmail;
-use fake::faker::name::en::Name;
-use fake::Fake;
+use production_rust::idempotency::delete_expired_keys;
 use std::time::Duration;
 use uuid::Uuid;
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
-        "name": name,
-        "email": email,
-    }))
-    .unwrap();
-
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-    app.post_subscribers(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    let email_request = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-
-    app.get_confirmation_links(&email_request)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_link.html_link)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
 #[tokio::test]
 async fn newsletters_unavailable_for_unconfirmed_subscribers() {
     let app = spawn_app().await;
@@ -115,6 +75,40 @@ async fn newsletters_available_for_confirmed_subscribers() {
     app.dispatch_all_pending_emails().await;
 }
 
+#[tokio::test]
+async fn issues_are_only_sent_once_their_deliveries_are_done() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_eq!(issue_status(&app).await, "publishing");
+
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(issue_status(&app).await, "sent");
+}
+
+async fn issue_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .status
+}
+
 #[tokio::test]
 async fn login_required_to_see_newsletter_form() {
     let app = spawn_app().await;
@@ -141,6 +135,43 @@ async fn login_required_to_publish_newsletter() {
     assert_is_redirect_to(&response, "/login");
 }
 
+#[tokio::test]
+async fn delivered_issues_carry_each_body_in_its_own_field() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(html_body.contains("<p>Newsletter body as HTML</p>"));
+    assert!(text_body.contains("Newsletter body as plain text"));
+    assert!(!text_body.contains("<p>"));
+}
+
 #[tokio::test]
 async fn newsletter_creation_is_idempotent() {
     let app = spawn_app().await;
@@ -219,6 +250,79 @@ async fn graceful_handling_concurrent_form_submission() {
     app.dispatch_all_pending_emails().await;
 }
 
+async fn count_issues(app: &TestApp) -> i64 {
+    sqlx::query!("SELECT COUNT(*) as \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn expired_idempotency_keys_are_processed_again() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "action": "save_draft",
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_eq!(count_issues(&app).await, 1);
+
+    sqlx::query!("UPDATE idempotency SET created_at = now() - interval '2 days'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    assert!(response.status().is_redirection());
+    assert_eq!(count_issues(&app).await, 2);
+}
+
+#[tokio::test]
+async fn only_expired_idempotency_keys_are_cleaned_up() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..2 {
+        app.post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "action": "save_draft",
+        }))
+        .await;
+    }
+    sqlx::query!(
+        r#"
+        UPDATE idempotency SET created_at = now() - interval '2 days'
+        WHERE idempotency_key = (SELECT MIN(idempotency_key) FROM idempotency)
+        "#
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+
+    let n_deleted = delete_expired_keys(&app.pg_pool, Duration::from_secs(24 * 60 * 60))
+        .await
+        .unwrap();
+
+    assert_eq!(n_deleted, 1);
+    let n_remaining = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM idempotency")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_remaining, 1);
+}
+
 // #[tokio::test]
 // async fn idempotency_expiration_prevents_queries() {
 //     let app = spawn_app().await;
@@ -357,13 +461,10 @@ async fn rejected_key_prevents_action() {
     let response = app.post_publish_newsletter(&newsletter_request_body).await;
     assert_is_redirect_to(&response, "/admin/newsletter");
 
-    assert_eq!(
-        false,
-        html_page.contains(
-            "<p><i>The newsletter issue has been accepted -> \
+    assert!(!html_page.contains(
+        "<p><i>The newsletter issue has been accepted -> \
         emails will be delivered shortly.</i></p>"
-        )
-    );
+    ));
 }
 
 #[tokio::test]
@@ -390,3 +491,151 @@ async fn invalid_key_prevents_action() {
     let html_page = app.get_manage_settings_html().await;
     assert!(html_page.contains("The idempotency key cannot be empty!"));
 }
+
+#[tokio::test]
+async fn markdown_renders_both_bodies() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "markdown_content": "# News\n\nRead [the post](https://example.com/post).",
+        "text_content": "",
+        "html_content": "",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    assert!(body["HtmlBody"].as_str().unwrap().contains(
+        "<h1>News</h1>\n<p>Read <a href=\"https://example.com/post\" rel=\"noopener noreferrer\">the post</a>.</p>\n"
+    ));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("News\n====\n\nRead the post[1].\n\n[1] https://example.com/post\n"));
+}
+
+#[tokio::test]
+async fn explicit_bodies_override_rendered_markdown() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "markdown_content": "# News",
+        "text_content": "",
+        "html_content": "<p>Hand-written HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("<p>Hand-written HTML</p>"));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("News\n====\n"));
+}
+
+#[tokio::test]
+async fn merge_fields_are_resolved_per_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hi {{name}}, this is for {{ email }}.",
+        "html_content": "<p>Hi {{name}}</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    app.dispatch_all_pending_emails().await;
+
+    let subscriber = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.contains(&format!(
+        "Hi {}, this is for {}.",
+        subscriber.name, subscriber.email
+    )));
+    assert!(text_body.contains("/subscriptions/unsubscribe?unsubscribe_token="));
+    assert!(body["HtmlBody"].as_str().unwrap().contains(&format!(
+        "<p>Hi {}</p>",
+        htmlescape::encode_minimal(&subscriber.name)
+    )));
+}
+
+#[tokio::test]
+async fn newsletters_with_broken_templates_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hi {{first_name}}",
+        "html_content": "<p>Hi {{name</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue could not be saved"));
+
+    let n_issues = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+    app.dispatch_all_pending_emails().await;
+}
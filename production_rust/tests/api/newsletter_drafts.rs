@@ -0,0 +1,159 @@
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn save_draft(app: &TestApp) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Draft title",
+            "text_content": "Draft body as plain text",
+            "html_content": "<p>Draft body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "action": "save_draft",
+        }))
+        .await;
+
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    assert_is_redirect_to(
+        &response,
+        &format!(
+            "/admin/issues/preview?newsletter_issue_id={}",
+            newsletter_issue_id
+        ),
+    );
+    newsletter_issue_id
+}
+
+#[tokio::test]
+async fn drafts_are_not_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = save_draft(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_preview_html(newsletter_issue_id).await;
+    assert!(html_page.contains("<p><i>The draft has been saved.</i></p>"));
+    assert!(html_page.contains("Status: draft"));
+
+    let html_page = app.get_drafts_html().await;
+    assert!(html_page.contains("Draft title"));
+}
+
+#[tokio::test]
+async fn preview_renders_both_html_and_text_content() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let newsletter_issue_id = save_draft(&app).await;
+
+    let html_page = app.get_issue_preview_html(newsletter_issue_id).await;
+    assert!(html_page.contains(&format!(
+        r#"srcdoc="{}""#,
+        htmlescape::encode_attribute("<p>Draft body as HTML</p>")
+    )));
+    assert!(html_page.contains("<pre>Draft body as plain text</pre>"));
+}
+
+#[tokio::test]
+async fn test_copies_only_go_to_the_given_address() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = save_draft(&app).await;
+    let response = app
+        .post_test_send(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+            "email": "editor@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!(
+            "/admin/issues/preview?newsletter_issue_id={}",
+            newsletter_issue_id
+        ),
+    );
+
+    let html_page = app.get_issue_preview_html(newsletter_issue_id).await;
+    assert!(html_page.contains("A test copy has been sent to editor@example.com."));
+
+    app.dispatch_all_pending_emails().await;
+
+    // the first request is the subscriber's confirmation email
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let email_request = email_requests.last().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "editor@example.com");
+    assert_eq!(body["Subject"], "[Test] Draft title");
+}
+
+#[tokio::test]
+async fn publishing_a_draft_delivers_it() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = save_draft(&app).await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Final title",
+            "text_content": "Final body as plain text",
+            "html_content": "<p>Final body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "newsletter_issue_id": newsletter_issue_id,
+            "action": "publish",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    app.dispatch_all_pending_emails().await;
+
+    let issue = sqlx::query!(
+        "SELECT title, status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id,
+    )
+    .fetch_one(&app.pg_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.title, "Final title");
+    assert_eq!(issue.status, "sent");
+
+    let html_page = app.get_drafts_html().await;
+    assert!(!html_page.contains("Final title"));
+}
+
+#[tokio::test]
+async fn login_required_to_see_drafts() {
+    let app = spawn_app().await;
+
+    let response = app.get_drafts().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,277 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use production_rust::authentication::create_password_reset_token;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const NEW_PASSWORD: &str = "kH8#tw2!Pq9@zR4x";
+
+async fn store_recovery_email(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+}
+
+async fn reset_token(app: &TestApp) -> String {
+    store_recovery_email(app).await;
+    create_password_reset_token(&app.test_user.username, &app.pg_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .token
+        .expose_secret()
+        .clone()
+}
+
+fn reset_body(token: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "new_password": NEW_PASSWORD,
+        "new_password_check": NEW_PASSWORD,
+    })
+}
+
+#[tokio::test]
+async fn the_login_form_links_to_password_resets() {
+    let app = spawn_app().await;
+    assert!(app
+        .get_login_html()
+        .await
+        .contains(r#"href="/password_reset""#));
+}
+
+#[tokio::test]
+async fn users_can_set_their_recovery_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_recovery_email("admin@example.com", &app.test_user.password)
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("Your recovery email has been updated."));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+
+    app.post_recovery_email("not-an-email", &app.test_user.password)
+        .await;
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("That is not a valid email address."));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+}
+
+#[tokio::test]
+async fn changing_the_recovery_email_requires_the_current_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_recovery_email("attacker@example.com", &Uuid::new_v4().to_string())
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("The current password is incorrect."));
+    assert!(!html_page.contains("attacker@example.com"));
+}
+
+#[tokio::test]
+async fn a_reset_link_is_emailed_and_sets_a_new_password() {
+    let app = spawn_app().await;
+    store_recovery_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_password_reset(&app.test_user.username).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("If that account has a recovery email, a reset link has been sent to it."));
+
+    let email_request = app.wait_for_email().await;
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "admin@example.com");
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html_link, links.text_link);
+    let token = links
+        .html_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .to_string();
+
+    let response = app.get_reset_password_form(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(&token));
+
+    let response = app.post_reset_password(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Your password has been reset, you can now log in."));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn unknown_usernames_get_the_same_answer_and_no_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_password_reset(&Uuid::new_v4().to_string()).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("If that account has a recovery email, a reset link has been sent to it."));
+
+    // users without a recovery email cannot be reset either
+    app.post_password_reset(&app.test_user.username).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
+
+#[tokio::test]
+async fn reset_tokens_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let token = reset_token(&app).await;
+
+    app.post_reset_password(&reset_body(&token)).await;
+
+    let response = app.post_reset_password(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/password_reset");
+    assert!(app
+        .get_password_reset_html()
+        .await
+        .contains("This reset link is invalid or has expired."));
+    let response = app.get_reset_password_form(&token).await;
+    assert_is_redirect_to(&response, "/password_reset");
+}
+
+#[tokio::test]
+async fn expired_reset_tokens_are_rejected() {
+    let app = spawn_app().await;
+    let token = reset_token(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app.post_reset_password(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/password_reset");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn mismatched_passwords_keep_the_token_usable() {
+    let app = spawn_app().await;
+    let token = reset_token(&app).await;
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": "something-else",
+        }))
+        .await;
+    assert_eq!(
+        response.headers().get("Location").unwrap(),
+        &format!("/password_reset/confirm?token={}", token)
+    );
+
+    let response = app.post_reset_password(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn reset_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    let token = reset_token(&app).await;
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": "qwerty",
+            "new_password_check": "qwerty",
+        }))
+        .await;
+    assert_eq!(
+        response.headers().get("Location").unwrap(),
+        &format!("/password_reset/confirm?token={}", token)
+    );
+    let html_page = app
+        .get_reset_password_form(&token)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("The new password must be at least 12 characters long."));
+}
+
+#[tokio::test]
+async fn a_reset_logs_out_every_session_of_the_user() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+
+    let token = reset_token(&app).await;
+    app.post_reset_password(&reset_body(&token)).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn reset_requests_are_throttled_without_locking_the_account() {
+    let app = spawn_app_with(|c| c.rate_limits.password_reset.max_requests = 3).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = app.post_password_reset(&app.test_user.username).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+    let response = app.post_password_reset(&app.test_user.username).await;
+    assert_is_redirect_to(&response, "/password_reset");
+    assert!(app
+        .get_password_reset_html()
+        .await
+        .contains("Too many reset requests, please try again later."));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
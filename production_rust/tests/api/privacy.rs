@@ -0,0 +1,247 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const EMAIL: &str = "calth_invigilatus@gmail.com";
+
+/// A confirmed subscriber with a newsletter delivery still queued
+/// and an earlier one that failed.
+async fn subscriber_with_history(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscribers("name=Aeonid%20Thiel&email=calth_invigilatus%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_link.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    app.test_user.login(app).await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures
+            (newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at)
+        SELECT newsletter_issue_id, upper(subscriber_email), 3, 'Mailbox full', now()
+        FROM issue_delivery_queue
+        "#
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+}
+
+async fn count_rows(app: &TestApp) -> Vec<i64> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens) AS "subscription_tokens!",
+            (SELECT count(*) FROM unsubscribe_tokens) AS "unsubscribe_tokens!",
+            (SELECT count(*) FROM issue_delivery_queue) AS "queued!",
+            (SELECT count(*) FROM issue_delivery_failures) AS "failures!"
+        "#
+    )
+    .fetch_one(&app.pg_pool)
+    .await
+    .unwrap();
+    vec![
+        counts.subscriptions,
+        counts.subscription_tokens,
+        counts.unsubscribe_tokens,
+        counts.queued,
+        counts.failures,
+    ]
+}
+
+#[tokio::test]
+async fn everything_held_about_an_address_is_exported() {
+    let app = spawn_app().await;
+    subscriber_with_history(&app).await;
+
+    let response = app
+        .get_admin_data_export("Calth_Invigilatus@gmail.com")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .contains("personal-data.json"));
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscriptions"][0]["email"], EMAIL);
+    assert_eq!(data["subscriptions"][0]["name"], "Aeonid Thiel");
+    assert_eq!(data["subscriptions"][0]["status"], "confirmed");
+    assert_eq!(data["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert!(!data["subscription_tokens"][0]["consumed_at"].is_null());
+    assert_eq!(data["unsubscribe_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(data["queued_deliveries"][0]["title"], "Newsletter title");
+    assert_eq!(data["delivery_failures"][0]["last_error"], "Mailbox full");
+}
+
+#[tokio::test]
+async fn erasure_removes_everything_and_blocks_reimports() {
+    let app = spawn_app().await;
+    subscriber_with_history(&app).await;
+    assert_eq!(count_rows(&app).await, vec![1, 1, 1, 1, 1]);
+
+    let response = app.post_admin_data_erasure(EMAIL).await;
+
+    assert_is_redirect_to(&response, "/admin/privacy");
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/privacy", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        html_page.contains("5 records have been erased, the address will not be imported again.")
+    );
+    assert_eq!(count_rows(&app).await, vec![0, 0, 0, 0, 0]);
+    let email_hash = sqlx::query!("SELECT email_hash FROM subscriber_suppressions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .email_hash;
+    assert!(!email_hash.contains("calth"));
+
+    let response = app
+        .post_import_subscribers(
+            "email,name\nCALTH_INVIGILATUS@gmail.com,Aeonid Thiel\nada@example.com,Ada",
+            "confirmed",
+        )
+        .await;
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("1 subscribers imported as confirmed"));
+    assert!(html_page.contains("1 addresses were skipped"));
+}
+
+#[tokio::test]
+async fn subscribers_can_export_their_data_from_an_emailed_link() {
+    let app = spawn_app().await;
+    subscriber_with_history(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_data_request(EMAIL, "export").await;
+
+    assert_is_redirect_to(&response, "/subscriptions/privacy");
+    // the first one confirmed the subscription
+    let email_request = app.wait_for_nth_email(2).await;
+    let link = app.get_confirmation_links(&email_request).text_link;
+    assert_eq!(link.path(), "/subscriptions/privacy/export");
+    let data: serde_json::Value = reqwest::get(link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(data["subscriptions"][0]["email"], EMAIL);
+
+    // the link is bound to its address
+    let mut forged = link;
+    let query = forged
+        .query()
+        .unwrap()
+        .replace("calth_invigilatus", "someone_else");
+    forged.set_query(Some(&query));
+    let response = reqwest::get(forged).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscribers_can_erase_their_data_after_confirming() {
+    let app = spawn_app().await;
+    subscriber_with_history(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_data_request(EMAIL, "erase").await;
+    // the first one confirmed the subscription
+    let email_request = app.wait_for_nth_email(2).await;
+    let link = app.get_confirmation_links(&email_request).text_link;
+
+    // following the link only shows a confirmation form
+    let html_page = reqwest::get(link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"action="/subscriptions/privacy/erase""#));
+    assert_eq!(count_rows(&app).await[0], 1);
+
+    // an export link cannot be turned into an erasure
+    let export_link = link.as_str().replace("/erase?", "/export?");
+    let response = reqwest::get(export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let query: Vec<(String, String)> = link.query_pairs().into_owned().collect();
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/privacy/erase", &app.address))
+        .form(&query)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count_rows(&app).await, vec![0, 0, 0, 0, 0]);
+}
+
+#[tokio::test]
+async fn requests_for_unknown_addresses_send_nothing() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_data_request("nobody@example.com", "erase").await;
+
+    assert_is_redirect_to(&response, "/subscriptions/privacy");
+    let html_page = app
+        .api_client
+        .get(format!("{}/subscriptions/privacy", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(
+        "If we hold data about that address, a link to confirm the request has been sent to it."
+    ));
+    // leave the background task time to send something it should not
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
@@ -0,0 +1,174 @@
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+use chrono::Utc;
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn datetime_local(offset: chrono::Duration) -> String {
+    (Utc::now() + offset).format("%Y-%m-%dT%H:%M").to_string()
+}
+
+async fn schedule_newsletter(app: &TestApp, send_at: &str) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "send_at": send_at,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_delivered_before_send_at() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    schedule_newsletter(&app, &datetime_local(chrono::Duration::hours(1))).await;
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("The newsletter issue has been scheduled"));
+
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_scheduled_issues_html().await;
+    assert!(html_page.contains("Newsletter title"));
+}
+
+#[tokio::test]
+async fn rescheduled_issues_are_delivered_once_due() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id =
+        schedule_newsletter(&app, &datetime_local(chrono::Duration::hours(1))).await;
+    app.dispatch_all_pending_emails().await;
+
+    let response = app
+        .post_reschedule_issue(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+            "send_at": datetime_local(-chrono::Duration::minutes(1)),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let html_page = app.get_scheduled_issues_html().await;
+    assert!(html_page.contains("The issue has been rescheduled"));
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn scheduled_issues_reach_subscribers_who_confirmed_before_send_at() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let newsletter_issue_id =
+        schedule_newsletter(&app, &datetime_local(chrono::Duration::hours(1))).await;
+    let n_queued = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+
+    create_confirmed_subscriber(&app).await;
+    app.post_reschedule_issue(&serde_json::json!({
+        "newsletter_issue_id": newsletter_issue_id,
+        "send_at": datetime_local(-chrono::Duration::minutes(1)),
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn cancelled_issues_are_never_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id =
+        schedule_newsletter(&app, &datetime_local(chrono::Duration::hours(1))).await;
+
+    let response = app
+        .post_cancel_issue(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let html_page = app.get_scheduled_issues_html().await;
+    assert!(html_page.contains("The issue has been cancelled."));
+    assert!(!html_page.contains("Newsletter title"));
+
+    let n_queued = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+}
+
+#[tokio::test]
+async fn issues_already_due_cannot_be_cancelled() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let newsletter_issue_id =
+        schedule_newsletter(&app, &datetime_local(-chrono::Duration::minutes(1))).await;
+
+    let response = app
+        .post_cancel_issue(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let html_page = app.get_scheduled_issues_html().await;
+    assert!(html_page.contains("The issue is no longer pending"));
+}
+
+#[tokio::test]
+async fn login_required_to_see_scheduled_issues() {
+    let app = spawn_app().await;
+
+    let response = app.get_scheduled_issues().await;
+    assert_is_redirect_to(&response, "/login");
+}
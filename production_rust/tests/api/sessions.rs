@@ -0,0 +1,131 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+async fn get_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+// the id in the revoke form of the session opened from `user_agent`
+fn revocable_session_id(html_page: &str, user_agent: &str) -> String {
+    let row = html_page
+        .split("<tr>")
+        .find(|row| row.contains(user_agent))
+        .unwrap();
+    let (_, rest) = row.split_once(r#"name="session_id" value=""#).unwrap();
+    rest.split('"').next().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn every_session_of_the_user_is_listed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.second_session("Laptop/1.0 <script>").await;
+
+    let html_page = app.get_sessions_html().await;
+
+    assert!(html_page.contains("This session"));
+    assert!(html_page.contains("Laptop/1.0 &lt;script&gt;"));
+    assert!(html_page.contains("127.0.0.1"));
+    assert_eq!(html_page.matches(r#"name="session_id""#).count(), 1);
+}
+
+#[tokio::test]
+async fn expired_sessions_are_not_listed() {
+    let app = spawn_app_with(|c| c.application.session_ttl_seconds = 3).await;
+    app.second_session("Laptop/1.0").await;
+
+    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("This session"));
+    assert!(!html_page.contains("Laptop/1.0"));
+}
+
+#[tokio::test]
+async fn a_single_session_can_be_revoked() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let laptop = app.second_session("Laptop/1.0").await;
+    let phone = app.second_session("Phone/1.0").await;
+    let session_id = revocable_session_id(&app.get_sessions_html().await, "Laptop/1.0");
+
+    let response = app.post_revoke_session(&session_id).await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(app
+        .get_sessions_html()
+        .await
+        .contains("The session has been revoked."));
+
+    assert_is_redirect_to(&get_dashboard(&app, &laptop).await, "/login");
+    assert_eq!(get_dashboard(&app, &phone).await.status().as_u16(), 200);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_cannot_be_revoked() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_revoke_session(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    assert!(app
+        .get_sessions_html()
+        .await
+        .contains("No matching active session found."));
+}
+
+#[tokio::test]
+async fn all_other_sessions_can_be_revoked_at_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let laptop = app.second_session("Laptop/1.0").await;
+    let phone = app.second_session("Phone/1.0").await;
+
+    let response = app.post_revoke_other_sessions().await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("All your other sessions have been logged out."));
+    assert!(!html_page.contains("Laptop/1.0"));
+    assert_is_redirect_to(&get_dashboard(&app, &laptop).await, "/login");
+    assert_is_redirect_to(&get_dashboard(&app, &phone).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_the_other_sessions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let laptop = app.second_session("Laptop/1.0").await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "kH8#tw2!Pq9@zR4x",
+            "new_password_check": "kH8#tw2!Pq9@zR4x",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    assert_is_redirect_to(&get_dashboard(&app, &laptop).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
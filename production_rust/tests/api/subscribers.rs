@@ -0,0 +1,467 @@
+use crate::helpers::{assert_is_redirect_to, create_unconfirmed_subscriber, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: &str, subscribed_at: &str) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+        VALUES ($1, $2, $3, $4, $5::text::timestamptz)
+        "#,
+        subscriber_id,
+        email,
+        email.split('@').next().unwrap(),
+        status,
+        subscribed_at,
+    )
+    .execute(&app.pg_pool)
+    .await
+    .expect("Failed to insert a subscriber.");
+    subscriber_id
+}
+
+async fn subscriber_status(app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(&app.pg_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+// the query string of the next page link, unescaped
+fn next_page_query(html_page: &str) -> Option<String> {
+    let (_, rest) = html_page.split_once(r#"href="/admin/subscribers?"#)?;
+    Some(rest.split('"').next().unwrap().replace("&amp;", "&"))
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscribers("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "alice@example.com",
+        "confirmed",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+    insert_subscriber(&app, "bob@example.com", "confirmed", "2026-01-02T00:00:00Z").await;
+
+    let html_page = app.get_subscribers_html("q=ALI").await;
+    assert!(html_page.contains("alice@example.com"));
+    assert!(!html_page.contains("bob@example.com"));
+
+    let html_page = app.get_subscribers_html("q=nobody").await;
+    assert!(html_page.contains("No matching subscribers."));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_signup_date() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "old@example.com", "confirmed", "2025-06-01T12:00:00Z").await;
+    insert_subscriber(&app, "new@example.com", "confirmed", "2026-03-01T12:00:00Z").await;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        "2026-03-01T12:00:00Z",
+    )
+    .await;
+
+    let html_page = app
+        .get_subscribers_html("status=confirmed&signed_up_from=2026-01-01&signed_up_to=")
+        .await;
+    assert!(html_page.contains("new@example.com"));
+    assert!(!html_page.contains("old@example.com"));
+    assert!(!html_page.contains("pending@example.com"));
+
+    let html_page = app
+        .get_subscribers_html("q=&status=&signed_up_to=2026-03-01")
+        .await;
+    assert!(html_page.contains("old@example.com"));
+    assert!(html_page.contains("pending@example.com"));
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_subscribers("status=vip").await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_subscribers("signed_up_from=yesterday").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_are_paged_newest_first() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // two subscribers share a signup time, the id breaks the tie
+    for (email, subscribed_at) in [
+        ("first@example.com", "2026-01-01T00:00:00Z"),
+        ("second@example.com", "2026-01-02T00:00:00Z"),
+        ("third@example.com", "2026-01-02T00:00:00Z"),
+        ("fourth@example.com", "2026-01-03T00:00:00Z"),
+        ("ignored@other.org", "2026-01-04T00:00:00Z"),
+    ] {
+        insert_subscriber(&app, email, "confirmed", subscribed_at).await;
+    }
+
+    let mut seen = Vec::new();
+    let mut query = "q=example.com&per_page=2".to_string();
+    loop {
+        let html_page = app.get_subscribers_html(&query).await;
+        let page: Vec<_> = html_page
+            .split("<td>")
+            .filter_map(|cell| cell.split_once("@example.com</td>"))
+            .map(|(local, _)| local.to_string())
+            .collect();
+        assert!(page.len() <= 2);
+        seen.extend(page);
+        match next_page_query(&html_page) {
+            Some(next) => query = next,
+            None => break,
+        }
+    }
+
+    assert_eq!(seen.len(), 4);
+    assert_eq!(seen[0], "fourth");
+    assert_eq!(seen[3], "first");
+    assert!(seen.contains(&"second".to_string()) && seen.contains(&"third".to_string()));
+}
+
+#[tokio::test]
+async fn pending_subscribers_can_be_confirmed_manually() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+
+    let response = app.post_subscriber_action("confirm", subscriber_id).await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(app
+        .get_subscribers_html("")
+        .await
+        .contains("The subscriber has been confirmed."));
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("confirmed")
+    );
+
+    app.post_subscriber_action("confirm", subscriber_id).await;
+    assert!(app
+        .get_subscribers_html("")
+        .await
+        .contains("No matching pending subscriber found."));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_unsubscribed_from_the_console() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "reader@example.com",
+        "confirmed",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+
+    let response = app
+        .post_subscriber_action("unsubscribe", subscriber_id)
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(app
+        .get_subscribers_html("")
+        .await
+        .contains("The subscriber has been unsubscribed."));
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("unsubscribed")
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_be_deleted_with_their_tokens() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .id;
+
+    let response = app.post_subscriber_action("delete", subscriber_id).await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(app
+        .get_subscribers_html("")
+        .await
+        .contains("The subscriber has been deleted."));
+    assert_eq!(subscriber_status(&app, subscriber_id).await, None);
+    let n_tokens = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscription_tokens")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 0);
+}
+
+#[tokio::test]
+async fn deleted_subscribers_leave_no_delivery_history_behind() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "confirmed@example.com",
+        "confirmed",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures
+            (newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at)
+        SELECT newsletter_issue_id, subscriber_email, 3, 'Mailbox full', now()
+        FROM issue_delivery_queue
+        "#
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+
+    app.post_subscriber_action("delete", subscriber_id).await;
+
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM issue_delivery_queue) AS "queued!",
+            (SELECT count(*) FROM issue_delivery_failures) AS "failures!"
+        "#
+    )
+    .fetch_one(&app.pg_pool)
+    .await
+    .unwrap();
+    assert_eq!(counts.queued, 0);
+    assert_eq!(counts.failures, 0);
+}
+
+#[tokio::test]
+async fn confirmation_emails_can_be_resent_from_the_console() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriber_action("resend", subscriber_id).await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(app
+        .get_subscribers_html("")
+        .await
+        .contains("A new confirmation link has been sent."));
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_link.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await.as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[tokio::test]
+async fn imported_rows_are_validated_and_deduplicated() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "existing@example.com",
+        "confirmed",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+    let csv = "Name,Email,Source\n\
+        Ada Lovelace,ada@example.com,old provider\n\
+        Not An Address,not-an-email,old provider\n\
+        ,nameless@example.com,old provider\n\
+        Ada Again,ADA@example.com,old provider\n\
+        Existing,Existing@Example.com,old provider\n\
+        \"Hopper, Grace\",grace@example.com,old provider";
+
+    let response = app.post_import_subscribers(csv, "confirmed").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page
+        .contains("2 subscribers imported as confirmed, 2 duplicates skipped, 2 rows rejected."));
+    assert!(html_page.contains("<tr><td>3</td><td>not-an-email is not a valid subscriber email."));
+    assert!(html_page.contains("<tr><td>4</td>"));
+    let imported =
+        sqlx::query!("SELECT name, status FROM subscriptions WHERE email = 'grace@example.com'")
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap();
+    assert_eq!(imported.name, "Hopper, Grace");
+    assert_eq!(imported.status, "confirmed");
+    let n_unsubscribe_tokens =
+        sqlx::query!("SELECT count(*) AS \"count!\" FROM unsubscribe_tokens")
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap()
+            .count;
+    assert_eq!(n_unsubscribe_tokens, 2);
+}
+
+#[tokio::test]
+async fn imported_subscribers_can_be_asked_to_opt_in() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_import_subscribers("email,name\nada@example.com,Ada", "pending_confirmation")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app.wait_for_email().await;
+    let confirmation_link = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_link.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn imports_without_the_expected_columns_are_refused() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_import_subscribers("address,full_name\nada@example.com,Ada", "confirmed")
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers/import", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("The file needs an email and a name column."));
+    let n_subscribers = sqlx::query!("SELECT count(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv_filtered_by_status() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // more than a batch, the export spans several queries
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+        SELECT gen_random_uuid(), 'reader' || n || '@example.com', 'Reader ' || n, 'confirmed',
+            '2026-01-01'::timestamptz + n * interval '1 minute'
+        FROM generate_series(1, 501) AS n
+        "#
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        "2026-01-01T00:00:00Z",
+    )
+    .await;
+
+    let response = app.get_subscribers_export("status=confirmed").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 502);
+    assert_eq!(lines[0], "email,name,status,subscribed_at");
+    assert_eq!(
+        lines[1],
+        "reader1@example.com,Reader 1,confirmed,2026-01-01T00:01:00Z"
+    );
+    assert!(lines[501].starts_with("reader501@example.com,"));
+    assert!(!csv.contains("pending@example.com"));
+
+    let response = app.get_subscribers_export("status=vip").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
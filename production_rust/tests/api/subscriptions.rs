@@ -1,4 +1,5 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+use uuid::Uuid;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -11,8 +12,8 @@ async fn subscribe_endpoint_is_accessable() {
     assert!(html_page.contains("<h2>Subscribe</h2>"));
 }
 
-#[tokio::test] // valid form data returns 200
-async fn valid_subscribe_returns_200() {
+#[tokio::test] // valid form data redirects back to the form
+async fn valid_subscribe_redirects_with_a_confirmation_message() {
     let app = spawn_app().await; // Future
 
     let body = "name=Aeonid%20Thiel&email=calth_invigilatus%40gmail.com";
@@ -25,7 +26,9 @@ async fn valid_subscribe_returns_200() {
 
     let response = app.post_subscribers(body.into()).await;
 
-    assert_eq!(200, response.status().as_u16());
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains("<p><i>You are now subscribed!</i></p>"));
 }
 
 #[tokio::test]
@@ -53,7 +56,7 @@ async fn valid_subscriber_persists() {
 }
 
 #[tokio::test]
-async fn invalid_subscribe_returns_400_empty_fields() {
+async fn invalid_subscribe_flashes_an_error_for_empty_fields() {
     let app = spawn_app().await;
 
     let test_cases = vec![
@@ -65,13 +68,20 @@ async fn invalid_subscribe_returns_400_empty_fields() {
     for (invalid_body, error_message) in test_cases {
         let response = app.post_subscribers(invalid_body.into()).await;
 
-        assert_eq!(
-            400,
-            response.status().as_u16(),
-            "The API did not return with 400: {}.",
+        assert_is_redirect_to(&response, "/subscriptions");
+        let html_page = app.get_subscribe_html().await;
+        assert!(
+            html_page.contains("is not a valid"),
+            "The form did not flash an error: {}.",
             error_message,
         );
     }
+    let n_saved = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_saved, 0);
 }
 
 #[tokio::test] // Parametrized Test: missing form data returns 400
@@ -148,4 +158,84 @@ async fn subscribe_fails_with_fatal_database_error() {
     let response = app.post_subscribers(body.into()).await;
 
     assert_eq!(response.status().as_u16(), 500);
+    // the JSON error body is reserved for the API
+    assert_ne!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+}
+
+#[tokio::test]
+async fn subscribing_again_while_pending_sends_a_new_link() {
+    let app = spawn_app().await;
+    let body = "name=Aeonid%20Thiel&email=calth_invigilatus%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscribers(body.into()).await;
+    let response = app.post_subscribers(body.into()).await;
+
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains("<p><i>You are now subscribed!</i></p>"));
+    let first_link = app.get_confirmation_links(&app.wait_for_nth_email(1).await);
+    let second_link = app.get_confirmation_links(&app.wait_for_nth_email(2).await);
+    assert_ne!(first_link.html_link, second_link.html_link);
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_sends_nothing() {
+    let app = spawn_app().await;
+    let body = "name=Aeonid%20Thiel&email=calth_invigilatus%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscribers(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_link.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = app.post_subscribers(body.into()).await;
+
+    // the same answer as for a new address
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains("<p><i>You are now subscribed!</i></p>"));
+    // leave the background task time to send something it should not
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
+
+#[tokio::test]
+async fn subscribing_again_while_pending_is_throttled_per_address() {
+    let app = spawn_app_with(|c| c.rate_limits.confirmation_resend.max_requests = 2).await;
+    // requests are throttled in Redis, which outlives the test apps
+    let body = format!("name=Aeonid%20Thiel&email={}%40example.com", Uuid::new_v4());
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..5 {
+        let response = app.post_subscribers(body.clone()).await;
+        assert_is_redirect_to(&response, "/subscriptions");
+        let html_page = app.get_subscribe_html().await;
+        assert!(html_page.contains("<p><i>You are now subscribed!</i></p>"));
+    }
+
+    // the first link and two new ones, whatever happened to the others
+    app.wait_for_nth_email(3).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 3);
 }
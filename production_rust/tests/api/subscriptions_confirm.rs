@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    assert_is_redirect_to, create_unconfirmed_subscriber, spawn_app, spawn_app_with, TestApp,
+};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -27,7 +29,7 @@ async fn valid_link_200() {
 
     app.post_subscribers(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_link = app.get_confirmation_links(&email_request);
+    let confirmation_link = app.get_confirmation_links(email_request);
 
     let response = reqwest::get(confirmation_link.html_link).await.unwrap();
 
@@ -47,7 +49,7 @@ async fn valid_link_confirms_subscriber() {
 
     app.post_subscribers(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_link = app.get_confirmation_links(&email_request);
+    let confirmation_link = app.get_confirmation_links(email_request);
 
     reqwest::get(confirmation_link.html_link)
         .await
@@ -64,3 +66,168 @@ async fn valid_link_confirms_subscriber() {
     assert_eq!(saved.name, "Aeonid Thiel");
     assert_eq!(saved.status, "confirmed");
 }
+
+/// Requests are throttled in Redis, which outlives the test apps.
+fn random_address() -> String {
+    format!("{}@example.com", uuid::Uuid::new_v4())
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to fetch saved subscription.")
+        .status
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected_with_a_resend_form() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(&format!(
+        "{}/subscriptions/confirm?subscription_token=doesnotexist",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("This confirmation link is invalid or has already been used."));
+    assert!(html_page.contains(r#"action="/subscriptions/confirm/resend""#));
+}
+
+#[tokio::test]
+async fn confirmation_links_cannot_be_used_twice() {
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+
+    let response = reqwest::get(confirmation_link.html_link.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reqwest::get(confirmation_link.html_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn expired_links_are_rejected_and_leave_the_subscriber_pending() {
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '49 hours'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link.html_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 410);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("This confirmation link has expired."));
+    assert!(html_page.contains(r#"action="/subscriptions/confirm/resend""#));
+    assert_eq!(subscriber_status(&app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn links_of_subscribers_who_unsubscribed_are_rejected() {
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link.html_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let html_page = response.text().await.unwrap();
+    assert!(!html_page.contains("your subscription is confirmed"));
+    assert_eq!(subscriber_status(&app).await, "unsubscribed");
+}
+
+#[tokio::test]
+async fn resent_links_confirm_the_subscriber() {
+    let app = spawn_app().await;
+    let email = random_address();
+    let body = format!("name=Aeonid%20Thiel&email={}", urlencoding::encode(&email));
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscribers(body).await;
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '49 hours'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app.post_resend_confirmation(&email).await;
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains(
+        "If that address is awaiting confirmation, a new confirmation link has been sent to it."
+    ));
+
+    // the new link is sent in the background
+    let email_request = loop {
+        let mut requests = app.email_server.received_requests().await.unwrap();
+        if requests.len() == 2 {
+            break requests.pop().unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+    let confirmation_link = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_link.html_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn resending_to_an_unknown_address_sends_nothing() {
+    let app = spawn_app().await;
+    let nobody = random_address();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_resend_confirmation(&nobody).await;
+
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_subscribe_html().await;
+    assert!(html_page.contains(
+        "If that address is awaiting confirmation, a new confirmation link has been sent to it."
+    ));
+    // leave the background task time to send something it should not
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
+
+#[tokio::test]
+async fn resend_requests_are_throttled_per_address() {
+    let app = spawn_app_with(|c| c.rate_limits.confirmation_resend.max_requests = 3).await;
+    let nobody = random_address();
+
+    for _ in 0..3 {
+        app.post_resend_confirmation(&nobody).await;
+        assert!(app.get_subscribe_html().await.contains(
+            "If that address is awaiting confirmation, a new confirmation link has been sent to it."
+        ));
+    }
+    let response = app.post_resend_confirmation(&nobody).await;
+    assert_is_redirect_to(&response, "/subscriptions");
+    assert!(app
+        .get_subscribe_html()
+        .await
+        .contains("Too many requests, please try again later."));
+
+    // other addresses are not held back
+    app.post_resend_confirmation(&random_address()).await;
+    assert!(app.get_subscribe_html().await.contains(
+        "If that address is awaiting confirmation, a new confirmation link has been sent to it."
+    ));
+}
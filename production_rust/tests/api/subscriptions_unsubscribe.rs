@@ -0,0 +1,179 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn list_unsubscribe_link(app: &TestApp, email_request: &wiremock::Request) -> reqwest::Url {
+    let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let header = |name: &str| {
+        email_body["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == name)
+            .and_then(|h| h["Value"].as_str())
+            .unwrap()
+            .to_owned()
+    };
+    assert_eq!(
+        header("List-Unsubscribe-Post"),
+        "List-Unsubscribe=One-Click"
+    );
+
+    let raw_link = header("List-Unsubscribe");
+    let link = reqwest::Url::parse(raw_link.trim_matches(|c| c == '<' || c == '>')).unwrap();
+    assert_eq!(link.port(), Some(app.port));
+    link
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn reject_tokenless_unsubscribe_400() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(&format!("{}/subscriptions/unsubscribe", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn reject_unknown_unsubscribe_token_404() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            app.address,
+            app.unsubscribe_links.sign("unknown")
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn reject_unsigned_unsubscribe_token_404() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM unsubscribe_tokens")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+
+    for forged in [
+        unsubscribe_token.clone(),
+        format!("{}.not-the-signature", unsubscribe_token),
+    ] {
+        let response = reqwest::Client::new()
+            .post(format!(
+                "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+                app.address, forged
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn newsletter_carries_list_unsubscribe_headers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let link = list_unsubscribe_link(&app, &email_request);
+
+    let response = reqwest::get(link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Unsubscribe"));
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_stops_newsletter_delivery() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .named("First issue")
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let link = list_unsubscribe_link(&app, &email_request);
+
+    let response = reqwest::Client::new()
+        .post(link)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+
+    app.email_server.reset().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+}
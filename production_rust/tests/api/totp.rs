@@ -0,0 +1,161 @@
+use crate::helpers::{assert_is_redirect_to, enable_totp, spawn_app, spawn_app_with, totp_code_at};
+
+#[tokio::test]
+async fn enrollment_shows_a_provisioning_uri_and_recovery_codes() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_enroll_totp().await;
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = app.get_totp_settings_html().await;
+    assert!(html_page.contains("otpauth://totp/Newsletter:"));
+
+    let response = app.post_confirm_totp("000000").await;
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = app.get_totp_settings_html().await;
+    assert!(html_page.contains("<p><i>Invalid authentication code.</i></p>"));
+
+    let (secret, recovery_codes) = enable_totp(&app).await;
+    assert_eq!(recovery_codes.len(), 8);
+    let html_page = app.get_totp_settings_html().await;
+    assert!(html_page.contains("Two-factor authentication is on."));
+
+    let saved = sqlx::query!("SELECT encrypted_secret, confirmed FROM totp_credentials")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert!(saved.confirmed);
+    assert!(!saved
+        .encrypted_secret
+        .windows(secret.len())
+        .any(|w| w == secret.as_slice()));
+}
+
+#[tokio::test]
+async fn a_correct_password_alone_does_not_grant_access() {
+    let app = spawn_app().await;
+    enable_totp(&app).await;
+    app.post_logout().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/totp");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_login_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_valid_code_completes_the_login_but_cannot_be_replayed() {
+    let app = spawn_app().await;
+    let (secret, _) = enable_totp(&app).await;
+    app.post_logout().await;
+
+    app.test_user.login(&app).await;
+    let code = totp_code_at(&secret, 1);
+    let response = app.post_login_totp(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&code).await;
+    assert_is_redirect_to(&response, "/login/totp");
+}
+
+#[tokio::test]
+async fn recovery_codes_can_be_used_once() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enable_totp(&app).await;
+    app.post_logout().await;
+
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/totp");
+    let response = app.post_login_totp(&recovery_codes[1]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn repeated_invalid_codes_require_the_password_again() {
+    // no delays, they would turn the codes away before the session does
+    let app = spawn_app_with(|c| c.login_throttle.free_attempts = 10).await;
+    enable_totp(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..4 {
+        let response = app.post_login_totp("000000").await;
+        assert_is_redirect_to(&response, "/login/totp");
+    }
+    let response = app.post_login_totp("000000").await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_login_totp().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invalid_codes_lock_the_account_across_password_logins() {
+    let app = spawn_app_with(|c| {
+        c.login_throttle.free_attempts = 10;
+        c.login_throttle.max_failures = 7;
+    })
+    .await;
+    enable_totp(&app).await;
+    app.post_logout().await;
+
+    app.test_user.login(&app).await;
+    for _ in 0..5 {
+        app.post_login_totp("000000").await;
+    }
+    // a fresh password login does not start the count again
+    app.test_user.login(&app).await;
+    app.post_login_totp("000000").await;
+    let response = app.post_login_totp("000000").await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Too many failed login attempts, try again in 900 seconds."));
+
+    // not even the right password gets through now
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn totp_can_be_turned_off_with_a_code() {
+    let app = spawn_app().await;
+    let (secret, _) = enable_totp(&app).await;
+
+    let response = app.post_disable_totp(&totp_code_at(&secret, 1)).await;
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = app.get_totp_settings_html().await;
+    assert!(html_page.contains("<p><i>Two-factor authentication has been turned off.</i></p>"));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
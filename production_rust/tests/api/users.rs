@@ -0,0 +1,215 @@
+use crate::helpers::{assert_is_redirect_to, invite_user, spawn_app};
+use uuid::Uuid;
+
+fn draft_body(action: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "action": action,
+    })
+}
+
+#[tokio::test]
+async fn invited_users_can_log_in() {
+    let app = spawn_app().await;
+
+    let (_, credentials) = invite_user(&app, "viewer").await;
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains(&format!(
+        "<td>{}</td>",
+        credentials["username"].as_str().unwrap()
+    )));
+    assert!(html_page.contains("<td>viewer</td>"));
+
+    let response = app.post_login(&credentials).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn usernames_must_be_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": &app.test_user.username,
+            "role": "editor",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains("<p><i>That username is already taken.</i></p>"));
+}
+
+#[tokio::test]
+async fn editors_can_draft_but_not_publish() {
+    let app = spawn_app().await;
+    let (_, credentials) = invite_user(&app, "editor").await;
+    app.post_login(&credentials).await;
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(!html_page.contains(r#"value="publish""#));
+
+    let response = app.post_publish_newsletter(&draft_body("publish")).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_publish_newsletter(&draft_body("save_draft")).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let status = sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "draft");
+}
+
+#[tokio::test]
+async fn viewers_are_read_only() {
+    let app = spawn_app().await;
+    let (_, credentials) = invite_user(&app, "viewer").await;
+    app.post_login(&credentials).await;
+
+    let response = app.get_scheduled_issues().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_publish_newsletter(&draft_body("save_draft")).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users_and_keys() {
+    let app = spawn_app().await;
+    let (_, credentials) = invite_user(&app, "editor").await;
+    app.post_login(&credentials).await;
+
+    let response = app.get_manage_users().await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.get_manage_settings().await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app
+        .post_invite_user(&serde_json::json!({ "username": "intruder", "role": "owner" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn disabled_users_are_logged_out_and_cannot_log_back_in() {
+    let app = spawn_app().await;
+    let (user_id, credentials) = invite_user(&app, "editor").await;
+
+    let response = app
+        .post_disable_user(&serde_json::json!({ "user_id": user_id, "disabled": true }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been disabled.</i></p>"));
+
+    let response = app.post_login(&credentials).await;
+    assert_is_redirect_to(&response, "/login");
+
+    sqlx::query!(
+        "UPDATE users SET disabled = false WHERE user_id = $1",
+        user_id
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+    app.post_login(&credentials).await;
+    sqlx::query!(
+        "UPDATE users SET disabled = true WHERE user_id = $1",
+        user_id
+    )
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn disabling_a_user_revokes_their_api_tokens_and_sessions() {
+    let app = spawn_app().await;
+    let (user_id, credentials) = invite_user(&app, "owner").await;
+    // the invited user logs in from their own browser and creates a token
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    client
+        .post(format!("{}/login", &app.address))
+        .form(&credentials)
+        .send()
+        .await
+        .unwrap();
+    let html_page = client
+        .post(format!("{}/admin/tokens", &app.address))
+        .form(&[("name", "CI"), ("scope", "subscribers:read")])
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let start = html_page.find("<code>").unwrap() + "<code>".len();
+    let end = html_page.find("</code>").unwrap();
+    let token = &html_page[start..end];
+
+    app.post_disable_user(&serde_json::json!({ "user_id": user_id, "disabled": true }))
+        .await;
+
+    let response = app
+        .bearer_client(token)
+        .get(format!("{}/api/v1/subscribers", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deleted_users_are_removed() {
+    let app = spawn_app().await;
+    let (user_id, credentials) = invite_user(&app, "viewer").await;
+
+    let response = app
+        .post_delete_user(&serde_json::json!({ "user_id": user_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been deleted.</i></p>"));
+    assert!(!html_page.contains(credentials["username"].as_str().unwrap()));
+    let response = app.post_login(&credentials).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_cannot_lock_themselves_out() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_disable_user(&serde_json::json!({
+        "user_id": app.test_user.user_id,
+        "disabled": true,
+    }))
+    .await;
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot disable your own account.</i></p>"));
+
+    app.post_delete_user(&serde_json::json!({ "user_id": app.test_user.user_id }))
+        .await;
+    let html_page = app.get_manage_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot delete your own account.</i></p>"));
+}